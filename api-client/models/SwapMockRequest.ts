@@ -19,6 +19,18 @@ import { mapValues } from '../runtime';
  * @interface SwapMockRequest
  */
 export interface SwapMockRequest {
+    /**
+     * 
+     * @type {string}
+     * @memberof SwapMockRequest
+     */
+    fromToken: string;
+    /**
+     * 
+     * @type {string}
+     * @memberof SwapMockRequest
+     */
+    toToken: string;
     /**
      * Amount to swap (decimal as string)
      * @type {string}
@@ -26,19 +38,26 @@ export interface SwapMockRequest {
      */
     amountIn: string;
     /**
-     * Execution price (decimal as string)
+     * Price source name; the default source without it
+     * @type {string}
+     * @memberof SwapMockRequest
+     */
+    source?: string;
+    /**
+     * Minimum acceptable output (decimal as string); the swap fails below it
      * @type {string}
      * @memberof SwapMockRequest
      */
-    price: string;
+    minAmountOut?: string;
 }
 
 /**
  * Check if a given object implements the SwapMockRequest interface.
  */
 export function instanceOfSwapMockRequest(value: object): value is SwapMockRequest {
+    if (!('fromToken' in value) || value['fromToken'] === undefined) return false;
+    if (!('toToken' in value) || value['toToken'] === undefined) return false;
     if (!('amountIn' in value) || value['amountIn'] === undefined) return false;
-    if (!('price' in value) || value['price'] === undefined) return false;
     return true;
 }
 
@@ -52,8 +71,11 @@ export function SwapMockRequestFromJSONTyped(json: any, ignoreDiscriminator: boo
     }
     return {
         
+        'fromToken': json['from_token'],
+        'toToken': json['to_token'],
         'amountIn': json['amount_in'],
-        'price': json['price'],
+        'source': json['source'] == null ? undefined : json['source'],
+        'minAmountOut': json['min_amount_out'] == null ? undefined : json['min_amount_out'],
     };
 }
 
@@ -68,8 +90,11 @@ export function SwapMockRequestToJSONTyped(value?: SwapMockRequest | null, ignor
 
     return {
         
+        'from_token': value['fromToken'],
+        'to_token': value['toToken'],
         'amount_in': value['amountIn'],
-        'price': value['price'],
+        'source': value['source'],
+        'min_amount_out': value['minAmountOut'],
     };
 }
 
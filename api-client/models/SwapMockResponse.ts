@@ -26,11 +26,23 @@ export interface SwapMockResponse {
      */
     amountOut?: string;
     /**
-     * Execution price (decimal as string)
+     * Execution price from the source (decimal as string)
      * @type {string}
      * @memberof SwapMockResponse
      */
     price?: string;
+    /**
+     * Name of the price source
+     * @type {string}
+     * @memberof SwapMockResponse
+     */
+    source?: string;
+    /**
+     * Time of the price (unix time, milliseconds)
+     * @type {number}
+     * @memberof SwapMockResponse
+     */
+    timestampMs?: number;
 }
 
 /**
@@ -52,6 +64,8 @@ export function SwapMockResponseFromJSONTyped(json: any, ignoreDiscriminator: bo
         
         'amountOut': json['amount_out'] == null ? undefined : json['amount_out'],
         'price': json['price'] == null ? undefined : json['price'],
+        'source': json['source'] == null ? undefined : json['source'],
+        'timestampMs': json['timestamp_ms'] == null ? undefined : json['timestamp_ms'],
     };
 }
 
@@ -68,6 +82,8 @@ export function SwapMockResponseToJSONTyped(value?: SwapMockResponse | null, ign
         
         'amount_out': value['amountOut'],
         'price': value['price'],
+        'source': value['source'],
+        'timestamp_ms': value['timestampMs'],
     };
 }
 
@@ -45,6 +45,7 @@ fuzz_target!(|data: &[u8]| {
             let swap_req = SwapMockRequest {
                 amount_in,
                 price,
+                min_amount_out: None,
             };
             
             // Просто создаем объект для проверки сериализации
@@ -16,6 +16,7 @@ fuzz_target!(|data: &[u8]| {
             let req = SwapMockRequest {
                 amount_in,
                 price,
+                min_amount_out: None,
             };
             
             // Тестируем парсинг и вычисления
@@ -0,0 +1,344 @@
+//! Конфигурация сервера: TOML-файл из `SMARTSWAP_CONFIG` (без него — значения по умолчанию)
+//! и переопределения из переменных окружения. Проверяется при старте, до приёма запросов.
+use serde::{Deserialize, Serialize};
+use smartswap_core::price_source::Exchange;
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub pricing: PricingConfig,
+    /// Пары `BASE/QUOTE`, которые источники котируют напрямую: граф кросс-курсов и поток цен
+    pub markets: Vec<String>,
+    /// Сеть развёртывания: по ней реестр выбирает токены (decimals USDT в Ethereum и BSC разные);
+    /// без неё — сеть первого токена реестра
+    pub chain_id: Option<u64>,
+    pub paths: PathsConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind: String,
+    /// Разрешённые Origin; `*` — любой
+    pub cors_origins: Vec<String>,
+    pub price_stream_interval_secs: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PricingConfig {
+    pub default_source: String,
+    pub sources: BTreeMap<String, SourceConfig>,
+    /// Промежуточные токены кросс-курсов в порядке предпочтения
+    pub intermediates: Vec<String>,
+    /// Цена старше отклоняется
+    pub max_price_age_secs: u64,
+    /// Скачок больше (в процентах) требует подтверждения
+    pub max_jump_pct: f64,
+    /// Источник, которым подтверждаются скачки цен остальных источников (например, `chainlink`);
+    /// без него скачок останавливает котирование пары
+    pub confirmation_source: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PathsConfig {
+    /// JSON-массив токенов; без него — встроенный реестр
+    pub token_registry: Option<PathBuf>,
+    /// Каталог журнала всех отданных цен (`prices.jsonl` с ротацией)
+    pub price_record_dir: Option<PathBuf>,
+}
+
+/// Источник цен и его параметры; `type` выбирает вариант.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum SourceConfig {
+    Mock,
+    /// Фиксированные цены `{"ETH/USDT" = 3200.0}`, обратные пары — автоматически
+    Static { prices: BTreeMap<String, f64> },
+    Coingecko {
+        base_url: Option<String>,
+        api_key: Option<String>,
+    },
+    Cex {
+        exchange: Exchange,
+        base_url: Option<String>,
+    },
+    /// Пул V2 с ноды (`rpc_url`) или из JSON-снимков (`pool_state_file`) — ровно одно из двух.
+    /// Decimals по умолчанию — из реестра токенов сети `chain_id`
+    UniswapV2 {
+        pool: String,
+        token0: String,
+        token1: String,
+        decimals0: Option<u8>,
+        decimals1: Option<u8>,
+        rpc_url: Option<String>,
+        pool_state_file: Option<PathBuf>,
+    },
+    /// История цен (CSV/JSONL) по часам, идущим со скоростью `speed` от первой записи
+    Replay {
+        path: PathBuf,
+        #[serde(default = "default_replay_speed")]
+        speed: f64,
+    },
+    /// Агрегаторы Chainlink на ноде `rpc_url`: `{"ETH/USD" = { address = "0x…", heartbeat_secs = 3600 }}`.
+    /// Символы вне реестра токенов (USD) — как есть
+    Chainlink {
+        rpc_url: String,
+        feeds: BTreeMap<String, ChainlinkFeedConfig>,
+    },
+    /// Первая цена из других источников этой конфигурации по порядку `sources`,
+    /// каждый ограничен `timeout_ms` (например `["uniswap", "coingecko", "fixed"]`)
+    Fallback {
+        sources: Vec<String>,
+        #[serde(default = "default_fallback_timeout_ms")]
+        timeout_ms: u64,
+    },
+}
+
+/// Фид Chainlink: адрес агрегатора и период его обновления.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChainlinkFeedConfig {
+    pub address: String,
+    pub heartbeat_secs: u64,
+}
+
+fn default_replay_speed() -> f64 {
+    1.0
+}
+
+fn default_fallback_timeout_ms() -> u64 {
+    2000
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            server: ServerConfig::default(),
+            pricing: PricingConfig::default(),
+            markets: vec!["ETH/USDT".to_string(), "WBTC/USDT".to_string()],
+            chain_id: None,
+            paths: PathsConfig::default(),
+        }
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind: "127.0.0.1:8088".to_string(),
+            cors_origins: vec!["http://localhost:3000".to_string()],
+            price_stream_interval_secs: 5,
+        }
+    }
+}
+
+impl Default for PricingConfig {
+    fn default() -> Self {
+        Self {
+            default_source: "mock".to_string(),
+            sources: BTreeMap::from([("mock".to_string(), SourceConfig::Mock)]),
+            intermediates: vec!["USDT".to_string()],
+            max_price_age_secs: 60,
+            max_jump_pct: 10.0,
+            confirmation_source: None,
+        }
+    }
+}
+
+impl Config {
+    /// `.env`, затем файл `SMARTSWAP_CONFIG`, затем переопределения из окружения и проверка.
+    pub fn load() -> Result<Self, String> {
+        dotenv::dotenv().ok();
+        let config = match std::env::var("SMARTSWAP_CONFIG") {
+            Ok(path) => Self::from_file(&path)?,
+            Err(_) => Self::default(),
+        };
+        let config = config.with_env(|name| std::env::var(name).ok())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let raw = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read config {}: {e}", path.display()))?;
+        Self::from_toml(&raw).map_err(|e| format!("Invalid config {}: {e}", path.display()))
+    }
+
+    pub fn from_toml(raw: &str) -> Result<Self, String> {
+        toml::from_str(raw).map_err(|e| e.to_string())
+    }
+
+    /// Переопределения из окружения (`var` — обычно `std::env::var`):
+    /// `SMARTSWAP_BIND`, `SMARTSWAP_CORS_ORIGINS` и `SMARTSWAP_MARKETS` (через запятую),
+    /// `PRICE_SOURCE`, `CHAIN_ID`, `TOKEN_REGISTRY_PATH`, `PRICE_RECORD_DIR`,
+    /// `PRICE_REPLAY_PATH` и `PRICE_REPLAY_SPEED` (источник `replay` становится основным).
+    pub fn with_env(mut self, var: impl Fn(&str) -> Option<String>) -> Result<Self, String> {
+        let list = |value: String| value.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect();
+        if let Some(bind) = var("SMARTSWAP_BIND") {
+            self.server.bind = bind;
+        }
+        if let Some(origins) = var("SMARTSWAP_CORS_ORIGINS") {
+            self.server.cors_origins = list(origins);
+        }
+        if let Some(markets) = var("SMARTSWAP_MARKETS") {
+            self.markets = list(markets);
+        }
+        if let Some(source) = var("PRICE_SOURCE") {
+            self.pricing.default_source = source;
+        }
+        if let Some(chain_id) = var("CHAIN_ID") {
+            self.chain_id = Some(chain_id.parse().map_err(|_| format!("Invalid CHAIN_ID {chain_id}"))?);
+        }
+        if let Some(path) = var("TOKEN_REGISTRY_PATH") {
+            self.paths.token_registry = Some(path.into());
+        }
+        if let Some(dir) = var("PRICE_RECORD_DIR") {
+            self.paths.price_record_dir = Some(dir.into());
+        }
+        if let Some(path) = var("PRICE_REPLAY_PATH") {
+            let speed = match var("PRICE_REPLAY_SPEED") {
+                Some(speed) => speed.parse().map_err(|_| format!("Invalid PRICE_REPLAY_SPEED {speed}"))?,
+                None => default_replay_speed(),
+            };
+            self.pricing.sources.insert("replay".to_string(), SourceConfig::Replay { path: path.into(), speed });
+            self.pricing.default_source = "replay".to_string();
+        }
+        Ok(self)
+    }
+
+    /// Проверки, не требующие файлов и реестра токенов; все ошибки сразу.
+    /// Символы рынков и пулов проверяет `AppState::from_config` по загруженному реестру.
+    pub fn validate(&self) -> Result<(), String> {
+        let mut errors = Vec::new();
+        if self.server.bind.parse::<SocketAddr>().is_err() {
+            errors.push(format!("server.bind: {} is not a host:port address", self.server.bind));
+        }
+        for origin in &self.server.cors_origins {
+            if origin != "*" && !(origin.starts_with("http://") || origin.starts_with("https://")) {
+                errors.push(format!("server.cors_origins: {origin} must be * or start with http:// or https://"));
+            }
+        }
+        if self.server.price_stream_interval_secs == 0 {
+            errors.push("server.price_stream_interval_secs must be positive".to_string());
+        }
+        if !self.pricing.sources.contains_key(&self.pricing.default_source) {
+            errors.push(format!(
+                "pricing.default_source: {} is not one of the configured sources ({})",
+                self.pricing.default_source,
+                self.pricing.sources.keys().cloned().collect::<Vec<_>>().join(", ")
+            ));
+        }
+        if let Some(confirmation) = &self.pricing.confirmation_source {
+            if !self.pricing.sources.contains_key(confirmation) {
+                errors.push(format!("pricing.confirmation_source: {confirmation} is not one of the configured sources"));
+            } else if *confirmation == self.pricing.default_source {
+                errors.push(format!("pricing.confirmation_source: {confirmation} must differ from pricing.default_source"));
+            }
+        }
+        if !self.pricing.max_jump_pct.is_finite() || self.pricing.max_jump_pct <= 0.0 {
+            errors.push("pricing.max_jump_pct must be positive".to_string());
+        }
+        for market in &self.markets {
+            if parse_pair(market).is_none() {
+                errors.push(format!("markets: {market} is not BASE/QUOTE"));
+            }
+        }
+        for (name, source) in &self.pricing.sources {
+            source.validate(&format!("pricing.sources.{name}"), &mut errors);
+            // Звенья цепочки — другие источники, не цепочки
+            if let SourceConfig::Fallback { sources, .. } = source {
+                for member in sources {
+                    match self.pricing.sources.get(member) {
+                        None => errors.push(format!("pricing.sources.{name}.sources: {member} is not one of the configured sources")),
+                        Some(SourceConfig::Fallback { .. }) => {
+                            errors.push(format!("pricing.sources.{name}.sources: {member} is a fallback chain itself"))
+                        }
+                        Some(_) => {}
+                    }
+                }
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("Invalid config: {}", errors.join("; ")))
+        }
+    }
+
+    /// Рынки парами `(base, quote)`; форматы проверены `validate`.
+    pub fn market_pairs(&self) -> Vec<(&str, &str)> {
+        self.markets.iter().filter_map(|m| parse_pair(m)).collect()
+    }
+}
+
+impl SourceConfig {
+    fn validate(&self, at: &str, errors: &mut Vec<String>) {
+        let is_url = |url: &str| url.starts_with("http://") || url.starts_with("https://");
+        match self {
+            SourceConfig::Mock => {}
+            SourceConfig::Static { prices } => {
+                for (pair, price) in prices {
+                    if parse_pair(pair).is_none() {
+                        errors.push(format!("{at}.prices: {pair} is not BASE/QUOTE"));
+                    }
+                    if !price.is_finite() || *price <= 0.0 {
+                        errors.push(format!("{at}.prices.{pair}: price must be positive"));
+                    }
+                }
+            }
+            SourceConfig::Coingecko { base_url, .. } | SourceConfig::Cex { base_url, .. } => {
+                if let Some(url) = base_url.as_deref().filter(|url| !is_url(url)) {
+                    errors.push(format!("{at}.base_url: {url} is not an http(s) URL"));
+                }
+            }
+            SourceConfig::UniswapV2 { rpc_url, pool_state_file, .. } => match (rpc_url, pool_state_file) {
+                (Some(url), None) if !is_url(url) => errors.push(format!("{at}.rpc_url: {url} is not an http(s) URL")),
+                (Some(_), None) | (None, Some(_)) => {}
+                _ => errors.push(format!("{at}: set exactly one of rpc_url and pool_state_file")),
+            },
+            SourceConfig::Replay { speed, .. } => {
+                if !speed.is_finite() || *speed < 0.0 {
+                    errors.push(format!("{at}.speed must be zero or positive"));
+                }
+            }
+            SourceConfig::Fallback { sources, timeout_ms } => {
+                if sources.is_empty() {
+                    errors.push(format!("{at}.sources must not be empty"));
+                }
+                if *timeout_ms == 0 {
+                    errors.push(format!("{at}.timeout_ms must be positive"));
+                }
+            }
+            SourceConfig::Chainlink { rpc_url, feeds } => {
+                if !is_url(rpc_url) {
+                    errors.push(format!("{at}.rpc_url: {rpc_url} is not an http(s) URL"));
+                }
+                if feeds.is_empty() {
+                    errors.push(format!("{at}.feeds must not be empty"));
+                }
+                for (pair, feed) in feeds {
+                    if parse_pair(pair).is_none() {
+                        errors.push(format!("{at}.feeds: {pair} is not BASE/QUOTE"));
+                    }
+                    if feed.heartbeat_secs == 0 {
+                        errors.push(format!("{at}.feeds.{pair}.heartbeat_secs must be positive"));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// `ETH/USDT` → `("ETH", "USDT")`.
+pub fn parse_pair(pair: &str) -> Option<(&str, &str)> {
+    let (base, quote) = pair.split_once('/')?;
+    let (base, quote) = (base.trim(), quote.trim());
+    (!base.is_empty() && !quote.is_empty() && base != quote).then_some((base, quote))
+}
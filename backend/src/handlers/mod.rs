@@ -1,9 +1,10 @@
 use actix_web::{web, HttpResponse, Responder};
 use crate::state::AppState;
 use self::types::{AddOrderRequest, DeleteOrderRequest, SwapMockRequest, QuoteQuery};
-use smartswap_core::swap_engine::SwapEngine;
+use smartswap_core::swap_engine::{SwapEngine, SwapError, SwapMode, DEFAULT_SLIPPAGE_BPS};
 use smartswap_core::pricing;
-use smartswap_core::price_source::PriceSource;
+use smartswap_core::price_source::resilient::CircuitState;
+use smartswap_core::price_source::{now_ms, ExactOutPrice, TimedPrice};
 
 pub mod types;
 
@@ -12,11 +13,55 @@ pub struct PriceSourceQuery {
     pub from: String,
     pub to: String,
     pub amount: Option<String>,
+    /// Имя источника из `AppState::price_sources`; без него — источник по умолчанию
+    pub source: Option<String>,
 }
 
+#[derive(serde::Deserialize)]
+pub struct PricePair {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct BatchPriceRequest {
+    pub pairs: Vec<PricePair>,
+    #[serde(default)]
+    pub source: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct PriceStreamQuery {
+    /// Пары через запятую: `ETH/USDT,WBTC/USDT`
+    pub pairs: String,
+}
+
+/// Лимит пар в одном batch-запросе
+pub const MAX_BATCH_PAIRS: usize = 100;
+
 // --- Healthcheck ---
-pub async fn health_check() -> impl Responder {
-    HttpResponse::Ok().json(serde_json::json!({ "status": "ok" }))
+pub async fn health_check(
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let breakers: Vec<_> = data.circuit_breakers.iter().map(|b| b.snapshot()).collect();
+    // Разомкнутый breaker не роняет сервис, но помечает его как degraded
+    let degraded = breakers.iter().any(|b| b.state != CircuitState::Closed);
+    HttpResponse::Ok().json(serde_json::json!({
+        "status": if degraded { "degraded" } else { "ok" },
+        "price_sources": breakers,
+    }))
+}
+
+// --- Реестр токенов ---
+pub async fn list_tokens(
+    data: web::Data<AppState>,
+) -> impl Responder {
+    HttpResponse::Ok().json(serde_json::json!({ "tokens": data.tokens.tokens() }))
+}
+
+/// Канонические символы пары или ошибка про первый неизвестный токен.
+fn resolve_pair<'a>(data: &'a AppState, from: &str, to: &str) -> Result<(&'a str, &'a str), String> {
+    Ok((data.tokens.canonical(from)?, data.tokens.canonical(to)?))
 }
 
 // --- Hello world index ---
@@ -59,55 +104,203 @@ pub async fn delete_order(
 }
 
 // --- Swap mock endpoint ---
+/// Своп `amount_in` по цене источника `source` (по умолчанию — основного, как в котировке);
+/// с `min_amount_out` из котировки меньший выход отклоняется (`SLIPPAGE_EXCEEDED`).
 pub async fn swap_mock(
+    data: web::Data<AppState>,
     payload: web::Json<SwapMockRequest>,
 ) -> impl Responder {
     use rust_decimal::Decimal;
+    let (token_in, token_out) = match (data.tokens.resolve(&payload.from_token), data.tokens.resolve(&payload.to_token)) {
+        (Ok(token_in), Ok(token_out)) => (token_in, token_out),
+        (Err(e), _) | (_, Err(e)) => return HttpResponse::BadRequest().json(serde_json::json!({ "error": e })),
+    };
+    let (from, to) = (token_in.symbol.as_str(), token_out.symbol.as_str());
     let amount_in = payload.amount_in.parse::<Decimal>();
-    let price = payload.price.parse::<Decimal>();
-    match (amount_in, price) {
-        (Ok(amount_in), Ok(price)) if amount_in > Decimal::ZERO && price > Decimal::ZERO => {
-            let amount_out = amount_in * price;
-            HttpResponse::Ok().json(serde_json::json!({
-                "amount_out": amount_out.to_string(),
-                "price": payload.price
-            }))
-        },
-        (Err(_), _) | (_, Err(_)) => {
-            HttpResponse::BadRequest().json(serde_json::json!({ "error": "Invalid number format" }))
-        },
-        _ => {
-            HttpResponse::BadRequest().json(serde_json::json!({ "error": "Invalid amount or price" }))
-        }
+    let min_amount_out = payload.min_amount_out.as_deref().map(str::parse::<Decimal>).transpose();
+    let (amount_in, min_amount_out) = match (amount_in, min_amount_out) {
+        (Ok(amount_in), Ok(min_amount_out)) => (amount_in, min_amount_out),
+        _ => return HttpResponse::BadRequest().json(serde_json::json!({ "error": "Invalid number format" })),
+    };
+    if amount_in <= Decimal::ZERO {
+        return HttpResponse::BadRequest().json(serde_json::json!({ "error": "Invalid amount or price" }));
+    }
+    let (price, source, timestamp_ms) = match server_price(&data, payload.source.as_deref(), from, to, &payload.amount_in).await {
+        Ok(priced) => priced,
+        Err(response) => return response,
+    };
+    match SwapEngine::execute(amount_in, price, min_amount_out, token_in.decimals, token_out.decimals) {
+        Ok(amount_out) => HttpResponse::Ok().json(serde_json::json!({
+            "amount_out": amount_out.to_string(),
+            "price": price.to_string(),
+            "source": source,
+            "timestamp_ms": timestamp_ms,
+        })),
+        Err(e) => swap_error_response(e),
+    }
+}
+
+/// Цена пары на вход `amount_in` из источника `source` (по умолчанию — основного, свежая цена —
+/// с доски потока цен) с именем источника и временем цены.
+async fn server_price<'a>(
+    data: &'a AppState,
+    source: Option<&'a str>,
+    from: &str,
+    to: &str,
+    amount_in: &str,
+) -> Result<(rust_decimal::Decimal, &'a str, u64), HttpResponse> {
+    let price_source = data
+        .price_sources
+        .get(source)
+        .map_err(|e| HttpResponse::BadRequest().json(serde_json::json!({ "error": e })))?;
+    let timed = match board_price(data, source, from, to) {
+        Some(timed) => timed,
+        None => price_source
+            .get_price_timed(from, to, Some(amount_in))
+            .await
+            .map_err(|e| HttpResponse::BadRequest().json(serde_json::json!({ "error": e })))?,
+    };
+    let price = decimal_price(timed.price)?;
+    Ok((price, source.unwrap_or(data.price_sources.default_name()), timed.timestamp_ms))
+}
+
+/// Котировка за ровно `amount_out`, как `server_price`: у AMM-источника вход считается
+/// по кривой пула, с комиссией и price impact.
+async fn server_exact_out<'a>(
+    data: &'a AppState,
+    source: Option<&'a str>,
+    from: &str,
+    to: &str,
+    amount_out: &str,
+) -> Result<(rust_decimal::Decimal, &'a str, ExactOutPrice), HttpResponse> {
+    let price_source = data
+        .price_sources
+        .get(source)
+        .map_err(|e| HttpResponse::BadRequest().json(serde_json::json!({ "error": e })))?;
+    let quote = match board_price(data, source, from, to) {
+        Some(timed) => ExactOutPrice { price: timed.price, timestamp_ms: timed.timestamp_ms, amount_in: None },
+        None => price_source
+            .get_price_exact_out(from, to, amount_out)
+            .await
+            .map_err(|e| HttpResponse::BadRequest().json(serde_json::json!({ "error": e })))?,
+    };
+    let price = decimal_price(quote.price)?;
+    Ok((price, source.unwrap_or(data.price_sources.default_name()), quote))
+}
+
+fn decimal_price(price: f64) -> Result<rust_decimal::Decimal, HttpResponse> {
+    use rust_decimal::prelude::*;
+    Decimal::from_f64(price).filter(|price| *price > Decimal::ZERO).ok_or_else(|| {
+        HttpResponse::BadRequest().json(serde_json::json!({ "error": format!("Invalid price {price} from source") }))
+    })
+}
+
+fn swap_error_response(e: SwapError) -> HttpResponse {
+    match e {
+        SwapError::SlippageExceeded { amount_out, min_amount_out } => HttpResponse::BadRequest().json(serde_json::json!({
+            "error": e.to_string(),
+            "code": "SLIPPAGE_EXCEEDED",
+            "amount_out": amount_out.to_string(),
+            "min_amount_out": min_amount_out.to_string(),
+        })),
+        e => HttpResponse::BadRequest().json(serde_json::json!({ "error": e.to_string() })),
     }
 }
 
 // --- Получить quote (расчет без добавления заявки) ---
+/// `mode=exact_in` (по умолчанию): выход за `amount_in` и `min_amount_out`;
+/// `mode=exact_out`: вход за ровно `amount_out` (округлён вверх) и `max_amount_in`.
+/// Допуск — `slippage_bps`, по умолчанию 50 bps. Суммы — в decimals своих токенов:
+/// отдаваемое округляется вниз, получаемое — вверх.
+///
+/// Без `price` цена пары берётся из источника `source` (по умолчанию — основного, свежая цена —
+/// с доски потока цен), в ответе — его имя и время цены. Для `exact_in` источник получает объём `amount_in`,
+/// для `exact_out` — котировку за `amount_out`: у AMM-источника вход считается по кривой пула, а `price` — эффективная цена.
 pub async fn get_quote(
+    data: web::Data<AppState>,
     query: web::Query<QuoteQuery>,
 ) -> impl Responder {
-    use rust_decimal::Decimal;
-    let amount_in = query.amount_in.parse::<Decimal>();
-    let price = query.price.parse::<Decimal>();
-    match (amount_in, price) {
-        (Ok(amount_in), Ok(price)) if amount_in > Decimal::ZERO && price > Decimal::ZERO => {
-            match SwapEngine::get_quote(amount_in, price) {
-                Ok(amount_out) => HttpResponse::Ok().json(serde_json::json!({
-                    "amount_out": amount_out,
-                    "price": query.price,
-                    "from_token": query.from_token,
-                    "to_token": query.to_token,
-                })),
-                Err(e) => HttpResponse::BadRequest().json(serde_json::json!({ "error": e.to_string() })),
-            }
+    use rust_decimal::prelude::*;
+    let (token_in, token_out) = match (data.tokens.resolve(&query.from_token), data.tokens.resolve(&query.to_token)) {
+        (Ok(token_in), Ok(token_out)) => (token_in, token_out),
+        (Err(e), _) | (_, Err(e)) => return HttpResponse::BadRequest().json(serde_json::json!({ "error": e })),
+    };
+    let (from, to) = (token_in.symbol.as_str(), token_out.symbol.as_str());
+    let (amount, amount_name) = match query.mode {
+        SwapMode::ExactIn => (&query.amount_in, "amount_in"),
+        SwapMode::ExactOut => (&query.amount_out, "amount_out"),
+    };
+    let Some(amount) = amount else {
+        return HttpResponse::BadRequest().json(serde_json::json!({ "error": format!("{amount_name} is required") }));
+    };
+    let Ok(amount_value) = amount.parse::<Decimal>() else {
+        return HttpResponse::BadRequest().json(serde_json::json!({ "error": "Invalid number format" }));
+    };
+    // Вход по кривой пула — у exact_out из AMM-источника
+    let (price, source, timestamp_ms, curve_amount_in) = match (&query.price, query.mode) {
+        (Some(price), _) => match price.parse::<Decimal>() {
+            Ok(price) => (price, "client", None, None),
+            Err(_) => return HttpResponse::BadRequest().json(serde_json::json!({ "error": "Invalid number format" })),
+        },
+        (None, SwapMode::ExactIn) => match server_price(&data, query.source.as_deref(), from, to, amount).await {
+            Ok((price, source, timestamp_ms)) => (price, source, Some(timestamp_ms), None),
+            Err(response) => return response,
         },
-        (Err(_), _) | (_, Err(_)) => {
-            HttpResponse::BadRequest().json(serde_json::json!({ "error": "Invalid number format" }))
+        (None, SwapMode::ExactOut) => match server_exact_out(&data, query.source.as_deref(), from, to, amount).await {
+            Ok((price, source, quote)) => (price, source, Some(quote.timestamp_ms), quote.amount_in),
+            Err(response) => return response,
+        },
+    };
+    if amount_value <= Decimal::ZERO || price <= Decimal::ZERO {
+        return HttpResponse::BadRequest().json(serde_json::json!({ "error": "Invalid amount or price" }));
+    }
+    let slippage_bps = query.slippage_bps.unwrap_or(DEFAULT_SLIPPAGE_BPS);
+    let quote = match curve_amount_in {
+        Some(amount_in) => match amount_in.parse::<Decimal>() {
+            Ok(amount_in) => SwapEngine::quote_exact_out_with_input(
+                amount_value,
+                amount_in,
+                slippage_bps,
+                token_in.decimals,
+                token_out.decimals,
+            ),
+            Err(_) => return HttpResponse::BadRequest().json(serde_json::json!({ "error": format!("Invalid amount_in {amount_in} from source") })),
         },
-        _ => {
-            HttpResponse::BadRequest().json(serde_json::json!({ "error": "Invalid amount or price" }))
+        None => SwapEngine::quote(query.mode, amount_value, price, slippage_bps, token_in.decimals, token_out.decimals),
+    };
+    match quote {
+        Ok(quote) => {
+            let mut body = serde_json::json!({
+                "mode": quote.mode,
+                "amount_in": quote.amount_in,
+                "amount_out": quote.amount_out,
+                "min_amount_out": quote.min_amount_out,
+                "max_amount_in": quote.max_amount_in,
+                "slippage_bps": quote.slippage_bps,
+                "price": price.to_string(),
+                "source": source,
+                "from_token": query.from_token,
+                "to_token": query.to_token,
+            });
+            if let Some(timestamp_ms) = timestamp_ms {
+                body["timestamp_ms"] = timestamp_ms.into();
+            }
+            HttpResponse::Ok().json(body)
         }
+        Err(e) => swap_error_response(e),
+    }
+}
+
+/// Свежая цена пары с доски потока цен, если котировка идёт по источнику по умолчанию
+/// и его цена не зависит от объёма (см. `AppState::quotes_from_board`).
+fn board_price(data: &AppState, source: Option<&str>, from: &str, to: &str) -> Option<TimedPrice> {
+    if !data.quotes_from_board || source.is_some_and(|source| source != data.price_sources.default_name()) {
+        return None;
     }
+    let update = data.prices.latest(from, to)?;
+    let age_ms = now_ms().saturating_sub(update.timestamp_ms);
+    (u128::from(age_ms) <= data.max_price_age.as_millis())
+        .then_some(TimedPrice { price: update.price, timestamp_ms: update.timestamp_ms })
 }
 
 // --- Legacy: статический прайсинг (core) ---
@@ -120,33 +313,104 @@ pub async fn get_price_handler(
     }
 }
 
-// --- Новый handler: динамический прайсинг через AppState::price_source (Uniswap/Mock/CoinGecko) ---
+// --- Новый handler: динамический прайсинг через AppState::price_sources (Uniswap/Mock/CoinGecko) ---
 pub async fn price_source_handler(
     data: web::Data<AppState>,
     query: web::Query<PriceSourceQuery>,
 ) -> impl Responder {
+    named_source_price(&data, query.source.as_deref(), &query).await
+}
+
+/// Цена пары из источника `source` (по умолчанию — основного) с его именем и путём кросс-курса в ответе.
+async fn named_source_price(data: &AppState, source: Option<&str>, query: &PriceSourceQuery) -> HttpResponse {
+    let (from, to) = match resolve_pair(data, &query.from, &query.to) {
+        Ok(pair) => pair,
+        Err(e) => return HttpResponse::BadRequest().json(serde_json::json!({ "error": e })),
+    };
+    let price_source = match data.price_sources.get(source) {
+        Ok(price_source) => price_source,
+        Err(e) => return HttpResponse::BadRequest().json(serde_json::json!({ "error": e })),
+    };
+    let source = source.unwrap_or(data.price_sources.default_name());
     let amount = query.amount.as_deref();
-    match data.price_source.get_price(&query.from, &query.to, amount).await {
-        Ok(price) => HttpResponse::Ok().json(serde_json::json!({ "price": price })),
+    match price_source.get_price_with_path(from, to, amount).await {
+        Ok(priced) => HttpResponse::Ok().json(serde_json::json!({ "price": priced.price, "source": source, "path": priced.path })),
         Err(e) => HttpResponse::BadRequest().json(serde_json::json!({ "error": e })),
     }
 }
 
+// --- Пакетный прайсинг: много пар за один вызов price_source ---
+pub async fn batch_price_handler(
+    data: web::Data<AppState>,
+    payload: web::Json<BatchPriceRequest>,
+) -> impl Responder {
+    if payload.pairs.is_empty() || payload.pairs.len() > MAX_BATCH_PAIRS {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("pairs must contain 1..={MAX_BATCH_PAIRS} entries")
+        }));
+    }
+    let price_source = match data.price_sources.get(payload.source.as_deref()) {
+        Ok(price_source) => price_source,
+        Err(e) => return HttpResponse::BadRequest().json(serde_json::json!({ "error": e })),
+    };
+    // Неизвестные токены отсекаются до источника, в источник уходят канонические символы
+    let resolved: Vec<_> = payload.pairs.iter().map(|p| resolve_pair(&data, &p.from, &p.to)).collect();
+    let valid: Vec<(&str, &str)> = resolved.iter().filter_map(|r| r.as_ref().ok().copied()).collect();
+    let mut fetched = price_source.get_prices(&valid).await.into_iter();
+    let results = resolved.into_iter().map(|r| match r {
+        Ok(_) => match fetched.next() {
+            Some(price) => price.map_err(|e| e.to_string()),
+            None => Err("Missing price".to_string()),
+        },
+        Err(e) => Err(e),
+    });
+    let prices: Vec<_> = payload.pairs.iter().zip(results).map(|(pair, result)| match result {
+        Ok(price) => serde_json::json!({ "from": pair.from, "to": pair.to, "price": price }),
+        Err(e) => serde_json::json!({ "from": pair.from, "to": pair.to, "error": e }),
+    }).collect();
+    HttpResponse::Ok().json(serde_json::json!({ "prices": prices }))
+}
+
+// --- Поток цен (Server-Sent Events) ---
+/// Сначала текущие цены пар, затем каждое изменение событием `data: {PriceUpdate}`.
+pub async fn price_stream_handler(
+    data: web::Data<AppState>,
+    query: web::Query<PriceStreamQuery>,
+) -> impl Responder {
+    let mut pairs = Vec::new();
+    for pair in query.pairs.split(',').filter(|p| !p.trim().is_empty()) {
+        let Some((from, to)) = pair.split_once('/') else {
+            return HttpResponse::BadRequest().json(serde_json::json!({ "error": format!("Invalid pair {pair}, expected FROM/TO") }));
+        };
+        let (from, to) = match resolve_pair(&data, from, to) {
+            Ok(resolved) => resolved,
+            Err(e) => return HttpResponse::BadRequest().json(serde_json::json!({ "error": e })),
+        };
+        if !data.markets.iter().any(|(base, quote)| base == from && quote == to) {
+            return HttpResponse::BadRequest().json(serde_json::json!({ "error": format!("Pair {from}/{to} is not streamed") }));
+        }
+        pairs.push((from, to));
+    }
+    if pairs.is_empty() {
+        return HttpResponse::BadRequest().json(serde_json::json!({ "error": "pairs must not be empty" }));
+    }
+    let watch = data.prices.watch(&pairs);
+    let events = futures_util::stream::unfold(watch, |mut watch| async move {
+        let update = watch.next().await?;
+        let event = format!("data: {}\n\n", serde_json::to_string(&update).unwrap_or_default());
+        Some((Ok::<_, actix_web::Error>(web::Bytes::from(event)), watch))
+    });
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(events)
+}
+
 // --- Получить цену только с Uniswap ---
+/// То же, что `/pricing/source?source=uniswap`.
 pub async fn uniswap_price_handler(
     data: web::Data<AppState>,
     query: web::Query<PriceSourceQuery>,
 ) -> impl Responder {
-    // Попробуем привести price_source к UniswapV2PriceSource
-    use smartswap_core::price_source::uniswap_v2::UniswapV2PriceSource;
-    let price_source = data.price_source.clone();
-    if let Some(uniswap) = price_source.as_any().downcast_ref::<UniswapV2PriceSource>() {
-        let amount = query.amount.as_deref();
-        match uniswap.get_price(&query.from, &query.to, amount).await {
-            Ok(price) => HttpResponse::Ok().json(serde_json::json!({ "price": price, "source": "uniswap" })),
-            Err(e) => HttpResponse::BadRequest().json(serde_json::json!({ "error": e })),
-        }
-    } else {
-        HttpResponse::BadRequest().json(serde_json::json!({ "error": "UniswapV2PriceSource не инициализирован" }))
-    }
+    named_source_price(&data, Some("uniswap"), &query).await
 }
\ No newline at end of file
@@ -1,3 +1,5 @@
+pub mod config;
 pub mod handlers;
+pub mod metrics;
 pub mod routes;
 pub mod state;
\ No newline at end of file
@@ -1,26 +1,49 @@
-mod state;
-mod handlers;
-mod routes;
-
 use actix_web::{App, HttpServer};
 use actix_cors::Cors;
 use actix_web_prom::PrometheusMetricsBuilder;
+use smartswap_backend::{config, metrics, routes, state};
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    let app_state = state::AppState::new();
+    // Ошибки конфигурации — до старта сервера, все сразу
+    let config = config::Config::load().unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(1);
+    });
+    let app_state = state::AppState::from_config(&config).unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(1);
+    });
+    let streaming = app_state
+        .start_price_stream(std::time::Duration::from_secs(config.server.price_stream_interval_secs))
+        .await;
+    if let Err(e) = streaming {
+        eprintln!("Cannot start price stream: {e}");
+        std::process::exit(1);
+    }
+    app_state.start_order_triggers();
 
     // Инициализация Prometheus метрик
     let prometheus = PrometheusMetricsBuilder::new("api")
         .endpoint("/metrics")
         .build()
         .unwrap();
+    let registered = metrics::CircuitBreakerCollector::new("api", app_state.circuit_breakers.clone())
+        .and_then(|collector| prometheus.registry.register(Box::new(collector)));
+    if let Err(e) = registered {
+        eprintln!("Cannot register circuit breaker metrics: {e}");
+        std::process::exit(1);
+    }
 
-    println!("SmartSwap backend запущен на http://127.0.0.1:8088");
+    println!("SmartSwap backend запущен на http://{}", config.server.bind);
 
+    let cors_origins = config.server.cors_origins.clone();
     HttpServer::new(move || {
-        let cors = Cors::default()
-            .allowed_origin("http://localhost:3000")
+        let cors = cors_origins.iter().fold(Cors::default(), |cors, origin| match origin.as_str() {
+            "*" => cors.allow_any_origin(),
+            origin => cors.allowed_origin(origin),
+        });
+        let cors = cors
             .allow_any_method()
             .allow_any_header()
             .max_age(3600);
@@ -31,7 +54,7 @@ async fn main() -> std::io::Result<()> {
             .app_data(actix_web::web::Data::new(app_state.clone()))
             .service(routes::create_routes())
     })
-    .bind(("127.0.0.1", 8088))?
+    .bind(config.server.bind.as_str())?
     .run()
     .await
 }
\ No newline at end of file
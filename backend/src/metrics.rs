@@ -0,0 +1,50 @@
+use std::sync::Arc;
+use prometheus::core::{Collector, Desc};
+use prometheus::proto::MetricFamily;
+use prometheus::{IntCounterVec, IntGaugeVec, Opts};
+use smartswap_core::price_source::CircuitBreaker;
+
+/// Экспортирует состояние circuit breaker'ов источников цен в момент scrape.
+pub struct CircuitBreakerCollector {
+    breakers: Vec<Arc<CircuitBreaker>>,
+    state: IntGaugeVec,
+    consecutive_failures: IntGaugeVec,
+    times_opened: IntCounterVec,
+}
+
+impl CircuitBreakerCollector {
+    pub fn new(namespace: &str, breakers: Vec<Arc<CircuitBreaker>>) -> prometheus::Result<Self> {
+        let opts = |name: &str, help: &str| Opts::new(name, help).namespace(namespace);
+        Ok(Self {
+            breakers,
+            state: IntGaugeVec::new(opts("price_source_circuit_state", "Circuit state: 0 closed, 1 half-open, 2 open"), &["source"])?,
+            consecutive_failures: IntGaugeVec::new(opts("price_source_consecutive_failures", "Consecutive failed upstream calls"), &["source"])?,
+            times_opened: IntCounterVec::new(opts("price_source_circuit_opened_total", "How many times the circuit has opened"), &["source"])?,
+        })
+    }
+}
+
+impl Collector for CircuitBreakerCollector {
+    fn desc(&self) -> Vec<&Desc> {
+        let mut descs = self.state.desc();
+        descs.extend(self.consecutive_failures.desc());
+        descs.extend(self.times_opened.desc());
+        descs
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        for breaker in &self.breakers {
+            let snapshot = breaker.snapshot();
+            let labels = [snapshot.name.as_str()];
+            self.state.with_label_values(&labels).set(snapshot.state.as_gauge());
+            self.consecutive_failures.with_label_values(&labels).set(snapshot.consecutive_failures as i64);
+            // Счётчик только растёт: добавляем размыкания с прошлого scrape
+            let opened = self.times_opened.with_label_values(&labels);
+            opened.inc_by(snapshot.times_opened.saturating_sub(opened.get()));
+        }
+        let mut families = self.state.collect();
+        families.extend(self.consecutive_failures.collect());
+        families.extend(self.times_opened.collect());
+        families
+    }
+}
@@ -1,27 +1,320 @@
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use actix_web::rt::task::JoinHandle;
+use rust_decimal::prelude::*;
 use smartswap_core::orderbook::OrderBook;
-use smartswap_core::price_source::{PriceSource, MockPriceSource};
-use dotenv::dotenv;
+use smartswap_core::pool_state::{FilePoolStateProvider, PoolStateProvider, RpcPoolStateProvider};
+use smartswap_core::price_source::{PriceSource, MockPriceSource, StaticPriceSource, CoinGeckoPriceSource, CexPriceSource};
+use smartswap_core::price_source::{CircuitBreaker, ResilientPriceSource, GuardedPriceSource, TriangulatingPriceSource};
+use smartswap_core::price_source::{PollingPriceStream, PriceBoard, PriceStream, ReconnectingPriceStream, ReplayPriceSource};
+use smartswap_core::price_source::{PriceRecorder, PriceSourceRegistry, RecordingPriceSource};
+use smartswap_core::price_source::{ChainlinkPriceSource, FallbackPriceSource, chainlink::RpcAggregatorReader};
+use smartswap_core::price_source::{COINGECKO_API_URL, COINGECKO_PRO_API_URL};
+use smartswap_core::price_source::uniswap_v2::{self, UniswapV2PriceSource};
+use smartswap_core::tokens::TokenRegistry;
+use crate::config::{Config, SourceConfig};
 
 #[derive(Clone)]
 pub struct AppState {
     pub orderbook: Arc<Mutex<OrderBook>>,
-    pub price_source: Arc<dyn PriceSource>,
+    /// Источники цен по имени (`?source=`), `price_source()` — источник по умолчанию
+    pub price_sources: PriceSourceRegistry,
+    pub circuit_breakers: Vec<Arc<CircuitBreaker>>,
+    pub tokens: Arc<TokenRegistry>,
+    /// Последние цены из фонового потока (`start_price_stream`)
+    pub prices: Arc<PriceBoard>,
+    /// Рынки из конфигурации (канонические символы): их держит поток цен
+    pub markets: Vec<(String, String)>,
+    /// Котировки по источнику по умолчанию берут цену с `prices`, если она не старше `max_price_age`.
+    /// Только для источников, цена которых не зависит от объёма (не AMM)
+    pub quotes_from_board: bool,
+    pub max_price_age: Duration,
 }
 
-impl Default for AppState {
-    fn default() -> Self {
-        Self::new()
+impl AppState {
+    /// Состояние по `Config::load()`.
+    pub fn new() -> Result<Self, String> {
+        Config::load().and_then(|config| Self::from_config(&config))
     }
-}
 
-impl AppState {
-    pub fn new() -> Self {
-        dotenv().ok();
-        // Удалены неиспользуемые переменные и импорты
+    /// Строит реестр токенов и источники цен по конфигурации. Символы рынков и пулов
+    /// проверяются по реестру; файлы (реестр, снимки пулов, история) читаются здесь же.
+    ///
+    /// Живые источники оборачиваются так же, как раньше `mock`: предохранитель с именем источника,
+    /// кросс-курсы по `markets` через `intermediates`, проверка свежести и скачков; скачки
+    /// подтверждаются источником `confirmation_source`. Цепочка `fallback` опрашивает уже обёрнутые
+    /// (кроме проверки скачков) источники и проверяется сама.
+    /// `replay` отдаётся как есть: история для проверки свежести всегда устаревшая.
+    pub fn from_config(config: &Config) -> Result<Self, String> {
+        config.validate()?;
+        let (tokens, markets) = load_tokens(config)?;
+        let recorder = match &config.paths.price_record_dir {
+            Some(dir) => Some(Arc::new(PriceRecorder::new(dir, "prices")?)),
+            None => None,
+        };
+
+        let mut circuit_breakers = Vec::new();
+        // Пулы и фиды на одной ноде делят провайдер
+        let mut rpc_providers = HashMap::new();
+        // Сначала цепочки без проверки скачков: цепочкой `confirmation_source` подтверждаются скачки остальных
+        let mut chains = HashMap::new();
+        for (name, source_config) in &config.pricing.sources {
+            if matches!(source_config, SourceConfig::Fallback { .. }) {
+                continue;
+            }
+            let chain = build_source(name, source_config, config, &tokens, &markets, &mut circuit_breakers, &mut rpc_providers)
+                .map_err(|e| format!("Invalid config: pricing.sources.{name}: {e}"))?;
+            chains.insert(name, chain);
+        }
+        // Цепочки — из уже собранных источников; ссылки проверены `Config::validate`
+        for (name, source_config) in &config.pricing.sources {
+            if let SourceConfig::Fallback { sources, timeout_ms } = source_config {
+                let fallback = sources.iter().fold(FallbackPriceSource::new(), |fallback, member| {
+                    fallback.with_source(member, chains[member].clone(), Duration::from_millis(*timeout_ms))
+                });
+                chains.insert(name, Arc::new(fallback));
+            }
+        }
+        let confirmation_source = config.pricing.confirmation_source.as_ref();
+        let confirmation = confirmation_source.and_then(|name| chains.get(name)).cloned();
+
+        let mut registry: Option<PriceSourceRegistry> = None;
+        for (name, source_config) in &config.pricing.sources {
+            // Источник подтверждения сам себя не подтверждает
+            let confirmation = confirmation.clone().filter(|_| confirmation_source != Some(name));
+            let mut source = guard_source(config, source_config, chains[name].clone(), confirmation);
+            if let Some(recorder) = &recorder {
+                source = Arc::new(RecordingPriceSource::new(source, name, recorder.clone()));
+            }
+            registry = Some(match registry {
+                Some(registry) => registry.with_source(name, source),
+                None => PriceSourceRegistry::new(name, source),
+            });
+        }
+        let price_sources = registry
+            .ok_or("Invalid config: pricing.sources must not be empty")?
+            .with_default(&config.pricing.default_source)?;
+        let quotes_from_board = !is_amm(config, &config.pricing.default_source);
+        Ok(Self {
+            circuit_breakers,
+            quotes_from_board,
+            ..Self::with_tokens(config, price_sources, tokens, markets)
+        })
+    }
+
+    /// Состояние с готовым источником цен как есть, без обёрток `from_config`
+    /// (например, `ReplayPriceSource` в тестах: проверка свежести считала бы историю устаревшей).
+    /// В реестре он единственный, под именем `default`.
+    pub fn with_price_source(config: &Config, price_source: Arc<dyn PriceSource>) -> Result<Self, String> {
+        Self::with_price_sources(config, PriceSourceRegistry::new("default", price_source))
+    }
+
+    /// Реестр токенов и рынки — из `config`, источники цен — готовые; `config.pricing.sources` не используется.
+    pub fn with_price_sources(config: &Config, price_sources: PriceSourceRegistry) -> Result<Self, String> {
+        let (tokens, markets) = load_tokens(config)?;
+        Ok(Self::with_tokens(config, price_sources, tokens, markets))
+    }
+
+    fn with_tokens(
+        config: &Config,
+        price_sources: PriceSourceRegistry,
+        tokens: TokenRegistry,
+        markets: Vec<(String, String)>,
+    ) -> Self {
+        let tokens = Arc::new(tokens);
         Self {
-            orderbook: Arc::new(Mutex::new(OrderBook::new())),
-            price_source: Arc::new(MockPriceSource),
+            orderbook: Arc::new(Mutex::new(OrderBook::with_registry(tokens.clone()))),
+            price_sources,
+            circuit_breakers: Vec::new(),
+            tokens,
+            prices: Arc::new(PriceBoard::default()),
+            markets,
+            quotes_from_board: false,
+            max_price_age: Duration::from_secs(config.pricing.max_price_age_secs),
+        }
+    }
+
+    /// Источник цен по умолчанию.
+    pub fn price_source(&self) -> Arc<dyn PriceSource> {
+        self.price_sources.default_source()
+    }
+
+    /// Опрашивает `price_source()` раз в `interval` и публикует изменения `markets`
+    /// на `prices`; при обрыве поток переподключается сам. Нужен запущенный tokio runtime.
+    pub async fn start_price_stream(&self, interval: Duration) -> Result<(), String> {
+        let polling = PollingPriceStream::new(self.price_source(), interval);
+        let pairs: Vec<(&str, &str)> = self.markets.iter().map(|(b, q)| (b.as_str(), q.as_str())).collect();
+        let subscription = ReconnectingPriceStream::new(Arc::new(polling))
+            .subscribe(&pairs)
+            .await?;
+        self.prices.follow(subscription);
+        Ok(())
+    }
+
+    /// При каждом изменении цены `markets` на `prices` переносит сработавшие ордера
+    /// в `OrderBook::triggered`. Нужен запущенный tokio runtime.
+    pub fn start_order_triggers(&self) -> JoinHandle<()> {
+        let pairs: Vec<(&str, &str)> = self.markets.iter().map(|(b, q)| (b.as_str(), q.as_str())).collect();
+        let mut watch = self.prices.watch(&pairs);
+        let orderbook = self.orderbook.clone();
+        actix_web::rt::spawn(async move {
+            while let Some(update) = watch.next().await {
+                if let Some(price) = Decimal::from_f64(update.price) {
+                    orderbook.lock().unwrap().trigger(&update.from, &update.to, price);
+                }
+            }
+        })
+    }
+}
+
+/// Реестр токенов конфигурации (файл или встроенный, на `chain_id`) и её рынки в канонических символах.
+fn load_tokens(config: &Config) -> Result<(TokenRegistry, Vec<(String, String)>), String> {
+    let tokens = match &config.paths.token_registry {
+        Some(path) => TokenRegistry::load(path)?,
+        None => TokenRegistry::default(),
+    };
+    let tokens = match config.chain_id {
+        Some(chain_id) => tokens.with_chain(chain_id).map_err(|e| format!("Invalid config: chain_id: {e}"))?,
+        None => tokens,
+    };
+    let mut markets = Vec::new();
+    let mut errors = Vec::new();
+    for (base, quote) in config.market_pairs() {
+        match (tokens.canonical(base), tokens.canonical(quote)) {
+            (Ok(base), Ok(quote)) => markets.push((base.to_string(), quote.to_string())),
+            (Err(e), _) | (_, Err(e)) => errors.push(format!("markets: {e}")),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(format!("Invalid config: {}", errors.join("; ")));
+    }
+    Ok((tokens, markets))
+}
+
+/// Источник `name` по его параметрам, с обёртками живых источников (см. `AppState::from_config`)
+/// кроме проверки свежести и скачков — её добавляет `guard_source`.
+fn build_source(
+    name: &str,
+    source: &SourceConfig,
+    config: &Config,
+    tokens: &TokenRegistry,
+    markets: &[(String, String)],
+    circuit_breakers: &mut Vec<Arc<CircuitBreaker>>,
+    rpc_providers: &mut HashMap<String, Arc<RpcPoolStateProvider>>,
+) -> Result<Arc<dyn PriceSource>, String> {
+    let live: Arc<dyn PriceSource> = match source {
+        SourceConfig::Mock => Arc::new(MockPriceSource),
+        SourceConfig::Static { prices } => {
+            let mut source = StaticPriceSource::new();
+            for (pair, price) in prices {
+                let (base, quote) = crate::config::parse_pair(pair).ok_or_else(|| format!("{pair} is not BASE/QUOTE"))?;
+                source = source.with_pair(tokens.canonical(base)?, tokens.canonical(quote)?, *price);
+            }
+            Arc::new(source)
+        }
+        SourceConfig::Coingecko { base_url, api_key } => {
+            // С ключом и без base_url — pro API
+            let default_url = if api_key.is_some() { COINGECKO_PRO_API_URL } else { COINGECKO_API_URL };
+            let mut source = CoinGeckoPriceSource::new(base_url.as_deref().unwrap_or(default_url));
+            if let Some(api_key) = api_key {
+                source = source.with_api_key(api_key);
+            }
+            Arc::new(source.with_registry(tokens))
+        }
+        SourceConfig::Cex { exchange, base_url } => {
+            let source = CexPriceSource::new(*exchange, base_url.as_deref().unwrap_or(exchange.default_url()));
+            Arc::new(source.with_registry(tokens))
+        }
+        SourceConfig::UniswapV2 { pool, token0, token1, decimals0, decimals1, rpc_url, pool_state_file } => {
+            let state: Arc<dyn PoolStateProvider> = match (rpc_url, pool_state_file) {
+                (Some(url), None) => rpc_provider(rpc_providers, url)?,
+                (None, Some(path)) => Arc::new(FilePoolStateProvider::load(path)?),
+                _ => return Err("set exactly one of rpc_url and pool_state_file".to_string()),
+            };
+            let (token0, token1) = (tokens.resolve(token0)?, tokens.resolve(token1)?);
+            Arc::new(UniswapV2PriceSource::new(
+                state,
+                pool.as_str(),
+                &token0.symbol,
+                &token1.symbol,
+                decimals0.unwrap_or(token0.decimals),
+                decimals1.unwrap_or(token1.decimals),
+            ))
+        }
+        SourceConfig::Replay { path, speed } => {
+            let replay = ReplayPriceSource::load(path)?;
+            replay.clock().set_speed(*speed);
+            return Ok(Arc::new(replay));
         }
+        SourceConfig::Fallback { .. } => return Err("fallback chains are built from the other sources".to_string()),
+        SourceConfig::Chainlink { rpc_url, feeds } => {
+            let reader = RpcAggregatorReader::new(rpc_provider(rpc_providers, rpc_url)?.provider());
+            let mut source = ChainlinkPriceSource::new(Arc::new(reader));
+            for (pair, feed) in feeds {
+                let (base, quote) = crate::config::parse_pair(pair).ok_or_else(|| format!("{pair} is not BASE/QUOTE"))?;
+                source = source.with_feed(
+                    tokens.canonical(base).unwrap_or(base),
+                    tokens.canonical(quote).unwrap_or(quote),
+                    &feed.address,
+                    Duration::from_secs(feed.heartbeat_secs),
+                );
+            }
+            Arc::new(source)
+        }
+    };
+    let breaker = Arc::new(CircuitBreaker::new(name, 5, Duration::from_secs(30)));
+    circuit_breakers.push(breaker.clone());
+    let resilient = ResilientPriceSource::new(live, breaker);
+    let intermediates: Vec<&str> = config.pricing.intermediates.iter().map(String::as_str).collect();
+    let triangulating = markets
+        .iter()
+        .fold(TriangulatingPriceSource::new(Arc::new(resilient)), |tri, (base, quote)| tri.with_pair(base, quote))
+        .with_intermediates(&intermediates)
+        .with_registry(tokens);
+    Ok(Arc::new(triangulating))
+}
+
+/// Проверка свежести и скачков поверх цепочки `build_source`; скачки подтверждает `confirmation`.
+/// `replay` — как есть.
+fn guard_source(
+    config: &Config,
+    source: &SourceConfig,
+    chain: Arc<dyn PriceSource>,
+    confirmation: Option<Arc<dyn PriceSource>>,
+) -> Arc<dyn PriceSource> {
+    if matches!(source, SourceConfig::Replay { .. }) {
+        return chain;
     }
-}
\ No newline at end of file
+    let guarded = GuardedPriceSource::new(
+        chain,
+        Duration::from_secs(config.pricing.max_price_age_secs),
+        config.pricing.max_jump_pct,
+    );
+    match confirmation {
+        Some(confirmation) => Arc::new(guarded.with_confirmation(confirmation)),
+        None => Arc::new(guarded),
+    }
+}
+
+/// Цена источника `name` зависит от объёма (пул AMM, в том числе в цепочке `fallback`).
+fn is_amm(config: &Config, name: &str) -> bool {
+    match config.pricing.sources.get(name) {
+        Some(SourceConfig::UniswapV2 { .. }) => true,
+        Some(SourceConfig::Fallback { sources, .. }) => sources.iter().any(|member| is_amm(config, member)),
+        _ => false,
+    }
+}
+
+/// Провайдер ноды `url`, общий для всех источников на ней.
+fn rpc_provider(
+    rpc_providers: &mut HashMap<String, Arc<RpcPoolStateProvider>>,
+    url: &str,
+) -> Result<Arc<RpcPoolStateProvider>, String> {
+    if let Some(provider) = rpc_providers.get(url) {
+        return Ok(provider.clone());
+    }
+    let provider = Arc::new(RpcPoolStateProvider::new(uniswap_v2::connect(url)?));
+    rpc_providers.insert(url.to_string(), provider.clone());
+    Ok(provider)
+}
@@ -1,12 +1,13 @@
 use actix_web::{test, App};
 use smartswap_backend::routes;
 use smartswap_backend::state::AppState;
+#[allow(unused_imports)]
 use smartswap_backend::handlers::types::{AddOrderRequest, DeleteOrderRequest, SwapMockRequest, QuoteQuery};
 use serde_json::json;
 
 #[actix_web::test]
 async fn test_orderbook_add_and_list() {
-    let app_state = AppState::new();
+    let app_state = AppState::new().unwrap();
     let app = test::init_service(
         App::new()
             .app_data(actix_web::web::Data::new(app_state))
@@ -61,7 +62,7 @@ async fn test_orderbook_add_and_list() {
 
 #[actix_web::test]
 async fn test_orderbook_delete() {
-    let app_state = AppState::new();
+    let app_state = AppState::new().unwrap();
     let app = test::init_service(
         App::new()
             .app_data(actix_web::web::Data::new(app_state))
@@ -111,8 +112,9 @@ async fn test_orderbook_delete() {
 }
 
 #[actix_web::test]
+#[allow(clippy::needless_borrows_for_generic_args)]
 async fn test_swap_mock() {
-    let app_state = AppState::new();
+    let app_state = AppState::new().unwrap();
     let app = test::init_service(
         App::new()
             .app_data(actix_web::web::Data::new(app_state))
@@ -123,10 +125,9 @@ async fn test_swap_mock() {
     let req = test::TestRequest::post()
         .uri("/api/swap/mock")
         .set_json(&json!({
-            "from": "ETH",
-            "to": "USDT",
-            "amount_in": "1.5",
-            "price": "3000.0"
+            "from_token": "ETH",
+            "to_token": "USDT",
+            "amount_in": "1.5"
         }))
         .to_request();
     let resp = test::call_service(&app, req).await;
@@ -141,24 +142,848 @@ async fn test_swap_mock() {
     println!("SWAP MOCK BODY: {:?}", body); // DEBUG
     let amount_out: f64 = body["amount_out"].as_str().unwrap().parse().unwrap();
     let price: f64 = body["price"].as_str().unwrap().parse().unwrap();
-    assert!(amount_out > 0.0);
-    assert_eq!(price, 3000.0);
+    // Цена исполнения — из источника по умолчанию (Mock), а не от клиента
+    assert_eq!(amount_out, 4800.0);
+    assert_eq!(price, 3200.0);
+    assert_eq!(body["source"], "mock");
+}
+
+#[actix_web::test]
+async fn test_swap_mock_legacy_field_names() {
+    let app_state = AppState::new().unwrap();
+    let app = test::init_service(
+        App::new()
+            .app_data(actix_web::web::Data::new(app_state))
+            .service(routes::create_routes())
+    ).await;
+
+    // Старые клиенты присылают `from`/`to`
+    let req = test::TestRequest::post()
+        .uri("/api/swap/mock")
+        .set_json(json!({
+            "from": "ETH",
+            "to": "USDT",
+            "amount_in": "1.5"
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["amount_out"], "4800");
+}
+
+#[actix_web::test]
+async fn test_swap_slippage_protection() {
+    let app = test::init_service(
+        App::new()
+            .app_data(actix_web::web::Data::new(AppState::new().unwrap()))
+            .service(routes::create_routes())
+    ).await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/swap/quote?from_token=ETH&to_token=USDT&amount_in=2&price=3000&slippage_bps=100")
+        .to_request();
+    let quote: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(quote["amount_out"], "6000");
+    assert_eq!(quote["min_amount_out"], "5940");
+    assert_eq!(quote["slippage_bps"], 100);
+
+    // Без slippage_bps — 50 bps
+    let req = test::TestRequest::get()
+        .uri("/api/swap/quote?from_token=ETH&to_token=USDT&amount_in=2&price=3000")
+        .to_request();
+    let quote: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(quote["min_amount_out"], "5970");
+
+    let req = test::TestRequest::get()
+        .uri("/api/swap/quote?from_token=ETH&to_token=USDT&amount_in=2&price=3000&slippage_bps=20000")
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 400);
+
+    // Ровно 1000 USDT: вход округлён вверх, ограничение — max_amount_in
+    let req = test::TestRequest::get()
+        .uri("/api/swap/quote?from_token=ETH&to_token=USDT&mode=exact_out&amount_out=1000&price=3000&slippage_bps=100")
+        .to_request();
+    let quote: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(quote["mode"], "exact_out");
+    assert_eq!(quote["amount_out"], "1000");
+    assert_eq!(quote["amount_in"], "0.333333333333333334");
+    assert_eq!(quote["max_amount_in"], "0.336666666666666668");
+
+    // Суммы в decimals токенов: выход USDT (6 знаков) вниз, вход точнее токена — ошибка
+    let req = test::TestRequest::get()
+        .uri("/api/swap/quote?from_token=ETH&to_token=USDT&amount_in=1&price=3000.1234567")
+        .to_request();
+    let quote: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(quote["amount_out"], "3000.123456");
+
+    let req = test::TestRequest::get()
+        .uri("/api/swap/quote?from_token=USDT&to_token=ETH&amount_in=1.0000001&price=0.0003")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["error"], "Invalid amount: more than 6 decimal places");
+
+    let req = test::TestRequest::get()
+        .uri("/api/swap/quote?from_token=ETH&to_token=USDT&mode=exact_out&amount_in=1&price=3000")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["error"], "amount_out is required");
+
+    // Исполнение по цене источника (Mock, 3200) ниже допуска котировки
+    let req = test::TestRequest::post()
+        .uri("/api/swap/mock")
+        .set_json(json!({ "from_token": "ETH", "to_token": "USDT", "amount_in": "2", "min_amount_out": "6500" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], "SLIPPAGE_EXCEEDED");
+    assert_eq!(body["amount_out"], "6400");
+    assert_eq!(body["min_amount_out"], "6500");
+
+    let req = test::TestRequest::post()
+        .uri("/api/swap/mock")
+        .set_json(json!({ "from_token": "ETH", "to_token": "USDT", "amount_in": "2", "min_amount_out": "6368" }))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["amount_out"], "6400");
+
+    let req = test::TestRequest::post()
+        .uri("/api/swap/mock")
+        .set_json(json!({ "from_token": "ETH", "to_token": "DOGE", "amount_in": "2" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 400);
+}
+
+#[actix_web::test]
+async fn test_swap_quote_server_price() {
+    let app = test::init_service(
+        App::new()
+            .app_data(actix_web::web::Data::new(AppState::new().unwrap()))
+            .service(routes::create_routes())
+    ).await;
+
+    // Без price — цена основного источника (mock: ETH/USDT = 3200)
+    let req = test::TestRequest::get()
+        .uri("/api/swap/quote?from_token=ETH&to_token=USDT&amount_in=2")
+        .to_request();
+    let quote: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(quote["price"], "3200");
+    assert_eq!(quote["amount_out"], "6400");
+    assert_eq!(quote["source"], "mock");
+    assert!(quote["timestamp_ms"].as_u64().unwrap() > 0);
+
+    // Кросс-курс и exact-out тоже по цене источника
+    let req = test::TestRequest::get()
+        .uri("/api/swap/quote?from_token=USDT&to_token=WBTC&mode=exact_out&amount_out=1")
+        .to_request();
+    let quote: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let amount_in: f64 = quote["amount_in"].as_str().unwrap().parse().unwrap();
+    assert!((amount_in - 67000.0).abs() < 1e-5, "{quote}");
+
+    // Цена клиента по-прежнему принимается, источник — client
+    let req = test::TestRequest::get()
+        .uri("/api/swap/quote?from_token=ETH&to_token=USDT&amount_in=2&price=3000")
+        .to_request();
+    let quote: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(quote["source"], "client");
+    assert!(quote.get("timestamp_ms").is_none());
+
+    for (uri, error) in [
+        ("/api/swap/quote?from_token=ETH&to_token=USDT&amount_in=2&source=uniswap", "Unknown price source: uniswap"),
+        ("/api/swap/quote?from_token=ETH&to_token=BNB&amount_in=2", "No price for ETH/BNB"),
+    ] {
+        let resp = test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+        assert_eq!(resp.status(), 400);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert!(body["error"].as_str().unwrap().starts_with(error), "{body}");
+    }
 }
 
 #[actix_web::test]
 async fn test_uniswap_price_handler() {
-    use smartswap_backend::state::AppState;
-    let app_state = AppState::new();
+    use smartswap_core::pool_state::FilePoolStateProvider;
+    use smartswap_core::price_source::uniswap_v2::UniswapV2PriceSource;
+    use std::sync::Arc;
+
+    // Без источника uniswap — понятная ошибка, а не молчаливый downcast
+    let app = test::init_service(
+        App::new()
+            .app_data(actix_web::web::Data::new(AppState::new().unwrap()))
+            .service(routes::create_routes())
+    ).await;
+    let req = test::TestRequest::get().uri("/api/pricing/uniswap?from=ETH&to=USDT").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["error"], "Unknown price source: uniswap");
+
+    // Пул из записанного снимка рядом с источником по умолчанию
+    let pools = FilePoolStateProvider::load(concat!(env!("CARGO_MANIFEST_DIR"), "/../core/tests/fixtures/pools.json")).unwrap();
+    let uniswap = UniswapV2PriceSource::new(Arc::new(pools), "0x16b9a82891338f9ba80e2d6970fdda79d1eb0dae", "BNB", "USDT", 18, 18);
+    let app_state = AppState::new().unwrap();
+    let app_state = AppState {
+        price_sources: app_state.price_sources.clone().with_source("uniswap", Arc::new(uniswap)),
+        ..app_state
+    };
+    let app = test::init_service(
+        App::new()
+            .app_data(actix_web::web::Data::new(app_state))
+            .service(routes::create_routes())
+    ).await;
+
+    let req = test::TestRequest::get().uri("/api/pricing/uniswap?from=bnb&to=USDT").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!((body["price"].as_f64(), body["source"].as_str()), (Some(600.0), Some("uniswap")));
+
+    let req = test::TestRequest::get().uri("/api/pricing/source?from=BNB&to=USDT&source=uniswap").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["price"], 600.0);
+
+    // Без source — источник по умолчанию
+    let req = test::TestRequest::get().uri("/api/pricing/source?from=ETH&to=USDT").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!((body["price"].as_f64(), body["source"].as_str()), (Some(3200.0), Some("mock")));
+
+    let req = test::TestRequest::post()
+        .uri("/api/pricing/batch")
+        .set_json(json!({ "pairs": [{ "from": "BNB", "to": "USDT" }], "source": "uniswap" }))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["prices"][0]["price"], 600.0);
+
+    let req = test::TestRequest::get().uri("/api/pricing/source?from=ETH&to=USDT&source=coingecko").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 400);
+}
+#[actix_web::test]
+async fn test_health_reports_circuit_breakers() {
+    let app_state = AppState::new().unwrap();
+    let app = test::init_service(
+        App::new()
+            .app_data(actix_web::web::Data::new(app_state))
+            .service(routes::create_routes())
+    ).await;
+
+    let req = test::TestRequest::get().uri("/api/health").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["status"], "ok");
+    assert_eq!(body["price_sources"][0]["name"], "mock");
+    assert_eq!(body["price_sources"][0]["state"], "closed");
+}
+
+#[actix_web::test]
+async fn test_circuit_opened_metric_is_counter() {
+    use prometheus::proto::MetricType;
+    use smartswap_backend::metrics::CircuitBreakerCollector;
+    use smartswap_core::price_source::CircuitBreaker;
+    use std::sync::Arc;
+
+    let breaker = Arc::new(CircuitBreaker::new("flaky", 1, std::time::Duration::from_secs(60)));
+    let registry = prometheus::Registry::new();
+    registry.register(Box::new(CircuitBreakerCollector::new("api", vec![breaker.clone()]).unwrap())).unwrap();
+
+    let opened = |registry: &prometheus::Registry| {
+        let families = registry.gather();
+        let family = families.iter().find(|f| f.get_name() == "api_price_source_circuit_opened_total").unwrap();
+        assert_eq!(family.get_field_type(), MetricType::COUNTER);
+        family.get_metric()[0].get_counter().get_value()
+    };
+    assert_eq!(opened(&registry), 0.0);
+    breaker.try_acquire().unwrap().failure();
+    assert_eq!(opened(&registry), 1.0);
+    assert_eq!(opened(&registry), 1.0);
+}
+
+#[actix_web::test]
+async fn test_batch_pricing() {
+    let app_state = AppState::new().unwrap();
+    let app = test::init_service(
+        App::new()
+            .app_data(actix_web::web::Data::new(app_state))
+            .service(routes::create_routes())
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/pricing/batch")
+        .set_json(json!({
+            "pairs": [
+                { "from": "ETH", "to": "USDT" },
+                { "from": "DOGE", "to": "USDT" },
+                { "from": "WBTC", "to": "USDT" }
+            ]
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["prices"][0]["price"], 3200.0);
+    assert!(body["prices"][1]["error"].is_string());
+    assert_eq!(body["prices"][2]["from"], "WBTC");
+
+    // Пустой список пар — ошибка
+    let req = test::TestRequest::post()
+        .uri("/api/pricing/batch")
+        .set_json(json!({ "pairs": [] }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+}
+
+#[actix_web::test]
+async fn test_price_source_cross_rate() {
+    let app_state = AppState::new().unwrap();
+    let app = test::init_service(
+        App::new()
+            .app_data(actix_web::web::Data::new(app_state))
+            .service(routes::create_routes())
+    ).await;
+
+    // ETH/WBTC напрямую не котируется, выводится через USDT
+    let req = test::TestRequest::get()
+        .uri("/api/pricing/source?from=ETH&to=WBTC")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let price = body["price"].as_f64().unwrap();
+    assert!((price - 3200.0 / 67000.0).abs() < 1e-12);
+    assert_eq!(body["path"], json!(["ETH", "USDT", "WBTC"]));
+
+    let req = test::TestRequest::get().uri("/api/pricing/source?from=ETH&to=USDT").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["path"], json!(["ETH", "USDT"]));
+}
+
+#[actix_web::test]
+async fn test_unknown_tokens_rejected() {
+    let app_state = AppState::new().unwrap();
     let app = test::init_service(
         App::new()
             .app_data(actix_web::web::Data::new(app_state))
-            .service(smartswap_backend::routes::create_routes())
+            .service(routes::create_routes())
     ).await;
 
+    let req = test::TestRequest::get().uri("/api/tokens").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["tokens"].as_array().unwrap().iter().any(|t| t["symbol"] == "WBTC" && t["decimals"] == 8));
+
+    // Опечатка в символе отклоняется одинаково в ордербуке, прайсинге и котировке
+    let req = test::TestRequest::post()
+        .uri("/api/orderbook/add")
+        .set_json(json!({ "base": "ETHH", "quote": "USDT", "amount": "1", "price": "3000", "side": "BUY" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["error"], "Unknown token: ETHH");
+
+    let req = test::TestRequest::get().uri("/api/pricing/source?from=ETHH&to=USDT").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["error"], "Unknown token: ETHH");
+
     let req = test::TestRequest::get()
-        .uri("/api/pricing/uniswap?from=ETH&to=USDT")
+        .uri("/api/swap/quote?from_token=ETHH&to_token=USDT&amount_in=1&price=3000")
         .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 400);
+
+    // Алиасы сводятся к каноническому символу: BTC → WBTC
+    let req = test::TestRequest::get().uri("/api/pricing/source?from=btc&to=USDT").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["price"], 67000.0);
+}
+
+#[actix_web::test]
+async fn test_price_stream_events() {
+    use smartswap_core::price_source::PriceUpdate;
+    use std::time::Duration;
+
+    let app_state = AppState::new().unwrap();
+    app_state.start_price_stream(Duration::from_millis(10)).await.unwrap();
+    let board = app_state.prices.clone();
+    let app = test::init_service(
+        App::new()
+            .app_data(actix_web::web::Data::new(app_state))
+            .service(routes::create_routes())
+    ).await;
+
+    let req = test::TestRequest::get().uri("/api/pricing/stream?pairs=ETH/BNB").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 400);
+    let req = test::TestRequest::get().uri("/api/pricing/stream?pairs=ETHUSDT").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 400);
+
+    let req = test::TestRequest::get().uri("/api/pricing/stream?pairs=eth/USDT").to_request();
     let resp = test::call_service(&app, req).await;
-    // Должен вернуть 200 (если Uniswap инициализирован) или 400 (если нет)
-    assert!(resp.status().is_success() || resp.status().as_u16() == 400);
-}
\ No newline at end of file
+    assert_eq!(resp.headers().get("content-type").unwrap(), "text/event-stream");
+    let mut body = resp.into_body();
+    // Поток опроса публикует цену Mock-источника, затем приходит каждое изменение
+    let first = next_event(&mut body).await;
+    assert_eq!((first.from.as_str(), first.to.as_str(), first.price), ("ETH", "USDT", 3200.0));
+    board.publish(PriceUpdate::new("WBTC", "USDT", 68000.0, 1));
+    board.publish(PriceUpdate::new("ETH", "USDT", 3150.0, 2));
+    assert_eq!(next_event(&mut body).await.price, 3150.0);
+}
+
+#[actix_web::test]
+async fn test_quote_uses_fresh_board_price() {
+    use smartswap_core::price_source::{now_ms, PriceUpdate};
+
+    let app_state = AppState::new().unwrap();
+    let board = app_state.prices.clone();
+    let app = test::init_service(
+        App::new()
+            .app_data(actix_web::web::Data::new(app_state))
+            .service(routes::create_routes())
+    ).await;
+    let quote_price = |uri: &'static str| {
+        let app = &app;
+        async move {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            let body: serde_json::Value = test::call_and_read_body_json(app, req).await;
+            body["price"].as_str().unwrap().to_string()
+        }
+    };
+    let uri = "/api/swap/quote?from_token=ETH&to_token=USDT&amount_in=1";
+
+    // Устаревшая цена доски игнорируется — цена из источника
+    board.publish(PriceUpdate::new("ETH", "USDT", 3300.0, 1));
+    assert_eq!(quote_price(uri).await, "3200");
+    board.publish(PriceUpdate::new("ETH", "USDT", 3300.0, now_ms()));
+    assert_eq!(quote_price(uri).await, "3300");
+    // Источник по умолчанию, указанный явно, — тоже с доски
+    assert_eq!(quote_price("/api/swap/quote?from_token=ETH&to_token=USDT&amount_in=1&source=mock").await, "3300");
+}
+
+#[actix_web::test]
+async fn test_orders_triggered_by_price_changes() {
+    use smartswap_core::price_source::{now_ms, PriceUpdate};
+
+    let app_state = AppState::new().unwrap();
+    app_state.start_order_triggers();
+    let (board, orderbook) = (app_state.prices.clone(), app_state.orderbook.clone());
+    let app = test::init_service(
+        App::new()
+            .app_data(actix_web::web::Data::new(app_state))
+            .service(routes::create_routes())
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/orderbook/add")
+        .set_json(json!({ "base": "ETH", "quote": "USDT", "amount": "1", "price": "3000", "side": "BUY" }))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let id = body["order_id"].as_str().unwrap().to_string();
+
+    board.publish(PriceUpdate::new("ETH", "USDT", 3100.0, now_ms()));
+    board.publish(PriceUpdate::new("ETH", "USDT", 2990.0, now_ms()));
+    let triggered = with_timeout(async {
+        loop {
+            if let Some(order) = orderbook.lock().unwrap().get_triggered().first() {
+                return order.id.to_string();
+            }
+            actix_web::rt::time::sleep(std::time::Duration::from_millis(5)).await;
+        }
+    }).await;
+    assert_eq!(triggered, Some(id));
+    assert!(orderbook.lock().unwrap().get_orders().is_empty());
+}
+
+/// Следующее SSE-событие потока цен.
+async fn next_event(body: &mut actix_web::body::BoxBody) -> smartswap_core::price_source::PriceUpdate {
+    use actix_web::body::MessageBody;
+    let chunk = futures_util::future::poll_fn(|cx| std::pin::Pin::new(&mut *body).poll_next(cx));
+    let chunk = with_timeout(chunk).await.expect("no event within timeout").unwrap().unwrap();
+    let text = String::from_utf8(chunk.to_vec()).unwrap();
+    let json = text.strip_prefix("data: ").and_then(|t| t.strip_suffix("\n\n")).unwrap();
+    serde_json::from_str(json).unwrap()
+}
+
+async fn with_timeout<F: std::future::Future>(future: F) -> Option<F::Output> {
+    let deadline = actix_web::rt::time::sleep(std::time::Duration::from_secs(2));
+    futures_util::pin_mut!(future, deadline);
+    match futures_util::future::select(future, deadline).await {
+        futures_util::future::Either::Left((output, _)) => Some(output),
+        futures_util::future::Either::Right(_) => None,
+    }
+}
+
+#[actix_web::test]
+async fn test_replay_price_source() {
+    use smartswap_backend::config::Config;
+    use smartswap_core::price_source::{PriceRecord, ReplayPriceSource};
+    use std::sync::Arc;
+
+    let record = |timestamp_ms, price| PriceRecord::new(timestamp_ms, "ETH", "USDT", price);
+    let replay = ReplayPriceSource::new(vec![record(1_700_000_000_000, 2000.0), record(1_700_000_060_000, 2100.0)]);
+    let clock = replay.clock();
+    let app = test::init_service(
+        App::new()
+            .app_data(actix_web::web::Data::new(AppState::with_price_source(&Config::default(), Arc::new(replay)).unwrap()))
+            .service(routes::create_routes())
+    ).await;
+
+    let req = test::TestRequest::get().uri("/api/pricing/source?from=ETH&to=USDT").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["price"], 2000.0);
+
+    clock.set(1_700_000_060_000);
+    let req = test::TestRequest::get().uri("/api/pricing/source?from=eth&to=USDT").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["price"], 2100.0);
+}
+
+#[actix_web::test]
+async fn test_config_from_toml_and_env() {
+    use smartswap_backend::config::{Config, SourceConfig};
+    use smartswap_core::price_source::Exchange;
+
+    let config = Config::from_toml(
+        r#"
+        markets = ["ETH/USDT"]
+
+        [server]
+        bind = "0.0.0.0:9000"
+        cors_origins = ["https://smartswap.example"]
+
+        [pricing]
+        default_source = "binance"
+
+        [pricing.sources.binance]
+        type = "cex"
+        exchange = "binance"
+
+        [pricing.sources.fixed]
+        type = "static"
+        prices = { "ETH/USDT" = 3000.0 }
+        "#,
+    )
+    .unwrap();
+    assert_eq!(config.server.bind, "0.0.0.0:9000");
+    // Не заданное в файле — по умолчанию
+    assert_eq!(config.server.price_stream_interval_secs, 5);
+    assert_eq!(config.pricing.sources["binance"], SourceConfig::Cex { exchange: Exchange::Binance, base_url: None });
+    assert!(config.validate().is_ok());
+
+    let env = |name: &str| match name {
+        "SMARTSWAP_BIND" => Some("127.0.0.1:9100".to_string()),
+        "SMARTSWAP_MARKETS" => Some("ETH/USDT, WBTC/USDT".to_string()),
+        "PRICE_REPLAY_PATH" => Some("prices.csv".to_string()),
+        "PRICE_REPLAY_SPEED" => Some("0".to_string()),
+        "CHAIN_ID" => Some("56".to_string()),
+        _ => None,
+    };
+    let config = config.with_env(env).unwrap();
+    assert_eq!(config.server.bind, "127.0.0.1:9100");
+    assert_eq!(config.chain_id, Some(56));
+    assert_eq!(config.market_pairs(), vec![("ETH", "USDT"), ("WBTC", "USDT")]);
+    assert_eq!(config.pricing.default_source, "replay");
+    assert_eq!(config.pricing.sources["replay"], SourceConfig::Replay { path: "prices.csv".into(), speed: 0.0 });
+
+    let err = Config::from_toml("[server]\nport = 80").unwrap_err();
+    assert!(err.contains("unknown field `port`"), "{err}");
+}
+
+#[actix_web::test]
+async fn test_config_validation_errors() {
+    use smartswap_backend::config::Config;
+
+    let config = Config::from_toml(
+        r#"
+        markets = ["ETH-USDT"]
+
+        [server]
+        bind = "localhost"
+        cors_origins = ["localhost:3000"]
+
+        [pricing]
+        default_source = "coingecko"
+
+        [pricing.sources.pool]
+        type = "uniswap_v2"
+        pool = "0x16b9a82891338f9ba80e2d6970fdda79d1eb0dae"
+        token0 = "BNB"
+        token1 = "USDT"
+        "#,
+    )
+    .unwrap();
+    // Все ошибки сразу, с путём до поля
+    let err = config.validate().unwrap_err();
+    assert!(err.starts_with("Invalid config: "), "{err}");
+    for expected in [
+        "server.bind: localhost is not a host:port address",
+        "server.cors_origins: localhost:3000 must be * or start with http:// or https://",
+        "pricing.default_source: coingecko is not one of the configured sources (pool)",
+        "markets: ETH-USDT is not BASE/QUOTE",
+        "pricing.sources.pool: set exactly one of rpc_url and pool_state_file",
+    ] {
+        assert!(err.contains(expected), "{err}");
+    }
+
+    // Символы рынков проверяются по реестру токенов
+    let config = Config::from_toml(r#"markets = ["ETH/DOGE"]"#).unwrap();
+    let err = AppState::from_config(&config).err().unwrap();
+    assert_eq!(err, "Invalid config: markets: Unknown token: DOGE");
+
+    // Готовые источники тоже получают реестр из конфигурации: ошибка, а не паника
+    let config = Config::from_toml("[paths]\ntoken_registry = \"missing_tokens.json\"").unwrap();
+    let sources = AppState::new().unwrap().price_sources;
+    let err = AppState::with_price_sources(&config, sources).err().unwrap();
+    assert!(err.contains("missing_tokens.json"), "{err}");
+}
+
+#[actix_web::test]
+async fn test_confirmation_source_from_config() {
+    use smartswap_backend::config::{ChainlinkFeedConfig, Config, SourceConfig};
+
+    let toml = r#"
+        [pricing]
+        default_source = "fixed"
+        confirmation_source = "oracle"
+
+        [pricing.sources.fixed]
+        type = "static"
+        prices = { "ETH/USDT" = 3000.0 }
+
+        [pricing.sources.oracle]
+        type = "chainlink"
+        rpc_url = "http://127.0.0.1:8545"
+        feeds = { "ETH/USD" = { address = "0x5f4eC3Df9cbd43714FE2740f5E3616155c5b8419", heartbeat_secs = 3600 } }
+        "#;
+    let config = Config::from_toml(toml).unwrap();
+    assert_eq!(
+        config.pricing.sources["oracle"],
+        SourceConfig::Chainlink {
+            rpc_url: "http://127.0.0.1:8545".to_string(),
+            feeds: [("ETH/USD".to_string(), ChainlinkFeedConfig {
+                address: "0x5f4eC3Df9cbd43714FE2740f5E3616155c5b8419".to_string(),
+                heartbeat_secs: 3600,
+            })]
+            .into(),
+        }
+    );
+    // Нода при сборке не нужна
+    let app_state = AppState::from_config(&config).unwrap();
+    assert_eq!(app_state.price_sources.names(), vec!["fixed", "oracle"]);
+    assert_eq!(app_state.price_source().get_price("ETH", "USDT", None).await.unwrap(), 3000.0);
+
+    let config = Config::from_toml(&toml.replace(r#"confirmation_source = "oracle""#, r#"confirmation_source = "twap""#)).unwrap();
+    let err = config.validate().unwrap_err();
+    assert!(err.contains("pricing.confirmation_source: twap is not one of the configured sources"), "{err}");
+    let config = Config::from_toml(&toml.replace(r#"confirmation_source = "oracle""#, r#"confirmation_source = "fixed""#)).unwrap();
+    let err = config.validate().unwrap_err();
+    assert!(err.contains("pricing.confirmation_source: fixed must differ from pricing.default_source"), "{err}");
+    let config = Config::from_toml(&toml.replace("heartbeat_secs = 3600", "heartbeat_secs = 0")).unwrap();
+    let err = config.validate().unwrap_err();
+    assert!(err.contains("pricing.sources.oracle.feeds.ETH/USD.heartbeat_secs must be positive"), "{err}");
+}
+
+#[actix_web::test]
+async fn test_fallback_source_survives_outage() {
+    use smartswap_backend::config::Config;
+
+    // CoinGecko недоступен (никто не слушает порт) — цена приходит от следующего звена
+    let toml = r#"
+        [pricing]
+        default_source = "chain"
+
+        [pricing.sources.chain]
+        type = "fallback"
+        sources = ["coingecko", "fixed"]
+        timeout_ms = 500
+
+        [pricing.sources.coingecko]
+        type = "coingecko"
+        base_url = "http://127.0.0.1:9"
+
+        [pricing.sources.fixed]
+        type = "static"
+        prices = { "ETH/USDT" = 3000.0 }
+        "#;
+    let config = Config::from_toml(toml).unwrap();
+    let app_state = AppState::from_config(&config).unwrap();
+    assert_eq!(app_state.price_sources.names(), vec!["chain", "coingecko", "fixed"]);
+    assert!(app_state.quotes_from_board);
+    assert_eq!(app_state.price_source().get_price("ETH", "USDT", None).await.unwrap(), 3000.0);
+    let err = app_state.price_source().get_price("DOGE", "USDT", None).await.unwrap_err();
+    assert!(err.to_string().contains("coingecko"), "{err}");
+
+    let config = Config::from_toml(&toml.replace(r#"["coingecko", "fixed"]"#, r#"["coingecko", "chain", "cex"]"#)).unwrap();
+    let err = config.validate().unwrap_err();
+    assert!(err.contains("pricing.sources.chain.sources: chain is a fallback chain itself"), "{err}");
+    assert!(err.contains("pricing.sources.chain.sources: cex is not one of the configured sources"), "{err}");
+}
+
+#[actix_web::test]
+async fn test_app_state_from_config() {
+    use smartswap_backend::config::Config;
+
+    let config = Config::from_toml(&format!(
+        r#"
+        markets = ["WBNB/USDT"]
+        chain_id = 56
+
+        [pricing]
+        default_source = "pool"
+
+        [pricing.sources.pool]
+        type = "uniswap_v2"
+        pool = "0x16b9a82891338f9ba80e2d6970fdda79d1eb0dae"
+        token0 = "WBNB"
+        token1 = "USDT"
+        pool_state_file = "{}"
+
+        [pricing.sources.fixed]
+        type = "static"
+        prices = {{ "ETH/USDT" = 3000.0 }}
+        "#,
+        concat!(env!("CARGO_MANIFEST_DIR"), "/../core/tests/fixtures/pools.json")
+    ))
+    .unwrap();
+    let app_state = AppState::from_config(&config).unwrap();
+    assert_eq!(app_state.price_sources.names(), vec!["fixed", "pool"]);
+    assert_eq!(app_state.markets, vec![("WBNB".to_string(), "USDT".to_string())]);
+    // USDT сети BSC: 18 знаков без переопределения decimals в пуле
+    assert_eq!(app_state.tokens.resolve("USDT").unwrap().decimals, 18);
+    let breakers: Vec<&str> = app_state.circuit_breakers.iter().map(|b| b.name()).collect();
+    assert_eq!(breakers, vec!["fixed", "pool"]);
+
+    let price = app_state.price_source().get_price("WBNB", "USDT", None).await.unwrap();
+    assert!((price - 600.0).abs() < 1e-9, "{price}");
+    let fixed = app_state.price_sources.get(Some("fixed")).unwrap();
+    assert_eq!(fixed.get_price("USDT", "ETH", None).await.unwrap(), 1.0 / 3000.0);
+}
+
+#[actix_web::test]
+async fn test_exact_out_quote_priced_on_pool_curve() {
+    use smartswap_backend::config::Config;
+    use smartswap_core::amm::{format_units, parse_units, uniswap_v2};
+
+    let config = Config::from_toml(&format!(
+        r#"
+        markets = ["WBNB/USDT"]
+        chain_id = 56
+
+        [pricing]
+        default_source = "pool"
+
+        [pricing.sources.pool]
+        type = "uniswap_v2"
+        pool = "0x16b9a82891338f9ba80e2d6970fdda79d1eb0dae"
+        token0 = "WBNB"
+        token1 = "USDT"
+        pool_state_file = "{}"
+        "#,
+        concat!(env!("CARGO_MANIFEST_DIR"), "/../core/tests/fixtures/pools.json")
+    ))
+    .unwrap();
+    let app = test::init_service(
+        App::new()
+            .app_data(actix_web::web::Data::new(AppState::from_config(&config).unwrap()))
+            .service(routes::create_routes())
+    ).await;
+
+    // Резервы последнего блока фикстуры: 1000 WBNB / 600000 USDT, спот 600
+    let req = test::TestRequest::get()
+        .uri("/api/swap/quote?from_token=WBNB&to_token=USDT&mode=exact_out&amount_out=60000")
+        .to_request();
+    let quote: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let expected = uniswap_v2::get_amount_in(
+        parse_units("60000", 18).unwrap(),
+        parse_units("1000", 18).unwrap(),
+        parse_units("600000", 18).unwrap(),
+        uniswap_v2::DEFAULT_FEE_BPS,
+    ).unwrap();
+    // Вход по getAmountIn — с комиссией и price impact, а не 60000 / 600 = 100 по споту
+    assert_eq!(quote["amount_in"], format_units(expected, 18));
+    assert!(quote["amount_in"].as_str().unwrap().parse::<f64>().unwrap() > 111.0);
+    assert_eq!(quote["amount_out"], "60000");
+    assert_eq!(quote["source"], "pool");
+    let price: f64 = quote["price"].as_str().unwrap().parse().unwrap();
+    assert!(price < 540.0, "{price}");
+
+    // Больше резерва пул не отдаст
+    let req = test::TestRequest::get()
+        .uri("/api/swap/quote?from_token=WBNB&to_token=USDT&mode=exact_out&amount_out=600000")
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 400);
+}
+
+/// HTTP-заглушка на отдельном потоке: на любой запрос — `200` с `body`. Возвращает адрес и счётчик запросов.
+fn http_stub(body: &'static str) -> (String, std::sync::Arc<std::sync::atomic::AtomicUsize>) {
+    use std::io::{Read, Write};
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let requests = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let seen = requests.clone();
+    std::thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut buf = [0u8; 16 * 1024];
+            let _ = stream.read(&mut buf);
+            seen.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            let _ = stream.write_all(response.as_bytes());
+        }
+    });
+    (url, requests)
+}
+
+#[actix_web::test]
+async fn test_batch_pricing_single_upstream_call_through_chain() {
+    use smartswap_backend::config::Config;
+    use std::sync::atomic::Ordering;
+
+    let (url, requests) = http_stub(
+        r#"{"ethereum":{"tether":3000.0},"wrapped-bitcoin":{"tether":60000.0},"tether":{"wrapped-bitcoin":0.00002}}"#,
+    );
+    let record_dir = std::env::temp_dir().join(format!("batch_chain_{}", uuid::Uuid::new_v4()));
+    let config = Config::from_toml(&format!(
+        r#"
+        [pricing]
+        default_source = "coingecko"
+
+        [pricing.sources.coingecko]
+        type = "coingecko"
+        base_url = "{url}"
+
+        [paths]
+        price_record_dir = "{}"
+        "#,
+        record_dir.display()
+    ))
+    .unwrap();
+    // Recording(Guarded(Triangulating(Resilient(coingecko))))
+    let app = test::init_service(
+        App::new()
+            .app_data(actix_web::web::Data::new(AppState::from_config(&config).unwrap()))
+            .service(routes::create_routes())
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/pricing/batch")
+        .set_json(json!({
+            "pairs": [
+                { "from": "ETH", "to": "USDT" },
+                { "from": "WBTC", "to": "USDT" },
+                { "from": "ETH", "to": "WBTC" }
+            ]
+        }))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    std::fs::remove_dir_all(&record_dir).ok();
+    assert_eq!(body["prices"][0]["price"], 3000.0);
+    assert_eq!(body["prices"][1]["price"], 60000.0);
+    let cross = body["prices"][2]["price"].as_f64().unwrap();
+    assert!((cross - 0.06).abs() < 1e-12, "{body}");
+    // Один запрос на прямые пары и один на звенья кросс-курса, а не по запросу на пару
+    assert_eq!(requests.load(Ordering::SeqCst), 2);
+}
@@ -0,0 +1,146 @@
+//! Чистая математика AMM-пулов: без RPC и сети, только снимки состояния пулов.
+//! Суммы — целые base units (wei) в `U256`, как в контрактах.
+pub use primitive_types::U256;
+use std::fmt;
+
+pub mod stableswap;
+pub mod uniswap_v2;
+pub mod uniswap_v3;
+pub mod weighted;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AmmError {
+    InsufficientInputAmount,
+    InsufficientOutputAmount,
+    InsufficientLiquidity,
+    Overflow,
+    InvalidAmount(String),
+}
+
+impl fmt::Display for AmmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AmmError::InsufficientInputAmount => write!(f, "Insufficient input amount"),
+            AmmError::InsufficientOutputAmount => write!(f, "Insufficient output amount"),
+            AmmError::InsufficientLiquidity => write!(f, "Insufficient liquidity"),
+            AmmError::Overflow => write!(f, "Arithmetic overflow"),
+            AmmError::InvalidAmount(e) => write!(f, "Invalid amount: {e}"),
+        }
+    }
+}
+impl std::error::Error for AmmError {}
+
+/// Результат расчёта свопа по кривой пула. Цены — в человеческих единицах
+/// (выход за единицу входа), `price_impact` — доля ухудшения относительно спота.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AmmQuote {
+    pub amount_in: U256,
+    pub amount_out: U256,
+    pub spot_price: f64,
+    pub effective_price: f64,
+    pub price_impact: f64,
+}
+
+impl AmmQuote {
+    pub fn new(amount_in: U256, amount_out: U256, spot_price: f64, decimals_in: u8, decimals_out: u8) -> Self {
+        let effective_price = to_f64(amount_out, decimals_out) / to_f64(amount_in, decimals_in);
+        let price_impact = if spot_price > 0.0 { 1.0 - effective_price / spot_price } else { 0.0 };
+        Self { amount_in, amount_out, spot_price, effective_price, price_impact }
+    }
+}
+
+/// Вход для точного выхода `amount_out`, согласованный с exact-in моделью пула: оценка `estimate`
+/// (уже округлённая вверх) растёт, пока `out_given_in` не даст хотя бы `amount_out`.
+/// Так погрешности приближённых формул не уходят в пользу трейдера.
+pub(crate) fn settle_amount_in(
+    estimate: U256,
+    amount_out: U256,
+    out_given_in: impl Fn(U256) -> Result<U256, AmmError>,
+) -> Result<U256, AmmError> {
+    let mut amount_in = estimate.max(U256::one());
+    let mut step = (amount_in / U256::exp10(12)).max(U256::one());
+    for _ in 0..64 {
+        if out_given_in(amount_in)? >= amount_out {
+            return Ok(amount_in);
+        }
+        amount_in = amount_in.checked_add(step).ok_or(AmmError::Overflow)?;
+        step = step.checked_mul(U256::from(2)).ok_or(AmmError::Overflow)?;
+    }
+    Err(AmmError::InsufficientLiquidity)
+}
+
+/// Деление с округлением вверх.
+pub(crate) fn div_ceil(a: U256, b: U256) -> U256 {
+    let (q, r) = a.div_mod(b);
+    if r.is_zero() { q } else { q + 1 }
+}
+
+/// Десятичная строка ("1.5") в base units с `decimals` знаками.
+pub fn parse_units(amount: &str, decimals: u8) -> Result<U256, AmmError> {
+    let amount = amount.trim();
+    let (int_part, frac_part) = amount.split_once('.').unwrap_or((amount, ""));
+    let valid = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+    if (int_part.is_empty() && frac_part.is_empty()) || !valid(int_part) || !valid(frac_part) {
+        return Err(AmmError::InvalidAmount(amount.to_string()));
+    }
+    if frac_part.len() > decimals as usize {
+        return Err(AmmError::InvalidAmount(format!("{amount} has more than {decimals} decimal places")));
+    }
+    let digits = format!("{int_part}{frac_part:0<width$}", width = decimals as usize);
+    let digits = digits.trim_start_matches('0');
+    if digits.is_empty() {
+        return Ok(U256::zero());
+    }
+    U256::from_dec_str(digits).map_err(|_| AmmError::Overflow)
+}
+
+/// Base units в десятичную строку без хвостовых нулей ("1500000", 6 → "1.5").
+pub fn format_units(amount: U256, decimals: u8) -> String {
+    let digits = amount.to_string();
+    let decimals = decimals as usize;
+    if decimals == 0 {
+        return digits;
+    }
+    let padded = format!("{digits:0>width$}", width = decimals + 1);
+    let (int_part, frac_part) = padded.split_at(padded.len() - decimals);
+    let frac_part = frac_part.trim_end_matches('0');
+    if frac_part.is_empty() {
+        int_part.to_string()
+    } else {
+        format!("{int_part}.{frac_part}")
+    }
+}
+
+/// Приближённое значение base units в человеческих единицах.
+pub fn to_f64(amount: U256, decimals: u8) -> f64 {
+    let raw = amount
+        .0
+        .iter()
+        .rev()
+        .fold(0.0, |acc, limb| acc * 18_446_744_073_709_551_616.0 + *limb as f64);
+    raw / 10f64.powi(decimals as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_format_units() {
+        assert_eq!(parse_units("1.5", 6).unwrap(), U256::from(1_500_000u64));
+        assert_eq!(parse_units("0.000000000000000001", 18).unwrap(), U256::one());
+        assert_eq!(parse_units("42", 0).unwrap(), U256::from(42u64));
+        assert!(parse_units("1.1234567", 6).is_err());
+        assert!(parse_units("abc", 18).is_err());
+        assert!(parse_units("-1", 18).is_err());
+        assert_eq!(format_units(U256::from(1_500_000u64), 6), "1.5");
+        assert_eq!(format_units(U256::from(5u64), 3), "0.005");
+        assert_eq!(format_units(U256::from(7_000u64), 3), "7");
+    }
+
+    #[test]
+    fn test_to_f64() {
+        let wei = parse_units("1234.5", 18).unwrap();
+        assert!((to_f64(wei, 18) - 1234.5).abs() < 1e-9);
+    }
+}
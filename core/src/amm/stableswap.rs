@@ -0,0 +1,275 @@
+//! Curve StableSwap: инвариант A·n^n·ΣX + D = A·n^n·D + D^(n+1) / (n^n·ΠX).
+//! Порт `get_D` / `get_y` / `get_dy` из 3pool (Vyper), балансы приводятся к 18 знакам.
+use super::{div_ceil, settle_amount_in, to_f64, AmmError, AmmQuote, U256};
+
+/// Знаменатель комиссии Curve: fee = 4_000_000 означает 0.04%
+pub const FEE_DENOMINATOR: u64 = 10_000_000_000;
+const MAX_ITERATIONS: usize = 255;
+
+/// Снимок пула. `amp` — параметр A в форме контракта (A·n^(n-1), без A_PRECISION).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StableSwapPool {
+    pub balances: Vec<U256>,
+    pub decimals: Vec<u8>,
+    pub amp: u64,
+    pub fee: u64,
+}
+
+impl StableSwapPool {
+    pub fn new(balances: Vec<U256>, decimals: Vec<u8>, amp: u64, fee: u64) -> Result<Self, AmmError> {
+        if balances.len() < 2 || balances.len() != decimals.len() {
+            return Err(AmmError::InvalidAmount("pool needs at least 2 coins with decimals".to_string()));
+        }
+        if decimals.iter().any(|d| *d > 18) || amp == 0 || fee >= FEE_DENOMINATOR {
+            return Err(AmmError::InvalidAmount("invalid pool parameters".to_string()));
+        }
+        Ok(Self { balances, decimals, amp, fee })
+    }
+
+    fn multiplier(&self, i: usize) -> U256 {
+        U256::exp10(18 - self.decimals[i] as usize)
+    }
+
+    /// Балансы, приведённые к 18 знакам
+    fn xp(&self) -> Result<Vec<U256>, AmmError> {
+        self.balances
+            .iter()
+            .enumerate()
+            .map(|(i, b)| b.checked_mul(self.multiplier(i)).ok_or(AmmError::Overflow))
+            .collect()
+    }
+
+    fn check_indices(&self, i: usize, j: usize) -> Result<(), AmmError> {
+        if i == j || i >= self.balances.len() || j >= self.balances.len() {
+            return Err(AmmError::InvalidAmount(format!("invalid coin indices {i}, {j}")));
+        }
+        Ok(())
+    }
+
+    /// Инвариант D по методу Ньютона.
+    pub fn get_d(&self) -> Result<U256, AmmError> {
+        get_d(&self.xp()?, self.amp)
+    }
+
+    /// Выход монеты `j` за `dx` монеты `i` (base units) с учётом комиссии.
+    pub fn get_dy(&self, i: usize, j: usize, dx: U256) -> Result<U256, AmmError> {
+        self.check_indices(i, j)?;
+        if dx.is_zero() {
+            return Err(AmmError::InsufficientInputAmount);
+        }
+        let xp = self.xp()?;
+        let x = xp[i].checked_add(dx.checked_mul(self.multiplier(i)).ok_or(AmmError::Overflow)?).ok_or(AmmError::Overflow)?;
+        let y = get_y(i, j, x, &xp, self.amp)?;
+        if xp[j] <= y + 1 {
+            return Err(AmmError::InsufficientLiquidity);
+        }
+        let dy = (xp[j] - y - 1) / self.multiplier(j);
+        let fee = mul(dy, U256::from(self.fee))? / U256::from(FEE_DENOMINATOR);
+        Ok(dy - fee)
+    }
+
+    /// Вход монеты `i` для точного выхода `dy` монеты `j` (после комиссии), округлён вверх:
+    /// `get_dy` от результата не меньше `dy`.
+    pub fn get_dx(&self, i: usize, j: usize, dy: U256) -> Result<U256, AmmError> {
+        self.check_indices(i, j)?;
+        if dy.is_zero() {
+            return Err(AmmError::InsufficientOutputAmount);
+        }
+        let xp = self.xp()?;
+        // Выход до комиссии, как его видит get_dy
+        let fee_denominator = U256::from(FEE_DENOMINATOR);
+        let dy_before_fee = div_ceil(
+            dy.checked_mul(fee_denominator).ok_or(AmmError::Overflow)?,
+            fee_denominator - U256::from(self.fee),
+        );
+        let dy_xp = dy_before_fee.checked_mul(self.multiplier(j)).ok_or(AmmError::Overflow)?;
+        if dy_xp.checked_add(U256::one()).ok_or(AmmError::Overflow)? >= xp[j] {
+            return Err(AmmError::InsufficientLiquidity);
+        }
+        let x = get_y(j, i, xp[j] - dy_xp - 1, &xp, self.amp)?;
+        let estimate = div_ceil(x.saturating_sub(xp[i]), self.multiplier(i));
+        settle_amount_in(estimate, dy, |dx| self.get_dy(i, j, dx))
+    }
+
+    /// Предельная цена `j` за единицу `i` (без комиссии): отношение частных производных инварианта.
+    pub fn spot_price(&self, i: usize, j: usize) -> Result<f64, AmmError> {
+        self.check_indices(i, j)?;
+        let xp = self.xp()?;
+        if xp.iter().any(|x| x.is_zero()) {
+            return Err(AmmError::InsufficientLiquidity);
+        }
+        let n = self.balances.len() as f64;
+        let d = to_f64(get_d(&xp, self.amp)?, 18);
+        let x: Vec<f64> = xp.iter().map(|x| to_f64(*x, 18)).collect();
+        let ann = self.amp as f64 * n;
+        // D^(n+1) / (n^n · ΠX), посчитанное через отношения, чтобы не переполнить f64
+        let d_p = x.iter().fold(d, |acc, xk| acc * d / (n * xk));
+        Ok((ann + d_p / x[i]) / (ann + d_p / x[j]))
+    }
+
+    pub fn quote(&self, i: usize, j: usize, dx: U256) -> Result<AmmQuote, AmmError> {
+        let dy = self.get_dy(i, j, dx)?;
+        let spot = self.spot_price(i, j)?;
+        Ok(AmmQuote::new(dx, dy, spot, self.decimals[i], self.decimals[j]))
+    }
+
+    pub fn quote_exact_out(&self, i: usize, j: usize, dy: U256) -> Result<AmmQuote, AmmError> {
+        let dx = self.get_dx(i, j, dy)?;
+        let spot = self.spot_price(i, j)?;
+        Ok(AmmQuote::new(dx, dy, spot, self.decimals[i], self.decimals[j]))
+    }
+}
+
+pub fn get_d(xp: &[U256], amp: u64) -> Result<U256, AmmError> {
+    let n = U256::from(xp.len());
+    let sum = xp.iter().try_fold(U256::zero(), |acc, x| acc.checked_add(*x)).ok_or(AmmError::Overflow)?;
+    if sum.is_zero() {
+        return Ok(U256::zero());
+    }
+    if xp.iter().any(|x| x.is_zero()) {
+        return Err(AmmError::InsufficientLiquidity);
+    }
+    let ann = mul(U256::from(amp), n)?;
+    let mut d = sum;
+    for _ in 0..MAX_ITERATIONS {
+        let mut d_p = d;
+        for x in xp {
+            d_p = div(mul(d_p, d)?, mul(*x, n)?)?;
+        }
+        let d_prev = d;
+        let numerator = mul(add(mul(ann, sum)?, mul(d_p, n)?)?, d)?;
+        let denominator = add(mul(ann - 1, d)?, mul(n + 1, d_p)?)?;
+        d = div(numerator, denominator)?;
+        if abs_diff(d, d_prev) <= U256::one() {
+            return Ok(d);
+        }
+    }
+    Err(AmmError::InvalidAmount("get_D did not converge".to_string()))
+}
+
+/// Новый баланс монеты `j`, если баланс монеты `i` стал `x` (все в 18 знаках).
+pub fn get_y(i: usize, j: usize, x: U256, xp: &[U256], amp: u64) -> Result<U256, AmmError> {
+    let n = U256::from(xp.len());
+    let d = get_d(xp, amp)?;
+    let ann = mul(U256::from(amp), n)?;
+    let mut c = d;
+    let mut s = U256::zero();
+    for (k, xk) in xp.iter().enumerate() {
+        let xk = if k == i {
+            x
+        } else if k != j {
+            *xk
+        } else {
+            continue;
+        };
+        s = add(s, xk)?;
+        c = div(mul(c, d)?, mul(xk, n)?)?;
+    }
+    c = div(mul(c, d)?, mul(ann, n)?)?;
+    let b = add(s, d / ann)?;
+    let mut y = d;
+    for _ in 0..MAX_ITERATIONS {
+        let y_prev = y;
+        let denominator = add(mul(y, U256::from(2))?, b)?.checked_sub(d).ok_or(AmmError::Overflow)?;
+        y = div(add(mul(y, y)?, c)?, denominator)?;
+        if abs_diff(y, y_prev) <= U256::one() {
+            return Ok(y);
+        }
+    }
+    Err(AmmError::InvalidAmount("get_y did not converge".to_string()))
+}
+
+fn mul(a: U256, b: U256) -> Result<U256, AmmError> {
+    a.checked_mul(b).ok_or(AmmError::Overflow)
+}
+
+fn add(a: U256, b: U256) -> Result<U256, AmmError> {
+    a.checked_add(b).ok_or(AmmError::Overflow)
+}
+
+/// Деление на ноль (нулевой баланс) — тоже ошибка, а не паника
+fn div(a: U256, b: U256) -> Result<U256, AmmError> {
+    a.checked_div(b).ok_or(AmmError::Overflow)
+}
+
+fn abs_diff(a: U256, b: U256) -> U256 {
+    if a > b { a - b } else { b - a }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amm::parse_units;
+
+    /// 3pool-подобный снимок: DAI (18), USDC (6), USDT (6), A = 2000, комиссия 0.04%
+    fn three_pool(dai: &str, usdc: &str, usdt: &str) -> StableSwapPool {
+        StableSwapPool::new(
+            vec![parse_units(dai, 18).unwrap(), parse_units(usdc, 6).unwrap(), parse_units(usdt, 6).unwrap()],
+            vec![18, 6, 6],
+            2000,
+            4_000_000,
+        ).unwrap()
+    }
+
+    #[test]
+    fn test_balanced_pool_invariant_equals_sum() {
+        let pool = three_pool("1000000", "1000000", "1000000");
+        assert_eq!(pool.get_d().unwrap(), parse_units("3000000", 18).unwrap());
+        assert!((pool.spot_price(0, 1).unwrap() - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_get_dy_balanced_is_one_minus_fee() {
+        let pool = three_pool("1000000", "1000000", "1000000");
+        let dy = pool.get_dy(1, 2, parse_units("1000", 6).unwrap()).unwrap();
+        // 1000 USDC → ~999.6 USDT: комиссия 0.04% и почти нулевой impact
+        let out = to_f64(dy, 6);
+        assert!(out > 999.59 && out < 999.6, "got {out}");
+    }
+
+    #[test]
+    fn test_imbalanced_pool_beats_constant_product() {
+        let pool = three_pool("1000000", "500000", "1500000");
+        let spot = pool.spot_price(2, 1).unwrap();
+        // USDT в избытке дешевле USDC, но намного ближе к 1, чем 0.5/1.5 у x*y=k
+        assert!(spot < 1.0 && spot > 0.99, "got {spot}");
+        let q = pool.quote(2, 1, parse_units("10000", 6).unwrap()).unwrap();
+        assert!(q.price_impact > 0.0 && q.price_impact < 0.001);
+    }
+
+    #[test]
+    fn test_get_dx_inverts_get_dy() {
+        let pool = three_pool("1000000", "500000", "1500000");
+        // DAI (18 знаков) за ровно 10 000 USDC (6 знаков) и обратно
+        for (i, j, dy) in [(0, 1, parse_units("10000", 6).unwrap()), (1, 0, parse_units("10000", 18).unwrap())] {
+            let dx = pool.get_dx(i, j, dy).unwrap();
+            assert!(pool.get_dy(i, j, dx).unwrap() >= dy);
+            // Перебор не больше погрешности Ньютона: на 1e-9 меньше — уже не хватает
+            assert!(pool.get_dy(i, j, dx - dx / U256::exp10(9)).unwrap() < dy);
+        }
+        let q = pool.quote_exact_out(2, 1, parse_units("1000", 6).unwrap()).unwrap();
+        assert!(q.effective_price < q.spot_price);
+        assert_eq!(pool.get_dx(0, 1, U256::zero()), Err(AmmError::InsufficientOutputAmount));
+        assert_eq!(pool.get_dx(0, 1, parse_units("500000", 6).unwrap()), Err(AmmError::InsufficientLiquidity));
+    }
+
+    #[test]
+    fn test_invalid_inputs() {
+        let pool = three_pool("1000", "1000", "1000");
+        assert!(pool.get_dy(0, 0, U256::one()).is_err());
+        assert!(pool.get_dy(0, 5, U256::one()).is_err());
+        assert_eq!(pool.get_dy(0, 1, U256::zero()), Err(AmmError::InsufficientInputAmount));
+        assert!(StableSwapPool::new(vec![U256::one()], vec![18], 100, 0).is_err());
+    }
+
+    #[test]
+    fn test_huge_balances_overflow_is_an_error() {
+        // Балансы у предела U256: произведения в Ньютоне не помещаются
+        let huge = U256::MAX / U256::from(4);
+        assert_eq!(get_d(&[huge, huge], 2000), Err(AmmError::Overflow));
+        assert_eq!(get_y(0, 1, huge, &[huge, huge], 2000), Err(AmmError::Overflow));
+        let pool = StableSwapPool::new(vec![huge, huge], vec![18, 18], 2000, 4_000_000).unwrap();
+        assert_eq!(pool.get_dy(0, 1, U256::one()), Err(AmmError::Overflow));
+        assert!(pool.spot_price(0, 1).is_err());
+    }
+}
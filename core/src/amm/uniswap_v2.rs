@@ -0,0 +1,220 @@
+//! Constant-product пул (Uniswap V2 / PancakeSwap): x * y = k с комиссией на входе.
+use super::{to_f64, AmmError, AmmQuote, U256};
+
+/// Комиссия Uniswap V2 — 0.3%
+pub const DEFAULT_FEE_BPS: u32 = 30;
+const BPS: u32 = 10_000;
+
+/// Доля входа после комиссии в bps; комиссия 100% и выше — ошибка, а не переполнение.
+fn fee_multiplier(fee_bps: u32) -> Result<U256, AmmError> {
+    match BPS.checked_sub(fee_bps) {
+        Some(multiplier) if multiplier > 0 => Ok(U256::from(multiplier)),
+        _ => Err(AmmError::InvalidAmount(format!("fee {fee_bps} bps must be below {BPS}"))),
+    }
+}
+
+/// Снимок резервов пары в base units.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct V2Reserves {
+    pub reserve0: U256,
+    pub reserve1: U256,
+}
+
+/// `getAmountOut` из UniswapV2Library: выход за `amount_in` с учётом комиссии `fee_bps`.
+pub fn get_amount_out(amount_in: U256, reserve_in: U256, reserve_out: U256, fee_bps: u32) -> Result<U256, AmmError> {
+    if amount_in.is_zero() {
+        return Err(AmmError::InsufficientInputAmount);
+    }
+    if reserve_in.is_zero() || reserve_out.is_zero() {
+        return Err(AmmError::InsufficientLiquidity);
+    }
+    let amount_in_with_fee = amount_in.checked_mul(fee_multiplier(fee_bps)?).ok_or(AmmError::Overflow)?;
+    let numerator = amount_in_with_fee.checked_mul(reserve_out).ok_or(AmmError::Overflow)?;
+    let denominator = reserve_in
+        .checked_mul(U256::from(BPS))
+        .and_then(|r| r.checked_add(amount_in_with_fee))
+        .ok_or(AmmError::Overflow)?;
+    Ok(numerator / denominator)
+}
+
+/// `getAmountIn` из UniswapV2Library: вход для точного выхода `amount_out`, округлён вверх.
+pub fn get_amount_in(amount_out: U256, reserve_in: U256, reserve_out: U256, fee_bps: u32) -> Result<U256, AmmError> {
+    if amount_out.is_zero() {
+        return Err(AmmError::InsufficientOutputAmount);
+    }
+    if reserve_in.is_zero() || reserve_out.is_zero() || amount_out >= reserve_out {
+        return Err(AmmError::InsufficientLiquidity);
+    }
+    let numerator = reserve_in
+        .checked_mul(amount_out)
+        .and_then(|n| n.checked_mul(U256::from(BPS)))
+        .ok_or(AmmError::Overflow)?;
+    let denominator = (reserve_out - amount_out).checked_mul(fee_multiplier(fee_bps)?).ok_or(AmmError::Overflow)?;
+    Ok(numerator / denominator + 1)
+}
+
+/// Спот-цена `out` за единицу `in` в человеческих единицах (без комиссии).
+pub fn spot_price(reserve_in: U256, reserve_out: U256, decimals_in: u8, decimals_out: u8) -> Result<f64, AmmError> {
+    if reserve_in.is_zero() || reserve_out.is_zero() {
+        return Err(AmmError::InsufficientLiquidity);
+    }
+    Ok(to_f64(reserve_out, decimals_out) / to_f64(reserve_in, decimals_in))
+}
+
+/// Полная котировка: выход, эффективная цена и price impact относительно спота.
+pub fn quote(
+    amount_in: U256,
+    reserve_in: U256,
+    reserve_out: U256,
+    decimals_in: u8,
+    decimals_out: u8,
+    fee_bps: u32,
+) -> Result<AmmQuote, AmmError> {
+    let amount_out = get_amount_out(amount_in, reserve_in, reserve_out, fee_bps)?;
+    let spot = spot_price(reserve_in, reserve_out, decimals_in, decimals_out)?;
+    Ok(AmmQuote::new(amount_in, amount_out, spot, decimals_in, decimals_out))
+}
+
+/// Котировка exact-out: сколько отдать за ровно `amount_out`.
+pub fn quote_exact_out(
+    amount_out: U256,
+    reserve_in: U256,
+    reserve_out: U256,
+    decimals_in: u8,
+    decimals_out: u8,
+    fee_bps: u32,
+) -> Result<AmmQuote, AmmError> {
+    let amount_in = get_amount_in(amount_out, reserve_in, reserve_out, fee_bps)?;
+    let spot = spot_price(reserve_in, reserve_out, decimals_in, decimals_out)?;
+    Ok(AmmQuote::new(amount_in, amount_out, spot, decimals_in, decimals_out))
+}
+
+/// Накопленные цены пары на момент `timestamp` (unix-секунды по модулю 2^32, как в контракте).
+/// `price0_cumulative` — сумма reserve1/reserve0 в UQ112x112, умноженная на секунды.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct V2Cumulative {
+    pub price0_cumulative: U256,
+    pub price1_cumulative: U256,
+    pub timestamp: u32,
+}
+
+/// `UniswapV2OracleLibrary.currentCumulativePrices`: дописывает к `*CumulativeLast` накопление
+/// с последнего обновления резервов до `now`, не дожидаясь следующего свопа.
+pub fn current_cumulative(
+    price0_cumulative_last: U256,
+    price1_cumulative_last: U256,
+    block_timestamp_last: u32,
+    reserves: V2Reserves,
+    now: u32,
+) -> V2Cumulative {
+    let mut cumulative = V2Cumulative {
+        price0_cumulative: price0_cumulative_last,
+        price1_cumulative: price1_cumulative_last,
+        timestamp: now,
+    };
+    let elapsed = now.wrapping_sub(block_timestamp_last);
+    if elapsed > 0 && !reserves.reserve0.is_zero() && !reserves.reserve1.is_zero() {
+        let elapsed = U256::from(elapsed);
+        let price0 = (reserves.reserve1 << 112) / reserves.reserve0;
+        let price1 = (reserves.reserve0 << 112) / reserves.reserve1;
+        // Переполнение накопителя ожидаемо: разности считаются по модулю 2^256
+        cumulative.price0_cumulative = cumulative.price0_cumulative.overflowing_add(price0.overflowing_mul(elapsed).0).0;
+        cumulative.price1_cumulative = cumulative.price1_cumulative.overflowing_add(price1.overflowing_mul(elapsed).0).0;
+    }
+    cumulative
+}
+
+/// Средние цены между двумя наблюдениями в человеческих единицах:
+/// (token1 за token0, token0 за token1).
+pub fn twap(start: &V2Cumulative, end: &V2Cumulative, decimals0: u8, decimals1: u8) -> Result<(f64, f64), AmmError> {
+    let elapsed = end.timestamp.wrapping_sub(start.timestamp);
+    if elapsed == 0 {
+        return Err(AmmError::InvalidAmount("TWAP needs observations at different timestamps".to_string()));
+    }
+    let q112 = 2f64.powi(112);
+    let average = |from: U256, to: U256| to_f64(to.overflowing_sub(from).0 / U256::from(elapsed), 0) / q112;
+    let scale = 10f64.powi(decimals0 as i32 - decimals1 as i32);
+    Ok((
+        average(start.price0_cumulative, end.price0_cumulative) * scale,
+        average(start.price1_cumulative, end.price1_cumulative) / scale,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amm::parse_units;
+
+    #[test]
+    fn test_get_amount_out_matches_contract() {
+        // 1 ETH в пул 100 ETH / 200 000 USDC (6 знаков)
+        let out = get_amount_out(
+            parse_units("1", 18).unwrap(),
+            parse_units("100", 18).unwrap(),
+            parse_units("200000", 6).unwrap(),
+            DEFAULT_FEE_BPS,
+        ).unwrap();
+        assert_eq!(out, U256::from(1_974_316_068u64));
+    }
+
+    #[test]
+    fn test_get_amount_out_errors() {
+        let r = U256::from(1_000u64);
+        assert_eq!(get_amount_out(U256::zero(), r, r, 30), Err(AmmError::InsufficientInputAmount));
+        assert_eq!(get_amount_out(r, U256::zero(), r, 30), Err(AmmError::InsufficientLiquidity));
+        assert_eq!(get_amount_out(U256::MAX, r, r, 30), Err(AmmError::Overflow));
+        // Комиссия ≥ 100% не переполняет вычитание и не делит на ноль
+        assert!(matches!(get_amount_out(r, r, r, 10_001), Err(AmmError::InvalidAmount(_))));
+        assert!(matches!(get_amount_in(U256::one(), r, r, 10_000), Err(AmmError::InvalidAmount(_))));
+    }
+
+    #[test]
+    fn test_get_amount_in_rounds_up() {
+        // Ровно 2000 USDC из пула 100 ETH / 200 000 USDC
+        let (reserve_in, reserve_out) = (parse_units("100", 18).unwrap(), parse_units("200000", 6).unwrap());
+        let amount_out = parse_units("2000", 6).unwrap();
+        let amount_in = get_amount_in(amount_out, reserve_in, reserve_out, DEFAULT_FEE_BPS).unwrap();
+        assert_eq!(amount_in, U256::from(1_013_140_431_395_195_689u64));
+        // Минимальный достаточный вход: на 1 wei меньше — уже не хватает
+        assert_eq!(get_amount_out(amount_in, reserve_in, reserve_out, DEFAULT_FEE_BPS).unwrap(), amount_out);
+        assert!(get_amount_out(amount_in - 1, reserve_in, reserve_out, DEFAULT_FEE_BPS).unwrap() < amount_out);
+
+        let quote = quote_exact_out(amount_out, reserve_in, reserve_out, 18, 6, DEFAULT_FEE_BPS).unwrap();
+        assert_eq!((quote.amount_in, quote.amount_out), (amount_in, amount_out));
+        assert!(quote.price_impact > 0.0);
+
+        assert_eq!(get_amount_in(U256::zero(), reserve_in, reserve_out, 30), Err(AmmError::InsufficientOutputAmount));
+        assert_eq!(get_amount_in(reserve_out, reserve_in, reserve_out, 30), Err(AmmError::InsufficientLiquidity));
+    }
+
+    #[test]
+    fn test_quote_price_impact_grows_with_size() {
+        let reserve_in = parse_units("100", 18).unwrap();
+        let reserve_out = parse_units("200000", 6).unwrap();
+        let small = quote(parse_units("0.01", 18).unwrap(), reserve_in, reserve_out, 18, 6, DEFAULT_FEE_BPS).unwrap();
+        let large = quote(parse_units("10", 18).unwrap(), reserve_in, reserve_out, 18, 6, DEFAULT_FEE_BPS).unwrap();
+        assert_eq!(small.spot_price, 2000.0);
+        // Маленький объём: в основном комиссия 0.3%
+        assert!((small.price_impact - 0.0031).abs() < 1e-4);
+        assert!(large.price_impact > 0.09);
+        assert!(large.effective_price < small.effective_price);
+    }
+
+    #[test]
+    fn test_twap_weights_prices_by_time() {
+        let reserves = |eth: &str, usdc: &str| V2Reserves {
+            reserve0: parse_units(eth, 18).unwrap(),
+            reserve1: parse_units(usdc, 6).unwrap(),
+        };
+        // Накопитель у самой границы 2^256: разности должны пережить переполнение
+        let near_max = U256::MAX - U256::from(1_000u64);
+        let start = current_cumulative(near_max, U256::zero(), 1_000, reserves("100", "200000"), 1_000);
+        // 300 секунд по 2000, затем 100 секунд по 4000 после манипуляции резервами
+        let mid = current_cumulative(start.price0_cumulative, start.price1_cumulative, 1_000, reserves("100", "200000"), 1_300);
+        let end = current_cumulative(mid.price0_cumulative, mid.price1_cumulative, 1_300, reserves("100", "400000"), 1_400);
+        let (price0, price1) = twap(&start, &end, 18, 6).unwrap();
+        assert!((price0 - 2500.0).abs() < 1e-6, "got {price0}");
+        assert!((price1 - (300.0 / 2000.0 + 100.0 / 4000.0) / 400.0).abs() < 1e-12);
+        assert!(twap(&end, &end, 18, 6).is_err());
+    }
+}
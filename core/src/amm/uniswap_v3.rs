@@ -0,0 +1,560 @@
+//! Uniswap V3: концентрированная ликвидность. Порт TickMath, SqrtPriceMath и SwapMath
+//! из v3-core и симуляция свопа по инициализированным тикам снимка пула.
+use primitive_types::U512;
+use std::collections::BTreeMap;
+
+use super::{to_f64, AmmError, AmmQuote, U256};
+
+pub const MIN_TICK: i32 = -887_272;
+pub const MAX_TICK: i32 = 887_272;
+/// Комиссия в "пипсах" (миллионных долях): 3000 = 0.3%
+pub const FEE_DENOMINATOR: u32 = 1_000_000;
+
+pub fn min_sqrt_ratio() -> U256 {
+    U256::from(4_295_128_739u64)
+}
+
+pub fn max_sqrt_ratio() -> U256 {
+    U256::from_dec_str("1461446703485210103287273052203988822378723970342").unwrap()
+}
+
+fn q96() -> U256 {
+    U256::one() << 96
+}
+
+// --- FullMath --- //
+fn mul_div(a: U256, b: U256, denominator: U256) -> Result<U256, AmmError> {
+    if denominator.is_zero() {
+        return Err(AmmError::Overflow);
+    }
+    U256::try_from(a.full_mul(b) / U512::from(denominator)).map_err(|_| AmmError::Overflow)
+}
+
+fn mul_div_rounding_up(a: U256, b: U256, denominator: U256) -> Result<U256, AmmError> {
+    if denominator.is_zero() {
+        return Err(AmmError::Overflow);
+    }
+    let product = a.full_mul(b);
+    let denominator = U512::from(denominator);
+    let mut result = product / denominator;
+    if !(product % denominator).is_zero() {
+        result += U512::one();
+    }
+    U256::try_from(result).map_err(|_| AmmError::Overflow)
+}
+
+fn div_rounding_up(a: U256, b: U256) -> U256 {
+    let (q, r) = a.div_mod(b);
+    if r.is_zero() { q } else { q + 1 }
+}
+
+// --- TickMath --- //
+const TICK_RATIOS: [&str; 19] = [
+    "fff97272373d413259a46990580e213a",
+    "fff2e50f5f656932ef12357cf3c7fdcc",
+    "ffe5caca7e10e4e61c3624eaa0941cd0",
+    "ffcb9843d60f6159c9db58835c926644",
+    "ff973b41fa98c081472e6896dfb254c0",
+    "ff2ea16466c96a3843ec78b326b52861",
+    "fe5dee046a99a2a811c461f1969c3053",
+    "fcbe86c7900a88aedcffc83b479aa3a4",
+    "f987a7253ac413176f2b074cf7815e54",
+    "f3392b0822b70005940c7a398e4b70f3",
+    "e7159475a2c29b7443b29c7fa6e889d9",
+    "d097f3bdfd2022b8845ad8f792aa5825",
+    "a9f746462d870fdf8a65dc1f90e061e5",
+    "70d869a156d2a1b890bb3df62baf32f7",
+    "31be135f97d08fd981231505542fcfa6",
+    "9aa508b5b7a84e1c677de54f3e99bc9",
+    "5d6af8dedb81196699c329225ee604",
+    "2216e584f5fa1ea926041bedfe98",
+    "48a170391f7dc42444e8fa2",
+];
+
+/// sqrt(1.0001^tick) * 2^96
+pub fn get_sqrt_ratio_at_tick(tick: i32) -> Result<U256, AmmError> {
+    if !(MIN_TICK..=MAX_TICK).contains(&tick) {
+        return Err(AmmError::InvalidAmount(format!("tick {tick} out of range")));
+    }
+    let abs_tick = tick.unsigned_abs();
+    let mut ratio = if abs_tick & 1 != 0 {
+        U256::from_str_radix("fffcb933bd6fad37aa2d162d1a594001", 16).unwrap()
+    } else {
+        U256::one() << 128
+    };
+    for (bit, hex) in TICK_RATIOS.iter().enumerate() {
+        if abs_tick & (2 << bit) != 0 {
+            ratio = (ratio * U256::from_str_radix(hex, 16).unwrap()) >> 128;
+        }
+    }
+    if tick > 0 {
+        ratio = U256::MAX / ratio;
+    }
+    // Q128.128 → Q64.96 с округлением вверх
+    let rounding = if (ratio & U256::from(u32::MAX)).is_zero() { 0 } else { 1 };
+    Ok((ratio >> 32) + rounding)
+}
+
+/// Наибольший тик, для которого `get_sqrt_ratio_at_tick(tick) <= sqrt_price_x96`.
+pub fn get_tick_at_sqrt_ratio(sqrt_price_x96: U256) -> Result<i32, AmmError> {
+    if sqrt_price_x96 < min_sqrt_ratio() || sqrt_price_x96 >= max_sqrt_ratio() {
+        return Err(AmmError::InvalidAmount("sqrt price out of range".to_string()));
+    }
+    let (mut lo, mut hi) = (MIN_TICK, MAX_TICK);
+    while lo < hi {
+        let mid = lo + (hi - lo + 1) / 2;
+        if get_sqrt_ratio_at_tick(mid)? <= sqrt_price_x96 {
+            lo = mid;
+        } else {
+            hi = mid - 1;
+        }
+    }
+    Ok(lo)
+}
+
+// --- SqrtPriceMath --- //
+fn next_sqrt_price_from_amount0_rounding_up(sqrt_p: U256, liquidity: u128, amount: U256, add: bool) -> Result<U256, AmmError> {
+    if amount.is_zero() {
+        return Ok(sqrt_p);
+    }
+    let numerator1 = U256::from(liquidity) << 96;
+    let product = amount.checked_mul(sqrt_p);
+    if add {
+        if let Some(denominator) = product.and_then(|p| numerator1.checked_add(p)) {
+            return mul_div_rounding_up(numerator1, sqrt_p, denominator);
+        }
+        let denominator = (numerator1 / sqrt_p).checked_add(amount).ok_or(AmmError::Overflow)?;
+        Ok(div_rounding_up(numerator1, denominator))
+    } else {
+        let product = product.ok_or(AmmError::Overflow)?;
+        if numerator1 <= product {
+            return Err(AmmError::InsufficientLiquidity);
+        }
+        mul_div_rounding_up(numerator1, sqrt_p, numerator1 - product)
+    }
+}
+
+fn next_sqrt_price_from_amount1_rounding_down(sqrt_p: U256, liquidity: u128, amount: U256, add: bool) -> Result<U256, AmmError> {
+    let liquidity = U256::from(liquidity);
+    if add {
+        let quotient = mul_div(amount, q96(), liquidity)?;
+        sqrt_p.checked_add(quotient).ok_or(AmmError::Overflow)
+    } else {
+        let quotient = mul_div_rounding_up(amount, q96(), liquidity)?;
+        if sqrt_p <= quotient {
+            return Err(AmmError::InsufficientLiquidity);
+        }
+        Ok(sqrt_p - quotient)
+    }
+}
+
+fn next_sqrt_price_from_input(sqrt_p: U256, liquidity: u128, amount_in: U256, zero_for_one: bool) -> Result<U256, AmmError> {
+    if zero_for_one {
+        next_sqrt_price_from_amount0_rounding_up(sqrt_p, liquidity, amount_in, true)
+    } else {
+        next_sqrt_price_from_amount1_rounding_down(sqrt_p, liquidity, amount_in, true)
+    }
+}
+
+fn next_sqrt_price_from_output(sqrt_p: U256, liquidity: u128, amount_out: U256, zero_for_one: bool) -> Result<U256, AmmError> {
+    if zero_for_one {
+        next_sqrt_price_from_amount1_rounding_down(sqrt_p, liquidity, amount_out, false)
+    } else {
+        next_sqrt_price_from_amount0_rounding_up(sqrt_p, liquidity, amount_out, false)
+    }
+}
+
+/// Количество token0 между двумя ценами при ликвидности `liquidity`.
+pub fn get_amount0_delta(a: U256, b: U256, liquidity: u128, round_up: bool) -> Result<U256, AmmError> {
+    let (a, b) = if a > b { (b, a) } else { (a, b) };
+    if a.is_zero() {
+        return Err(AmmError::InvalidAmount("zero sqrt price".to_string()));
+    }
+    let numerator1 = U256::from(liquidity) << 96;
+    let numerator2 = b - a;
+    if round_up {
+        Ok(div_rounding_up(mul_div_rounding_up(numerator1, numerator2, b)?, a))
+    } else {
+        Ok(mul_div(numerator1, numerator2, b)? / a)
+    }
+}
+
+/// Количество token1 между двумя ценами при ликвидности `liquidity`.
+pub fn get_amount1_delta(a: U256, b: U256, liquidity: u128, round_up: bool) -> Result<U256, AmmError> {
+    let (a, b) = if a > b { (b, a) } else { (a, b) };
+    if round_up {
+        mul_div_rounding_up(U256::from(liquidity), b - a, q96())
+    } else {
+        mul_div(U256::from(liquidity), b - a, q96())
+    }
+}
+
+// --- SwapMath --- //
+
+/// Что задано в свопе: точный вход или точный выход.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwapAmount {
+    ExactIn(U256),
+    ExactOut(U256),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SwapStep {
+    pub sqrt_price_next: U256,
+    pub amount_in: U256,
+    pub amount_out: U256,
+    pub fee_amount: U256,
+}
+
+/// `SwapMath.computeSwapStep`: шаг свопа внутри одного диапазона ликвидности.
+pub fn compute_swap_step(
+    sqrt_current: U256,
+    sqrt_target: U256,
+    liquidity: u128,
+    remaining: SwapAmount,
+    fee_pips: u32,
+) -> Result<SwapStep, AmmError> {
+    let zero_for_one = sqrt_current >= sqrt_target;
+    let fee_complement = match FEE_DENOMINATOR.checked_sub(fee_pips) {
+        Some(complement) if complement > 0 => U256::from(complement),
+        _ => return Err(invalid_fee(fee_pips)),
+    };
+    let mut amount_in = U256::zero();
+    let mut amount_out = U256::zero();
+
+    let sqrt_next = match remaining {
+        SwapAmount::ExactIn(remaining) => {
+            let remaining_less_fee = mul_div(remaining, fee_complement, U256::from(FEE_DENOMINATOR))?;
+            amount_in = if zero_for_one {
+                get_amount0_delta(sqrt_target, sqrt_current, liquidity, true)?
+            } else {
+                get_amount1_delta(sqrt_current, sqrt_target, liquidity, true)?
+            };
+            if remaining_less_fee >= amount_in {
+                sqrt_target
+            } else {
+                next_sqrt_price_from_input(sqrt_current, liquidity, remaining_less_fee, zero_for_one)?
+            }
+        }
+        SwapAmount::ExactOut(remaining) => {
+            amount_out = if zero_for_one {
+                get_amount1_delta(sqrt_target, sqrt_current, liquidity, false)?
+            } else {
+                get_amount0_delta(sqrt_current, sqrt_target, liquidity, false)?
+            };
+            if remaining >= amount_out {
+                sqrt_target
+            } else {
+                next_sqrt_price_from_output(sqrt_current, liquidity, remaining, zero_for_one)?
+            }
+        }
+    };
+
+    let reached_target = sqrt_target == sqrt_next;
+    let exact_in = matches!(remaining, SwapAmount::ExactIn(_));
+    let exact_out = !exact_in;
+    if zero_for_one {
+        if !(reached_target && exact_in) {
+            amount_in = get_amount0_delta(sqrt_next, sqrt_current, liquidity, true)?;
+        }
+        if !(reached_target && exact_out) {
+            amount_out = get_amount1_delta(sqrt_next, sqrt_current, liquidity, false)?;
+        }
+    } else {
+        if !(reached_target && exact_in) {
+            amount_in = get_amount1_delta(sqrt_current, sqrt_next, liquidity, true)?;
+        }
+        if !(reached_target && exact_out) {
+            amount_out = get_amount0_delta(sqrt_current, sqrt_next, liquidity, false)?;
+        }
+    }
+
+    let fee_amount = match remaining {
+        SwapAmount::ExactOut(remaining) => {
+            amount_out = amount_out.min(remaining);
+            mul_div_rounding_up(amount_in, U256::from(fee_pips), fee_complement)?
+        }
+        // Не дошли до цели — весь остаток, кроме входа, уходит в комиссию
+        SwapAmount::ExactIn(remaining) if sqrt_next != sqrt_target => remaining - amount_in,
+        SwapAmount::ExactIn(_) => mul_div_rounding_up(amount_in, U256::from(fee_pips), fee_complement)?,
+    };
+
+    Ok(SwapStep { sqrt_price_next: sqrt_next, amount_in, amount_out, fee_amount })
+}
+
+// --- Pool --- //
+
+fn invalid_fee(fee_pips: u32) -> AmmError {
+    AmmError::InvalidAmount(format!("fee {fee_pips} pips must be below {FEE_DENOMINATOR}"))
+}
+
+/// Снимок V3-пула: slot0, активная ликвидность и `liquidityNet` инициализированных тиков.
+#[derive(Debug, Clone, PartialEq)]
+pub struct V3PoolState {
+    pub sqrt_price_x96: U256,
+    pub tick: i32,
+    pub liquidity: u128,
+    pub fee_pips: u32,
+    pub ticks: BTreeMap<i32, i128>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct V3SwapResult {
+    /// Вход, включая комиссию
+    pub amount_in: U256,
+    pub amount_out: U256,
+    pub fee_amount: U256,
+    pub sqrt_price_x96_after: U256,
+    pub tick_after: i32,
+    pub liquidity_after: u128,
+    pub ticks_crossed: u32,
+}
+
+impl V3PoolState {
+    /// Снимок с проверкой комиссии: `fee_pips` должна быть меньше `FEE_DENOMINATOR`.
+    pub fn new(sqrt_price_x96: U256, tick: i32, liquidity: u128, fee_pips: u32, ticks: BTreeMap<i32, i128>) -> Result<Self, AmmError> {
+        if fee_pips >= FEE_DENOMINATOR {
+            return Err(invalid_fee(fee_pips));
+        }
+        Ok(Self { sqrt_price_x96, tick, liquidity, fee_pips, ticks })
+    }
+
+    /// Спот-цена token1 за token0 в человеческих единицах.
+    pub fn price_1_per_0(&self, decimals0: u8, decimals1: u8) -> f64 {
+        let sqrt = to_f64(self.sqrt_price_x96, 0) / 2f64.powi(96);
+        sqrt * sqrt * 10f64.powi(decimals0 as i32 - decimals1 as i32)
+    }
+
+    fn next_initialized_tick(&self, tick: i32, zero_for_one: bool) -> (i32, bool) {
+        let next = if zero_for_one {
+            self.ticks.range(..=tick).next_back()
+        } else {
+            self.ticks.range(tick + 1..).next()
+        };
+        match next {
+            Some((&t, _)) => (t.clamp(MIN_TICK, MAX_TICK), true),
+            None if zero_for_one => (MIN_TICK, false),
+            None => (MAX_TICK, false),
+        }
+    }
+
+    /// Симуляция `UniswapV3Pool.swap` без лимита цены. Не исполнившийся полностью своп —
+    /// ошибка `InsufficientLiquidity`, частичное исполнение котировать нельзя.
+    pub fn swap(&self, zero_for_one: bool, amount: SwapAmount) -> Result<V3SwapResult, AmmError> {
+        let (mut remaining, exact_in) = match amount {
+            SwapAmount::ExactIn(a) => (a, true),
+            SwapAmount::ExactOut(a) => (a, false),
+        };
+        if remaining.is_zero() {
+            return Err(AmmError::InsufficientInputAmount);
+        }
+        let limit = if zero_for_one { min_sqrt_ratio() + 1 } else { max_sqrt_ratio() - 1 };
+        let mut sqrt_price = self.sqrt_price_x96;
+        let mut tick = self.tick;
+        let mut liquidity = self.liquidity;
+        let mut total_in = U256::zero();
+        let mut total_out = U256::zero();
+        let mut total_fee = U256::zero();
+        let mut ticks_crossed = 0;
+
+        while !remaining.is_zero() && sqrt_price != limit {
+            let (tick_next, initialized) = self.next_initialized_tick(tick, zero_for_one);
+            let sqrt_next_tick = get_sqrt_ratio_at_tick(tick_next)?;
+            let target = if zero_for_one { sqrt_next_tick.max(limit) } else { sqrt_next_tick.min(limit) };
+            let step_amount = if exact_in { SwapAmount::ExactIn(remaining) } else { SwapAmount::ExactOut(remaining) };
+            let step = compute_swap_step(sqrt_price, target, liquidity, step_amount, self.fee_pips)?;
+
+            let step_in = step.amount_in + step.fee_amount;
+            if exact_in {
+                remaining -= step_in;
+            } else {
+                remaining -= step.amount_out;
+            }
+            total_in += step_in;
+            total_out += step.amount_out;
+            total_fee += step.fee_amount;
+
+            let start = sqrt_price;
+            sqrt_price = step.sqrt_price_next;
+            if sqrt_price == sqrt_next_tick {
+                if initialized {
+                    let net = *self.ticks.get(&tick_next)
+                        .ok_or_else(|| AmmError::InvalidAmount(format!("tick {tick_next} is outside the valid range")))?;
+                    let net = if zero_for_one { -net } else { net };
+                    liquidity = liquidity
+                        .checked_add_signed(net)
+                        .ok_or(AmmError::InsufficientLiquidity)?;
+                    ticks_crossed += 1;
+                }
+                tick = if zero_for_one { tick_next - 1 } else { tick_next };
+            } else if sqrt_price != start {
+                tick = get_tick_at_sqrt_ratio(sqrt_price)?;
+            }
+        }
+
+        if !remaining.is_zero() {
+            return Err(AmmError::InsufficientLiquidity);
+        }
+        Ok(V3SwapResult {
+            amount_in: total_in,
+            amount_out: total_out,
+            fee_amount: total_fee,
+            sqrt_price_x96_after: sqrt_price,
+            tick_after: tick,
+            liquidity_after: liquidity,
+            ticks_crossed,
+        })
+    }
+
+    /// Котировка exact-in в человеческих ценах: эффективная цена и impact относительно спота.
+    pub fn quote(&self, zero_for_one: bool, amount_in: U256, decimals0: u8, decimals1: u8) -> Result<AmmQuote, AmmError> {
+        let result = self.swap(zero_for_one, SwapAmount::ExactIn(amount_in))?;
+        Ok(self.quote_swap(zero_for_one, &result, decimals0, decimals1))
+    }
+
+    /// Котировка exact-out: вход за ровно `amount_out`, шаги свопа округляют его вверх, как пул.
+    pub fn quote_exact_out(&self, zero_for_one: bool, amount_out: U256, decimals0: u8, decimals1: u8) -> Result<AmmQuote, AmmError> {
+        let result = self.swap(zero_for_one, SwapAmount::ExactOut(amount_out))?;
+        Ok(self.quote_swap(zero_for_one, &result, decimals0, decimals1))
+    }
+
+    /// Котировка по уже просимулированному на этом снимке свопу.
+    pub fn quote_swap(&self, zero_for_one: bool, result: &V3SwapResult, decimals0: u8, decimals1: u8) -> AmmQuote {
+        let price = self.price_1_per_0(decimals0, decimals1);
+        let (spot, decimals_in, decimals_out) = if zero_for_one {
+            (price, decimals0, decimals1)
+        } else {
+            (1.0 / price, decimals1, decimals0)
+        };
+        AmmQuote::new(result.amount_in, result.amount_out, spot, decimals_in, decimals_out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn e18(n: u64) -> U256 {
+        U256::from(n) * U256::exp10(18)
+    }
+
+    /// encodePriceSqrt из тестов v3-core: sqrt(reserve1 / reserve0) * 2^96
+    fn encode_price_sqrt(reserve1: u64, reserve0: u64) -> U256 {
+        ((U256::from(reserve1) << 192) / U256::from(reserve0)).integer_sqrt()
+    }
+
+    /// Ликвидность 1e20 в [-600, 600] и ещё 2e20 в [-60, 60], цена 1.0 (тик 0).
+    fn fixture_pool() -> V3PoolState {
+        V3PoolState {
+            sqrt_price_x96: get_sqrt_ratio_at_tick(0).unwrap(),
+            tick: 0,
+            liquidity: 300_000_000_000_000_000_000,
+            fee_pips: 3000,
+            ticks: BTreeMap::from([
+                (-600, 100_000_000_000_000_000_000),
+                (-60, 200_000_000_000_000_000_000),
+                (60, -200_000_000_000_000_000_000),
+                (600, -100_000_000_000_000_000_000),
+            ]),
+        }
+    }
+
+    #[test]
+    fn test_tick_math_bounds() {
+        assert_eq!(get_sqrt_ratio_at_tick(0).unwrap(), U256::one() << 96);
+        assert_eq!(get_sqrt_ratio_at_tick(MIN_TICK).unwrap(), min_sqrt_ratio());
+        assert_eq!(get_sqrt_ratio_at_tick(MAX_TICK).unwrap(), max_sqrt_ratio());
+        assert_eq!(get_sqrt_ratio_at_tick(1).unwrap(), U256::from_dec_str("79232123823359799118286999568").unwrap());
+        assert!(get_sqrt_ratio_at_tick(MAX_TICK + 1).is_err());
+        for tick in [-887_000, -500, -1, 0, 1, 60, 12_345, 887_000] {
+            assert_eq!(get_tick_at_sqrt_ratio(get_sqrt_ratio_at_tick(tick).unwrap()).unwrap(), tick);
+        }
+    }
+
+    #[test]
+    fn test_swap_step_vectors() {
+        // Векторы из SwapMath.spec.ts (v3-core)
+        let price = encode_price_sqrt(1, 1);
+        let target = encode_price_sqrt(101, 100);
+        let step = compute_swap_step(price, target, 2_000_000_000_000_000_000, SwapAmount::ExactIn(e18(1)), 600).unwrap();
+        assert_eq!(step.amount_in, U256::from(9_975_124_224_178_055u64));
+        assert_eq!(step.fee_amount, U256::from(5_988_667_735_148u64));
+        assert_eq!(step.amount_out, U256::from(9_925_619_580_021_728u64));
+        assert_eq!(step.sqrt_price_next, target);
+
+        let step = compute_swap_step(price, target, 2_000_000_000_000_000_000, SwapAmount::ExactOut(e18(1)), 600).unwrap();
+        assert_eq!(step.amount_in, U256::from(9_975_124_224_178_055u64));
+        assert_eq!(step.amount_out, U256::from(9_925_619_580_021_728u64));
+
+        let far_target = encode_price_sqrt(1000, 100);
+        let step = compute_swap_step(price, far_target, 2_000_000_000_000_000_000, SwapAmount::ExactIn(e18(1)), 600).unwrap();
+        assert_eq!(step.amount_in, U256::from(999_400_000_000_000_000u64));
+        assert_eq!(step.fee_amount, U256::from(600_000_000_000_000u64));
+        assert_eq!(step.amount_out, U256::from(666_399_946_655_997_866u64));
+        assert!(step.sqrt_price_next < far_target);
+    }
+
+    #[test]
+    fn test_swap_within_range() {
+        let pool = fixture_pool();
+        let result = pool.swap(true, SwapAmount::ExactIn(U256::exp10(15))).unwrap();
+        assert_eq!(result.ticks_crossed, 0);
+        assert_eq!(result.liquidity_after, pool.liquidity);
+        assert!(result.amount_out < U256::exp10(15));
+        assert!(result.sqrt_price_x96_after < pool.sqrt_price_x96);
+    }
+
+    #[test]
+    fn test_swap_crosses_initialized_ticks() {
+        let pool = fixture_pool();
+        let result = pool.swap(true, SwapAmount::ExactIn(e18(1))).unwrap();
+        assert_eq!(result.ticks_crossed, 1);
+        assert_eq!(result.liquidity_after, 100_000_000_000_000_000_000);
+        assert!(result.tick_after < -60 && result.tick_after > -600);
+
+        let up = pool.swap(false, SwapAmount::ExactIn(e18(1))).unwrap();
+        assert_eq!(up.ticks_crossed, 1);
+        assert!(up.tick_after >= 60);
+
+        // За пределами всей ликвидности своп не исполняется
+        assert_eq!(pool.swap(true, SwapAmount::ExactIn(e18(1_000))), Err(AmmError::InsufficientLiquidity));
+    }
+
+    #[test]
+    fn test_invalid_pool_errors_instead_of_panicking() {
+        assert!(V3PoolState::new(U256::one() << 96, 0, 1, FEE_DENOMINATOR, BTreeMap::new()).is_err());
+        let mut pool = fixture_pool();
+        pool.fee_pips = FEE_DENOMINATOR + 1;
+        assert!(matches!(pool.swap(true, SwapAmount::ExactIn(e18(1))), Err(AmmError::InvalidAmount(_))));
+
+        // Тик за MAX_TICK зажимается к границе, которой нет среди ключей `ticks`: ошибка, не паника
+        let pool = V3PoolState::new(U256::one() << 96, 0, 1_000, 3000, BTreeMap::from([(MAX_TICK + 100, -1_000)])).unwrap();
+        assert_eq!(pool.swap(false, SwapAmount::ExactIn(e18(100_000))), Err(AmmError::InsufficientLiquidity));
+    }
+
+    #[test]
+    fn test_exact_out_round_trip() {
+        let pool = fixture_pool();
+        let exact_in = pool.swap(true, SwapAmount::ExactIn(e18(1))).unwrap();
+        let exact_out = pool.swap(true, SwapAmount::ExactOut(exact_in.amount_out)).unwrap();
+        assert_eq!(exact_out.amount_out, exact_in.amount_out);
+        // Округления в пользу пула: на точный выход нужно не меньше, но почти столько же
+        assert!(exact_out.amount_in <= exact_in.amount_in);
+        assert!(exact_in.amount_in - exact_out.amount_in < U256::from(1_000u64));
+
+        let quote = pool.quote_exact_out(true, exact_in.amount_out, 18, 18).unwrap();
+        assert_eq!((quote.amount_in, quote.amount_out), (exact_out.amount_in, exact_in.amount_out));
+        // Вход exact-out даёт при exact-in не меньше запрошенного
+        assert!(pool.swap(true, SwapAmount::ExactIn(quote.amount_in)).unwrap().amount_out >= quote.amount_out);
+    }
+
+    #[test]
+    fn test_quote_spot_and_impact() {
+        let pool = fixture_pool();
+        assert!((pool.price_1_per_0(18, 18) - 1.0).abs() < 1e-12);
+        let q = pool.quote(true, U256::exp10(15), 18, 18).unwrap();
+        assert!(q.price_impact > 0.0029 && q.price_impact < 0.0031);
+        let big = pool.quote(true, e18(1), 18, 18).unwrap();
+        assert!(big.price_impact > q.price_impact);
+    }
+}
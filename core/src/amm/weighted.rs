@@ -0,0 +1,253 @@
+//! Взвешенный пул (Balancer): инвариант ΠB_k^w_k = const, комиссия берётся со входа.
+//! Веса и комиссия — в 18-знаковой фиксированной точке, как в контракте; дробная
+//! степень считается в f64 через `ln_1p`/`exp_m1` с запасом на погрешность, выход
+//! округляется вниз до base units.
+use rust_decimal::prelude::*;
+
+use super::{format_units, parse_units, settle_amount_in, to_f64, AmmError, AmmQuote, U256};
+
+/// 1.0 в 18-знаковой фиксированной точке
+pub const ONE: u64 = 1_000_000_000_000_000_000;
+/// Balancer не пускает вход/выход больше 30% баланса токена за один своп
+pub const MAX_IN_RATIO: Decimal = Decimal::from_parts(3, 0, 0, false, 1);
+pub const MAX_OUT_RATIO: Decimal = Decimal::from_parts(3, 0, 0, false, 1);
+/// Запас на погрешность степени, как `MAX_POW_RELATIVE_ERROR` в `LogExpMath` Balancer (10^4 wei на 1e18)
+pub const MAX_POW_RELATIVE_ERROR: f64 = 1e-14;
+
+/// Снимок пула. `weights` — нормализованные веса (сумма = `ONE`), `swap_fee` — доля от `ONE`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WeightedPool {
+    pub balances: Vec<U256>,
+    pub decimals: Vec<u8>,
+    pub weights: Vec<u64>,
+    pub swap_fee: u64,
+}
+
+impl WeightedPool {
+    pub fn new(balances: Vec<U256>, decimals: Vec<u8>, weights: Vec<u64>, swap_fee: u64) -> Result<Self, AmmError> {
+        if balances.len() < 2 || balances.len() != decimals.len() || balances.len() != weights.len() {
+            return Err(AmmError::InvalidAmount("pool needs at least 2 tokens with decimals and weights".to_string()));
+        }
+        let total: u128 = weights.iter().map(|w| *w as u128).sum();
+        if weights.contains(&0) || total != ONE as u128 {
+            return Err(AmmError::InvalidAmount("weights must be non-zero and sum to 1".to_string()));
+        }
+        if decimals.iter().any(|d| *d > 18) || swap_fee >= ONE {
+            return Err(AmmError::InvalidAmount("invalid pool parameters".to_string()));
+        }
+        Ok(Self { balances, decimals, weights, swap_fee })
+    }
+
+    fn check_indices(&self, i: usize, j: usize) -> Result<(), AmmError> {
+        if i == j || i >= self.balances.len() || j >= self.balances.len() {
+            return Err(AmmError::InvalidAmount(format!("invalid token indices {i}, {j}")));
+        }
+        Ok(())
+    }
+
+    /// Спот-цена `j` за единицу `i` (без комиссии): (B_j / w_j) / (B_i / w_i).
+    pub fn spot_price(&self, i: usize, j: usize) -> Result<f64, AmmError> {
+        self.check_indices(i, j)?;
+        if self.balances[i].is_zero() || self.balances[j].is_zero() {
+            return Err(AmmError::InsufficientLiquidity);
+        }
+        let b_in = to_f64(self.balances[i], self.decimals[i]) / self.weights[i] as f64;
+        let b_out = to_f64(self.balances[j], self.decimals[j]) / self.weights[j] as f64;
+        Ok(b_out / b_in)
+    }
+
+    /// `calcOutGivenIn`: A_o = B_o · (1 − (B_i / (B_i + A_i·(1 − fee)))^(w_i / w_o)).
+    pub fn out_given_in(&self, i: usize, j: usize, amount_in: U256) -> Result<U256, AmmError> {
+        self.check_indices(i, j)?;
+        if amount_in.is_zero() {
+            return Err(AmmError::InsufficientInputAmount);
+        }
+        if self.balances[i].is_zero() || self.balances[j].is_zero() {
+            return Err(AmmError::InsufficientLiquidity);
+        }
+        let balance_in = to_decimal(self.balances[i], self.decimals[i])?;
+        let balance_out = to_decimal(self.balances[j], self.decimals[j])?;
+        let amount = to_decimal(amount_in, self.decimals[i])?;
+        if amount > balance_in * MAX_IN_RATIO {
+            return Err(AmmError::InvalidAmount("input exceeds 30% of pool balance".to_string()));
+        }
+        let fee = Decimal::from(self.swap_fee) / Decimal::from(ONE);
+        let ratio = (amount * (Decimal::ONE - fee) / balance_in).to_f64().ok_or(AmmError::Overflow)?;
+        let exponent = self.weights[i] as f64 / self.weights[j] as f64;
+        // 1 − (1 + r)^(−w_i/w_o) без потери точности на малых объёмах
+        let share = -(-exponent * ratio.ln_1p()).exp_m1();
+        // Степень округляется вверх на запас погрешности f64 — выход не превысит точный
+        let share = share - (1.0 - share) * MAX_POW_RELATIVE_ERROR;
+        let share = Decimal::from_f64(share).ok_or(AmmError::Overflow)?;
+        let amount_out = balance_out * share.clamp(Decimal::ZERO, Decimal::ONE);
+        if amount_out > balance_out * MAX_OUT_RATIO {
+            return Err(AmmError::InsufficientLiquidity);
+        }
+        from_decimal(amount_out, self.decimals[j])
+    }
+
+    /// `calcInGivenOut`: A_i = B_i · ((B_o / (B_o − A_o))^(w_o / w_i) − 1) / (1 − fee).
+    /// Вход округляется вверх и не меньше того, что по `out_given_in` даёт `amount_out`.
+    pub fn in_given_out(&self, i: usize, j: usize, amount_out: U256) -> Result<U256, AmmError> {
+        self.check_indices(i, j)?;
+        if amount_out.is_zero() {
+            return Err(AmmError::InsufficientOutputAmount);
+        }
+        if self.balances[i].is_zero() || self.balances[j].is_zero() {
+            return Err(AmmError::InsufficientLiquidity);
+        }
+        let balance_in = to_decimal(self.balances[i], self.decimals[i])?;
+        let balance_out = to_decimal(self.balances[j], self.decimals[j])?;
+        let amount = to_decimal(amount_out, self.decimals[j])?;
+        if amount > balance_out * MAX_OUT_RATIO {
+            return Err(AmmError::InsufficientLiquidity);
+        }
+        let fee = Decimal::from(self.swap_fee) / Decimal::from(ONE);
+        let ratio = (amount / balance_out).to_f64().ok_or(AmmError::Overflow)?;
+        let exponent = self.weights[j] as f64 / self.weights[i] as f64;
+        // (1 − r)^(−w_o/w_i) − 1 без потери точности на малых объёмах
+        let growth = (-exponent * (-ratio).ln_1p()).exp_m1();
+        let growth = Decimal::from_f64(growth).ok_or(AmmError::Overflow)?;
+        let amount_in = balance_in
+            .checked_mul(growth)
+            .and_then(|a| a.checked_div(Decimal::ONE - fee))
+            .ok_or(AmmError::Overflow)?;
+        if amount_in > balance_in * MAX_IN_RATIO {
+            return Err(AmmError::InvalidAmount("input exceeds 30% of pool balance".to_string()));
+        }
+        let estimate = from_decimal_up(amount_in, self.decimals[i])?;
+        settle_amount_in(estimate, amount_out, |amount_in| self.out_given_in(i, j, amount_in))
+    }
+
+    pub fn quote(&self, i: usize, j: usize, amount_in: U256) -> Result<AmmQuote, AmmError> {
+        let amount_out = self.out_given_in(i, j, amount_in)?;
+        let spot = self.spot_price(i, j)?;
+        Ok(AmmQuote::new(amount_in, amount_out, spot, self.decimals[i], self.decimals[j]))
+    }
+
+    pub fn quote_exact_out(&self, i: usize, j: usize, amount_out: U256) -> Result<AmmQuote, AmmError> {
+        let amount_in = self.in_given_out(i, j, amount_out)?;
+        let spot = self.spot_price(i, j)?;
+        Ok(AmmQuote::new(amount_in, amount_out, spot, self.decimals[i], self.decimals[j]))
+    }
+}
+
+fn to_decimal(amount: U256, decimals: u8) -> Result<Decimal, AmmError> {
+    Decimal::from_str_exact(&format_units(amount, decimals)).map_err(|_| AmmError::Overflow)
+}
+
+/// Округление вниз до base units — в пользу пула.
+fn from_decimal(amount: Decimal, decimals: u8) -> Result<U256, AmmError> {
+    let rounded = amount.round_dp_with_strategy(decimals as u32, RoundingStrategy::ToZero);
+    parse_units(&rounded.to_string(), decimals)
+}
+
+/// Округление вверх до base units — для входа, тоже в пользу пула.
+fn from_decimal_up(amount: Decimal, decimals: u8) -> Result<U256, AmmError> {
+    let rounded = amount.round_dp_with_strategy(decimals as u32, RoundingStrategy::AwayFromZero);
+    parse_units(&rounded.to_string(), decimals)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 80/20 BAL/WETH: 800 000 BAL, 1 000 WETH, комиссия 1%
+    fn bal_weth() -> WeightedPool {
+        WeightedPool::new(
+            vec![parse_units("800000", 18).unwrap(), parse_units("1000", 18).unwrap()],
+            vec![18, 18],
+            vec![ONE / 10 * 8, ONE / 10 * 2],
+            ONE / 100,
+        ).unwrap()
+    }
+
+    #[test]
+    fn test_spot_price_accounts_for_weights() {
+        let pool = bal_weth();
+        // (1000 / 0.2) / (800000 / 0.8) = 0.005 WETH за BAL
+        assert!((pool.spot_price(0, 1).unwrap() - 0.005).abs() < 1e-15);
+        assert!((pool.spot_price(1, 0).unwrap() - 200.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_out_given_in_matches_closed_form() {
+        let pool = bal_weth();
+        let out = pool.out_given_in(1, 0, parse_units("10", 18).unwrap()).unwrap();
+        // 800000 · (1 − (1000 / 1009.9)^0.25), степень — с запасом на погрешность
+        let expected = 800000.0 * (1.0 - (1000.0f64 / 1009.9).powf(0.25) * (1.0 + MAX_POW_RELATIVE_ERROR));
+        assert!((to_f64(out, 18) - expected).abs() < 1e-9, "got {}", to_f64(out, 18));
+    }
+
+    #[test]
+    fn test_equal_weights_match_constant_product() {
+        let pool = WeightedPool::new(
+            vec![parse_units("100", 18).unwrap(), parse_units("200000", 6).unwrap()],
+            vec![18, 6],
+            vec![ONE / 2, ONE / 2],
+            ONE / 1000 * 3,
+        ).unwrap();
+        let out = pool.out_given_in(0, 1, parse_units("1", 18).unwrap()).unwrap();
+        // Тот же вектор, что у V2: 1 ETH → 1974.316068 USDC
+        assert_eq!(out, U256::from(1_974_316_068u64));
+        // И exact-out: ровно 2000 USDC — почти столько же, сколько по getAmountIn V2
+        // (с точностью до шага подбора входа и запаса на погрешность степени)
+        let amount_out = parse_units("2000", 6).unwrap();
+        let amount_in = pool.in_given_out(0, 1, amount_out).unwrap();
+        let v2 = crate::amm::uniswap_v2::get_amount_in(amount_out, pool.balances[0], pool.balances[1], 30).unwrap();
+        let diff = if amount_in > v2 { amount_in - v2 } else { v2 - amount_in };
+        assert!(diff < U256::exp10(7), "got {amount_in}, v2 {v2}");
+    }
+
+    #[test]
+    fn test_equal_weights_never_exceed_constant_product_on_large_balances() {
+        let balance = parse_units("1000000000", 18).unwrap();
+        let pool = WeightedPool::new(vec![balance, balance], vec![18, 18], vec![ONE / 2, ONE / 2], ONE / 1000 * 3).unwrap();
+        for amount in ["1", "12345.678", "1000000", "250000000"] {
+            let amount_in = parse_units(amount, 18).unwrap();
+            let out = pool.out_given_in(0, 1, amount_in).unwrap();
+            let v2 = crate::amm::uniswap_v2::get_amount_out(amount_in, balance, balance, 30).unwrap();
+            // Погрешность f64 не даёт выхода больше точного, а запас не больше 1e-14 баланса
+            assert!(out <= v2, "{amount}: got {out}, v2 {v2}");
+            assert!(v2 - out <= U256::exp10(13), "{amount}: got {out}, v2 {v2}");
+        }
+    }
+
+    #[test]
+    fn test_in_given_out_rounds_in_pool_favour() {
+        let pool = bal_weth();
+        let amount_out = parse_units("1", 18).unwrap();
+        let amount_in = pool.in_given_out(0, 1, amount_out).unwrap();
+        // 800000 · ((1000 / 999)^0.25 − 1) / 0.99
+        let expected = 800000.0 * ((1000.0f64 / 999.0).powf(0.25) - 1.0) / 0.99;
+        // Запас на погрешность степени в exact-in немного увеличивает нужный вход
+        assert!((to_f64(amount_in, 18) - expected).abs() < 1e-8, "got {}", to_f64(amount_in, 18));
+        assert!(pool.out_given_in(0, 1, amount_in).unwrap() >= amount_out);
+
+        let quote = pool.quote_exact_out(0, 1, amount_out).unwrap();
+        assert_eq!((quote.amount_in, quote.amount_out), (amount_in, amount_out));
+        assert_eq!(pool.in_given_out(0, 1, U256::zero()), Err(AmmError::InsufficientOutputAmount));
+        assert_eq!(pool.in_given_out(1, 0, parse_units("250000", 18).unwrap()), Err(AmmError::InsufficientLiquidity));
+    }
+
+    #[test]
+    fn test_in_given_out_overflow_is_an_error() {
+        // Вес входа 2%: рост (1 − r)^(−49) за 29% выхода ~1e7, B_i · рост не помещается в Decimal
+        let balance = parse_units("10000000000000000000000", 18).unwrap();
+        let pool = WeightedPool::new(vec![balance, balance], vec![18, 18], vec![ONE / 50, ONE / 50 * 49], 0).unwrap();
+        let amount_out = parse_units("2900000000000000000000", 18).unwrap();
+        assert_eq!(pool.in_given_out(0, 1, amount_out), Err(AmmError::Overflow));
+    }
+
+    #[test]
+    fn test_quote_and_limits() {
+        let pool = bal_weth();
+        let q = pool.quote(0, 1, parse_units("1000", 18).unwrap()).unwrap();
+        // 1% комиссии плюс ~0.3% сдвига цены
+        assert!(q.price_impact > 0.013 && q.price_impact < 0.0135);
+        assert!(pool.out_given_in(1, 0, parse_units("301", 18).unwrap()).is_err());
+        assert_eq!(pool.out_given_in(0, 1, U256::zero()), Err(AmmError::InsufficientInputAmount));
+        assert!(pool.out_given_in(0, 0, U256::one()).is_err());
+        assert!(WeightedPool::new(vec![U256::one(), U256::one()], vec![18, 18], vec![ONE / 2, ONE / 3], 0).is_err());
+    }
+}
@@ -1,5 +1,8 @@
+pub mod amm;
 pub mod price_source;
 pub mod pricing;
 pub mod orderbook;
+pub mod pool_state;
 pub mod swap_engine;
+pub mod tokens;
 pub mod types;
\ No newline at end of file
@@ -1,7 +1,9 @@
 use std::collections::VecDeque;
+use std::sync::Arc;
 use serde::{Serialize, Deserialize};
 use rust_decimal::Decimal;
 use uuid::Uuid;
+use crate::tokens::TokenRegistry;
 use crate::types::AddOrderRequest;
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -23,11 +25,22 @@ pub struct Order {
     pub side: OrderSide,
 }
 
+impl Order {
+    /// Рынок дошёл до цены ордера: покупка — при цене не выше, продажа — не ниже.
+    pub fn is_triggered(&self, market_price: Decimal) -> bool {
+        match self.side {
+            OrderSide::Buy => market_price <= self.price,
+            OrderSide::Sell => market_price >= self.price,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum OrderbookError {
     InvalidAmount,
     InvalidPrice,
     InvalidSide,
+    UnknownToken(String),
     ParseError(String),
 }
 
@@ -38,6 +51,7 @@ impl std::fmt::Display for OrderbookError {
             InvalidAmount => write!(f, "Invalid amount"),
             InvalidPrice => write!(f, "Invalid price"),
             InvalidSide => write!(f, "Invalid side"),
+            UnknownToken(symbol) => write!(f, "Unknown token: {symbol}"),
             ParseError(e) => write!(f, "Parse error: {e}"),
         }
     }
@@ -46,6 +60,9 @@ impl std::error::Error for OrderbookError {}
 
 pub struct OrderBook {
     pub orders: VecDeque<Order>,
+    /// Сработавшие ордера, в порядке срабатывания
+    pub triggered: Vec<Order>,
+    tokens: Arc<TokenRegistry>,
 }
 
 impl Default for OrderBook {
@@ -55,11 +72,26 @@ impl Default for OrderBook {
 }
 
 impl OrderBook {
+    /// Ордербук с реестром токенов по умолчанию.
     pub fn new() -> Self {
-        Self { orders: VecDeque::new() }
+        Self::with_registry(Arc::new(TokenRegistry::default()))
+    }
+
+    /// Принимает только токены из `tokens`; символы в ордерах хранятся в каноническом виде.
+    pub fn with_registry(tokens: Arc<TokenRegistry>) -> Self {
+        Self { orders: VecDeque::new(), triggered: Vec::new(), tokens }
+    }
+
+    fn canonical_token(&self, symbol: &str) -> Result<String, OrderbookError> {
+        self.tokens
+            .canonical(symbol)
+            .map(str::to_string)
+            .map_err(|_| OrderbookError::UnknownToken(symbol.to_string()))
     }
 
     pub fn add_order(&mut self, req: AddOrderRequest) -> Result<Uuid, OrderbookError> {
+        let base = self.canonical_token(&req.base)?;
+        let quote = self.canonical_token(&req.quote)?;
         let amount = req.amount.parse::<Decimal>().map_err(|e| OrderbookError::ParseError(e.to_string()))?;
         let price = req.price.parse::<Decimal>().map_err(|e| OrderbookError::ParseError(e.to_string()))?;
         let side = match req.side.to_uppercase().as_str() {
@@ -71,8 +103,8 @@ impl OrderBook {
         if price <= Decimal::ZERO { return Err(OrderbookError::InvalidPrice); }
         let order = Order {
             id: Uuid::new_v4(),
-            base: req.base,
-            quote: req.quote,
+            base,
+            quote,
             amount,
             price,
             side,
@@ -94,6 +126,22 @@ impl OrderBook {
             false
         }
     }
+
+    /// Переносит ордера `base/quote`, сработавшие при рыночной цене `market_price`,
+    /// в `triggered` и возвращает их id.
+    pub fn trigger(&mut self, base: &str, quote: &str, market_price: Decimal) -> Vec<Uuid> {
+        let (triggered, waiting): (VecDeque<Order>, VecDeque<Order>) = std::mem::take(&mut self.orders)
+            .into_iter()
+            .partition(|order| order.base == base && order.quote == quote && order.is_triggered(market_price));
+        self.orders = waiting;
+        let ids = triggered.iter().map(|order| order.id).collect();
+        self.triggered.extend(triggered);
+        ids
+    }
+
+    pub fn get_triggered(&self) -> &[Order] {
+        &self.triggered
+    }
 }
 
 #[cfg(test)]
@@ -146,6 +194,24 @@ mod tests {
         assert!(matches!(res, Err(OrderbookError::InvalidSide)));
     }
 
+    #[test]
+    fn test_add_order_unknown_token() {
+        let mut ob = OrderBook::new();
+        let mut req = valid_request("BUY");
+        req.base = "ETHH".to_string();
+        let res = ob.add_order(req);
+        assert!(matches!(res, Err(OrderbookError::UnknownToken(ref s)) if s == "ETHH"));
+    }
+
+    #[test]
+    fn test_add_order_canonicalizes_aliases() {
+        let mut ob = OrderBook::new();
+        let mut req = valid_request("SELL");
+        req.base = "btc".to_string();
+        ob.add_order(req).unwrap();
+        assert_eq!(ob.get_orders()[0].base, "WBTC");
+    }
+
     #[test]
     fn test_delete_order() {
         let mut ob = OrderBook::new();
@@ -157,6 +223,22 @@ mod tests {
         assert_eq!(ob.get_orders().len(), 0);
     }
 
+    #[test]
+    fn test_trigger_moves_reached_orders() {
+        let mut ob = OrderBook::new();
+        let buy = ob.add_order(valid_request("BUY")).unwrap();
+        let sell = ob.add_order(valid_request("SELL")).unwrap();
+        let mut other_pair = valid_request("BUY");
+        other_pair.base = "WBTC".to_string();
+        ob.add_order(other_pair).unwrap();
+
+        assert_eq!(ob.trigger("ETH", "USDT", Decimal::from(3100)), vec![sell]);
+        assert_eq!(ob.trigger("ETH", "USDT", Decimal::from(2900)), vec![buy]);
+        assert!(ob.trigger("ETH", "USDT", Decimal::from(2900)).is_empty());
+        assert_eq!(ob.get_orders().len(), 1);
+        assert_eq!(ob.get_triggered().iter().map(|o| o.id).collect::<Vec<_>>(), vec![sell, buy]);
+    }
+
     #[test]
     fn test_delete_nonexistent_order() {
         let mut ob = OrderBook::new();
@@ -0,0 +1,322 @@
+//! Откуда AMM-источники цен берут снимки пулов: живой RPC (feature `uniswap`) или
+//! JSON-фикстуры с записанными резервами и тиками — для CI и офлайн-окружений.
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use crate::amm::uniswap_v2::{self, V2Cumulative, V2Reserves};
+use crate::amm::uniswap_v3::{V3PoolState, FEE_DENOMINATOR, MAX_TICK, MIN_TICK};
+use crate::amm::U256;
+
+#[cfg(feature = "uniswap")]
+mod rpc;
+#[cfg(feature = "uniswap")]
+pub use rpc::RpcPoolStateProvider;
+
+/// Пулы адресуются строкой (адрес контракта в hex); регистр не важен.
+#[async_trait]
+pub trait PoolStateProvider: Send + Sync {
+    /// Последний известный блок — по нему источники кэшируют резервы.
+    async fn block_number(&self) -> Result<u64, String>;
+
+    /// Резервы V2-пары на блоке `block` (по умолчанию — последнем).
+    async fn v2_reserves(&self, pool: &str, block: Option<u64>) -> Result<V2Reserves, String>;
+
+    /// Резервы многих V2-пар на одном блоке `block` (по умолчанию — последнем), в порядке `pools`.
+    /// По умолчанию — по пару за запрос; RPC-провайдер читает все за один `eth_call`.
+    async fn v2_reserves_batch(&self, pools: &[&str], block: Option<u64>) -> Result<Vec<V2Reserves>, String> {
+        if pools.is_empty() {
+            return Ok(Vec::new());
+        }
+        let block = match block {
+            Some(block) => block,
+            None => self.block_number().await?,
+        };
+        let mut reserves = Vec::with_capacity(pools.len());
+        for pool in pools {
+            reserves.push(self.v2_reserves(pool, Some(block)).await?);
+        }
+        Ok(reserves)
+    }
+
+    /// Накопленные цены V2-пары на момент блока `block` (по умолчанию — последнего), для TWAP.
+    async fn v2_cumulative(&self, pool: &str, block: Option<u64>) -> Result<V2Cumulative, String>;
+
+    /// Снимок V3-пула с тиками в `bitmap_words` словах tickBitmap в каждую сторону от текущего тика
+    /// и диапазон тиков, в котором известны все инициализированные тики.
+    async fn v3_state(&self, pool: &str, bitmap_words: i16) -> Result<(V3PoolState, (i32, i32)), String>;
+
+    /// Только `sqrtPriceX96` и текущий тик — для спот-цены.
+    async fn v3_slot0(&self, pool: &str) -> Result<(U256, i32), String> {
+        let (state, _) = self.v3_state(pool, 0).await?;
+        Ok((state.sqrt_price_x96, state.tick))
+    }
+}
+
+/// Состояния пулов на одном блоке.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PoolSnapshot {
+    pub block: u64,
+    /// Время блока (unix-секунды); нужно для накопленных цен V2
+    #[serde(default)]
+    pub timestamp: Option<u64>,
+    pub pools: HashMap<String, PoolFixture>,
+}
+
+/// Записанное состояние пула. Большие числа — десятичные строки (или hex с `0x`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PoolFixture {
+    UniswapV2 {
+        reserve0: String,
+        reserve1: String,
+        #[serde(default)]
+        price0_cumulative_last: Option<String>,
+        #[serde(default)]
+        price1_cumulative_last: Option<String>,
+        #[serde(default)]
+        block_timestamp_last: Option<u32>,
+    },
+    UniswapV3 {
+        sqrt_price_x96: String,
+        tick: i32,
+        liquidity: String,
+        fee: u32,
+        /// liquidityNet инициализированных тиков (ключ — номер тика)
+        #[serde(default)]
+        ticks: BTreeMap<String, String>,
+        /// Диапазон, для которого `ticks` полны; без него — весь диапазон тиков
+        #[serde(default)]
+        tick_range: Option<(i32, i32)>,
+    },
+}
+
+/// Провайдер на записанных снимках: файл — JSON-массив `PoolSnapshot`.
+/// Запрос на блоке `b` отдаёт последний снимок с `block <= b`.
+#[derive(Debug, Clone)]
+pub struct FilePoolStateProvider {
+    snapshots: Vec<PoolSnapshot>,
+}
+
+impl FilePoolStateProvider {
+    pub fn new(mut snapshots: Vec<PoolSnapshot>) -> Result<Self, String> {
+        if snapshots.is_empty() {
+            return Err("Pool fixture has no snapshots".to_string());
+        }
+        snapshots.sort_by_key(|s| s.block);
+        for snapshot in &snapshots {
+            for (pool, fixture) in &snapshot.pools {
+                if let PoolFixture::UniswapV3 { fee, .. } = fixture {
+                    if *fee >= FEE_DENOMINATOR {
+                        return Err(format!("Pool {pool} at block {}: fee {fee} must be below {FEE_DENOMINATOR}", snapshot.block));
+                    }
+                }
+            }
+        }
+        for snapshot in &mut snapshots {
+            snapshot.pools = snapshot.pools.drain().map(|(k, v)| (k.to_lowercase(), v)).collect();
+        }
+        Ok(Self { snapshots })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let raw = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read pool fixture {}: {e}", path.display()))?;
+        let snapshots = serde_json::from_str(&raw)
+            .map_err(|e| format!("Invalid pool fixture {}: {e}", path.display()))?;
+        Self::new(snapshots)
+    }
+
+    fn snapshot_pool(&self, pool: &str, block: Option<u64>) -> Result<(&PoolSnapshot, &PoolFixture), String> {
+        let snapshot = match block {
+            Some(block) => self.snapshots.iter().rev().find(|s| s.block <= block)
+                .ok_or_else(|| format!("No pool snapshot at or before block {block}"))?,
+            None => self.snapshots.last().expect("snapshots are not empty"),
+        };
+        let fixture = snapshot.pools.get(&pool.to_lowercase())
+            .ok_or_else(|| format!("Pool {pool} not found in snapshot at block {}", snapshot.block))?;
+        Ok((snapshot, fixture))
+    }
+
+    fn pool(&self, pool: &str, block: Option<u64>) -> Result<&PoolFixture, String> {
+        self.snapshot_pool(pool, block).map(|(_, fixture)| fixture)
+    }
+}
+
+#[async_trait]
+impl PoolStateProvider for FilePoolStateProvider {
+    async fn block_number(&self) -> Result<u64, String> {
+        Ok(self.snapshots.last().expect("snapshots are not empty").block)
+    }
+
+    async fn v2_reserves(&self, pool: &str, block: Option<u64>) -> Result<V2Reserves, String> {
+        match self.pool(pool, block)? {
+            PoolFixture::UniswapV2 { reserve0, reserve1, .. } => Ok(V2Reserves {
+                reserve0: parse_u256(reserve0)?,
+                reserve1: parse_u256(reserve1)?,
+            }),
+            _ => Err(format!("Pool {pool} is not a Uniswap V2 pair")),
+        }
+    }
+
+    /// Все пары — из одного снимка.
+    async fn v2_reserves_batch(&self, pools: &[&str], block: Option<u64>) -> Result<Vec<V2Reserves>, String> {
+        let Some(first) = pools.first() else {
+            return Ok(Vec::new());
+        };
+        let (snapshot, _) = self.snapshot_pool(first, block)?;
+        let mut reserves = Vec::with_capacity(pools.len());
+        for pool in pools {
+            reserves.push(self.v2_reserves(pool, Some(snapshot.block)).await?);
+        }
+        Ok(reserves)
+    }
+
+    async fn v2_cumulative(&self, pool: &str, block: Option<u64>) -> Result<V2Cumulative, String> {
+        let (snapshot, fixture) = self.snapshot_pool(pool, block)?;
+        match fixture {
+            PoolFixture::UniswapV2 {
+                price0_cumulative_last: Some(price0),
+                price1_cumulative_last: Some(price1),
+                block_timestamp_last: Some(timestamp_last),
+                ..
+            } => {
+                let reserves = self.v2_reserves(pool, Some(snapshot.block)).await?;
+                let now = snapshot.timestamp.map(|t| t as u32).unwrap_or(*timestamp_last);
+                Ok(uniswap_v2::current_cumulative(parse_u256(price0)?, parse_u256(price1)?, *timestamp_last, reserves, now))
+            }
+            PoolFixture::UniswapV2 { .. } => Err(format!("Pool {pool} has no cumulative prices at block {}", snapshot.block)),
+            _ => Err(format!("Pool {pool} is not a Uniswap V2 pair")),
+        }
+    }
+
+    /// Фикстура хранит тики целиком, `bitmap_words` не используется.
+    async fn v3_state(&self, pool: &str, _bitmap_words: i16) -> Result<(V3PoolState, (i32, i32)), String> {
+        match self.pool(pool, None)? {
+            PoolFixture::UniswapV3 { sqrt_price_x96, tick, liquidity, fee, ticks, tick_range } => {
+                let ticks = ticks
+                    .iter()
+                    .map(|(t, net)| {
+                        let t = t.parse::<i32>().map_err(|e| format!("Invalid tick {t}: {e}"))?;
+                        let net = net.parse::<i128>().map_err(|e| format!("Invalid liquidityNet {net}: {e}"))?;
+                        Ok((t, net))
+                    })
+                    .collect::<Result<BTreeMap<_, _>, String>>()?;
+                let state = V3PoolState::new(
+                    parse_u256(sqrt_price_x96)?,
+                    *tick,
+                    liquidity.parse().map_err(|e| format!("Invalid liquidity {liquidity}: {e}"))?,
+                    *fee,
+                    ticks,
+                )
+                .map_err(|e| format!("Pool {pool}: {e}"))?;
+                Ok((state, tick_range.unwrap_or((MIN_TICK, MAX_TICK))))
+            }
+            _ => Err(format!("Pool {pool} is not a Uniswap V3 pool")),
+        }
+    }
+}
+
+fn parse_u256(value: &str) -> Result<U256, String> {
+    let parsed = match value.strip_prefix("0x") {
+        Some(hex) => U256::from_str_radix(hex, 16).ok(),
+        None => U256::from_dec_str(value).ok(),
+    };
+    parsed.ok_or_else(|| format!("Invalid integer {value}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = r#"[
+        {"block": 200, "pools": {
+            "0xPAIR": {"type": "uniswap_v2", "reserve0": "200", "reserve1": "0x64"}
+        }},
+        {"block": 100, "pools": {
+            "0xpair": {"type": "uniswap_v2", "reserve0": "100", "reserve1": "100"},
+            "0xv3": {"type": "uniswap_v3", "sqrt_price_x96": "79228162514264337593543950336",
+                     "tick": 0, "liquidity": "1000", "fee": 3000, "ticks": {"-60": "1000", "60": "-1000"}}
+        }}
+    ]"#;
+
+    fn provider() -> FilePoolStateProvider {
+        FilePoolStateProvider::new(serde_json::from_str(FIXTURE).unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_v2_reserves_by_block() {
+        let provider = provider();
+        assert_eq!(provider.block_number().await.unwrap(), 200);
+        let latest = provider.v2_reserves("0xpair", None).await.unwrap();
+        assert_eq!((latest.reserve0, latest.reserve1), (U256::from(200), U256::from(100)));
+        let historical = provider.v2_reserves("0xPair", Some(150)).await.unwrap();
+        assert_eq!(historical.reserve0, U256::from(100));
+        assert!(provider.v2_reserves("0xpair", Some(99)).await.is_err());
+        assert!(provider.v2_reserves("0xunknown", None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_v2_reserves_batch_from_one_snapshot() {
+        let snapshots = serde_json::from_str(r#"[
+            {"block": 100, "pools": {
+                "0xa": {"type": "uniswap_v2", "reserve0": "1", "reserve1": "2"},
+                "0xb": {"type": "uniswap_v2", "reserve0": "3", "reserve1": "4"}
+            }},
+            {"block": 200, "pools": {
+                "0xa": {"type": "uniswap_v2", "reserve0": "5", "reserve1": "6"},
+                "0xb": {"type": "uniswap_v2", "reserve0": "7", "reserve1": "8"}
+            }}
+        ]"#).unwrap();
+        let provider = FilePoolStateProvider::new(snapshots).unwrap();
+        let reserve0 = |batch: Vec<V2Reserves>| batch.iter().map(|r| r.reserve0.as_u64()).collect::<Vec<_>>();
+        assert_eq!(reserve0(provider.v2_reserves_batch(&["0xb", "0xA"], None).await.unwrap()), vec![7, 5]);
+        assert_eq!(reserve0(provider.v2_reserves_batch(&["0xa", "0xb"], Some(150)).await.unwrap()), vec![1, 3]);
+        assert!(provider.v2_reserves_batch(&[], None).await.unwrap().is_empty());
+        assert!(provider.v2_reserves_batch(&["0xa", "0xunknown"], None).await.is_err());
+        assert!(provider.v2_reserves_batch(&["0xa"], Some(99)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_v3_state_from_fixture() {
+        let provider = provider();
+        // v3-пул есть только в старом снимке: последний снимок его не содержит
+        assert!(provider.v3_state("0xv3", 2).await.is_err());
+        let old = FilePoolStateProvider::new(vec![provider.snapshots[0].clone()]).unwrap();
+        let (state, range) = old.v3_state("0xv3", 2).await.unwrap();
+        assert_eq!(state.ticks.get(&-60), Some(&1000));
+        assert_eq!(range, (MIN_TICK, MAX_TICK));
+        assert_eq!(old.v3_slot0("0xv3").await.unwrap().1, 0);
+        assert!(old.v2_reserves("0xv3", None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_v2_cumulative_extends_to_snapshot_time() {
+        let snapshots = serde_json::from_str(r#"[
+            {"block": 10, "timestamp": 1100, "pools": {
+                "0xpair": {"type": "uniswap_v2", "reserve0": "1", "reserve1": "2",
+                           "price0_cumulative_last": "0", "price1_cumulative_last": "0", "block_timestamp_last": 1000}
+            }}
+        ]"#).unwrap();
+        let provider = FilePoolStateProvider::new(snapshots).unwrap();
+        let cumulative = provider.v2_cumulative("0xpair", None).await.unwrap();
+        assert_eq!(cumulative.timestamp, 1100);
+        assert_eq!(cumulative.price0_cumulative, (U256::from(2) << 112) * U256::from(100));
+        assert!(provider.v2_cumulative("0xpair", Some(9)).await.is_err());
+        assert!(self::provider().v2_cumulative("0xpair", None).await.is_err());
+    }
+
+    #[test]
+    fn test_empty_fixture_rejected() {
+        assert!(FilePoolStateProvider::new(Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_v3_fee_validated_on_load() {
+        let snapshots = serde_json::from_str(&FIXTURE.replace(r#""fee": 3000"#, r#""fee": 1000000"#)).unwrap();
+        let err = FilePoolStateProvider::new(snapshots).unwrap_err();
+        assert!(err.contains("fee 1000000"), "{err}");
+    }
+}
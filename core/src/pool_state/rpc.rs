@@ -0,0 +1,136 @@
+use async_trait::async_trait;
+use ethers::prelude::*;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use super::PoolStateProvider;
+use crate::amm::uniswap_v2::{self, V2Cumulative, V2Reserves};
+use crate::amm::uniswap_v3::V3PoolState;
+use crate::price_source::uniswap_v2::UniswapV2Pair;
+use crate::price_source::uniswap_v3::UniswapV3Pool;
+
+/// Состояния пулов с живой ноды через `eth_call`.
+pub struct RpcPoolStateProvider {
+    provider: Arc<Provider<Http>>,
+}
+
+impl RpcPoolStateProvider {
+    pub fn new(provider: Arc<Provider<Http>>) -> Self {
+        Self { provider }
+    }
+
+    pub fn provider(&self) -> Arc<Provider<Http>> {
+        self.provider.clone()
+    }
+
+    fn v3_pool(&self, pool: &str) -> Result<UniswapV3Pool<Provider<Http>>, String> {
+        Ok(UniswapV3Pool::new(parse_address(pool)?, self.provider.clone()))
+    }
+}
+
+fn parse_address(pool: &str) -> Result<Address, String> {
+    pool.parse().map_err(|e| format!("Invalid pool address {pool}: {e}"))
+}
+
+#[async_trait]
+impl PoolStateProvider for RpcPoolStateProvider {
+    async fn block_number(&self) -> Result<u64, String> {
+        self.provider.get_block_number().await
+            .map(|b| b.as_u64())
+            .map_err(|e| format!("Get block number failed: {e}"))
+    }
+
+    async fn v2_reserves(&self, pool: &str, block: Option<u64>) -> Result<V2Reserves, String> {
+        let pair = UniswapV2Pair::new(parse_address(pool)?, self.provider.clone());
+        let mut call = pair.get_reserves();
+        if let Some(block) = block {
+            call = call.block(block);
+        }
+        let (reserve0, reserve1, _) = call.call().await
+            .map_err(|e| format!("Get reserves failed: {e}"))?;
+        Ok(V2Reserves { reserve0: U256::from(reserve0), reserve1: U256::from(reserve1) })
+    }
+
+    /// Все пары за один `eth_call` через Multicall3.
+    async fn v2_reserves_batch(&self, pools: &[&str], block: Option<u64>) -> Result<Vec<V2Reserves>, String> {
+        if pools.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut multicall = Multicall::new(self.provider.clone(), Some(MULTICALL_ADDRESS)).await
+            .map_err(|e| format!("Multicall error: {e}"))?;
+        if let Some(block) = block {
+            multicall = multicall.block(block);
+        }
+        for pool in pools {
+            let pair = UniswapV2Pair::new(parse_address(pool)?, self.provider.clone());
+            multicall.add_call(pair.get_reserves(), false);
+        }
+        let results: Vec<(u128, u128, u32)> = multicall.call_array().await
+            .map_err(|e| format!("Get reserves failed: {e}"))?;
+        Ok(results
+            .into_iter()
+            .map(|(reserve0, reserve1, _)| V2Reserves { reserve0: U256::from(reserve0), reserve1: U256::from(reserve1) })
+            .collect())
+    }
+
+    async fn v2_cumulative(&self, pool: &str, block: Option<u64>) -> Result<V2Cumulative, String> {
+        let pair = UniswapV2Pair::new(parse_address(pool)?, self.provider.clone());
+        let block = match block {
+            Some(block) => block,
+            None => self.block_number().await?,
+        };
+        let (reserve0, reserve1, timestamp_last) = pair.get_reserves().block(block).call().await
+            .map_err(|e| format!("Get reserves failed: {e}"))?;
+        let price0 = pair.price_0_cumulative_last().block(block).call().await
+            .map_err(|e| format!("Get price0CumulativeLast failed: {e}"))?;
+        let price1 = pair.price_1_cumulative_last().block(block).call().await
+            .map_err(|e| format!("Get price1CumulativeLast failed: {e}"))?;
+        let now = self.provider.get_block(block).await
+            .map_err(|e| format!("Get block failed: {e}"))?
+            .ok_or_else(|| format!("Block {block} not found"))?
+            .timestamp;
+        let reserves = V2Reserves { reserve0: U256::from(reserve0), reserve1: U256::from(reserve1) };
+        Ok(uniswap_v2::current_cumulative(price0, price1, timestamp_last, reserves, now.low_u32()))
+    }
+
+    async fn v3_state(&self, pool: &str, bitmap_words: i16) -> Result<(V3PoolState, (i32, i32)), String> {
+        let pool = self.v3_pool(pool)?;
+        let (sqrt_price_x96, tick, ..) = pool.slot_0().call().await
+            .map_err(|e| format!("Get slot0 failed: {e}"))?;
+        let liquidity = pool.liquidity().call().await
+            .map_err(|e| format!("Get liquidity failed: {e}"))?;
+        let fee = pool.fee().call().await
+            .map_err(|e| format!("Get fee failed: {e}"))?;
+        let spacing = pool.tick_spacing().call().await
+            .map_err(|e| format!("Get tick spacing failed: {e}"))?;
+
+        let compressed = tick.div_euclid(spacing);
+        let word = (compressed >> 8) as i16;
+        let first_word = word.saturating_sub(bitmap_words.max(0));
+        let last_word = word.saturating_add(bitmap_words.max(0));
+        let mut ticks = BTreeMap::new();
+        for word_pos in first_word..=last_word {
+            let bitmap = pool.tick_bitmap(word_pos).call().await
+                .map_err(|e| format!("Get tick bitmap failed: {e}"))?;
+            for bit in (0..256).filter(|b| bitmap.bit(*b)) {
+                let initialized_tick = ((word_pos as i32) * 256 + bit as i32) * spacing;
+                let (_, liquidity_net, ..) = pool.ticks(initialized_tick).call().await
+                    .map_err(|e| format!("Get tick {initialized_tick} failed: {e}"))?;
+                ticks.insert(initialized_tick, liquidity_net);
+            }
+        }
+        let loaded = (
+            (first_word as i32) * 256 * spacing,
+            ((last_word as i32) + 1) * 256 * spacing - 1,
+        );
+        let state = V3PoolState::new(sqrt_price_x96, tick, liquidity, fee, ticks)
+            .map_err(|e| format!("Pool {}: {e}", pool.address()))?;
+        Ok((state, loaded))
+    }
+
+    async fn v3_slot0(&self, pool: &str) -> Result<(U256, i32), String> {
+        let (sqrt_price_x96, tick, ..) = self.v3_pool(pool)?.slot_0().call().await
+            .map_err(|e| format!("Get slot0 failed: {e}"))?;
+        Ok((sqrt_price_x96, tick))
+    }
+}
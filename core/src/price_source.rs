@@ -1,12 +1,188 @@
 use async_trait::async_trait;
 use reqwest;
 use serde_json;
-use std::any::Any;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::amm::{self, AmmError, AmmQuote};
+use crate::tokens::TokenRegistry;
+
+pub mod cex;
+pub mod chainlink;
+pub mod fallback;
+pub mod guard;
+pub mod pool;
+pub mod recorder;
+pub mod registry;
+pub mod replay;
+pub mod resilient;
+pub mod stableswap;
+pub mod stream;
+pub mod triangulation;
+pub mod twap;
+pub mod uniswap_v3;
+pub mod weighted;
+
+pub use cex::{BinanceTickerStream, CexPriceSource, Exchange};
+pub use chainlink::ChainlinkPriceSource;
+pub use fallback::FallbackPriceSource;
+pub use guard::GuardedPriceSource;
+pub use pool::{PoolModel, PoolPriceSource};
+pub use recorder::{PriceRecorder, RecordingPriceSource};
+pub use registry::PriceSourceRegistry;
+pub use replay::{PriceRecord, ReplayPriceSource, SimClock};
+pub use resilient::{CircuitBreaker, ResilientPriceSource, RetryPolicy};
+pub use stableswap::StableSwapPriceSource;
+pub use stream::{PollingPriceStream, PriceBoard, PriceStream, PriceSubscription, PriceUpdate, PriceWatch, ReconnectingPriceStream};
+pub use triangulation::{TriangulatedPrice, TriangulatingPriceSource};
+pub use twap::{SamplingStatus, TwapPriceSource};
+pub use weighted::WeightedPoolPriceSource;
+
+#[cfg(test)]
+mod test_http;
+
+/// Цена с моментом, к которому она относится (unix time, миллисекунды).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TimedPrice {
+    pub price: f64,
+    pub timestamp_ms: u64,
+}
+
+/// Котировка exact-out: цена исполнения (выход за единицу входа) за ровно `amount_out`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExactOutPrice {
+    pub price: f64,
+    pub timestamp_ms: u64,
+    /// Вход по кривой пула в человеческих единицах `from`, округлён вверх, с комиссией и price impact;
+    /// `None` — цена не зависит от объёма, вход — `amount_out / price`
+    pub amount_in: Option<String>,
+}
+
+impl ExactOutPrice {
+    /// Котировка exact-out кривой пула; `decimals_in` — decimals входа.
+    pub fn from_amm(quote: &AmmQuote, decimals_in: u8) -> Self {
+        Self {
+            price: quote.effective_price,
+            timestamp_ms: now_ms(),
+            amount_in: Some(amm::format_units(quote.amount_in, decimals_in)),
+        }
+    }
+}
+
+/// Почему источник не дал цену. `NoPrice` — источник ответил, но цены для такой пары
+/// или объёма у него нет: повтор не поможет. `Unavailable` — сбой сети, таймаут,
+/// битый ответ: повтор может пройти.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PriceError {
+    NoPrice(String),
+    Unavailable(String),
+}
+
+impl PriceError {
+    /// `No price for {from}/{to}`.
+    pub fn no_price(from: &str, to: &str) -> Self {
+        PriceError::NoPrice(format!("No price for {from}/{to}"))
+    }
+
+    pub fn unsupported_pair() -> Self {
+        PriceError::NoPrice("Pair not supported".to_string())
+    }
+
+    /// Стоит ли повторять запрос и считать отказ против circuit breaker.
+    pub fn is_transient(&self) -> bool {
+        matches!(self, PriceError::Unavailable(_))
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            PriceError::NoPrice(message) | PriceError::Unavailable(message) => message,
+        }
+    }
+
+    /// То же сообщение с префиксом `context: `, вид ошибки сохраняется.
+    pub fn context(self, context: &str) -> Self {
+        match self {
+            PriceError::NoPrice(message) => PriceError::NoPrice(format!("{context}: {message}")),
+            PriceError::Unavailable(message) => PriceError::Unavailable(format!("{context}: {message}")),
+        }
+    }
+}
+
+impl fmt::Display for PriceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+impl std::error::Error for PriceError {}
+
+/// Ошибки инфраструктуры (RPC, файлы, HTTP) — временные.
+impl From<String> for PriceError {
+    fn from(message: String) -> Self {
+        PriceError::Unavailable(message)
+    }
+}
+
+/// Кривая пула не котирует такой объём — повтор не поможет.
+impl From<AmmError> for PriceError {
+    fn from(e: AmmError) -> Self {
+        PriceError::NoPrice(e.to_string())
+    }
+}
+
+/// В JSON — сообщением, как раньше строка.
+impl Serialize for PriceError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.message())
+    }
+}
+
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
 
 #[async_trait]
 pub trait PriceSource: Send + Sync + 'static {
-    async fn get_price(&self, from: &str, to: &str, amount: Option<&str>) -> Result<f64, String>;
-    fn as_any(&self) -> &dyn Any;
+    async fn get_price(&self, from: &str, to: &str, amount: Option<&str>) -> Result<f64, PriceError>;
+
+    /// Цена вместе с её временем. Источники, которые знают время обновления
+    /// (CoinGecko, оракулы), переопределяют метод; по умолчанию — момент запроса.
+    async fn get_price_timed(&self, from: &str, to: &str, amount: Option<&str>) -> Result<TimedPrice, PriceError> {
+        let price = self.get_price(from, to, amount).await?;
+        Ok(TimedPrice { price, timestamp_ms: now_ms() })
+    }
+
+    /// Цена вместе с путём через промежуточные токены; у прямой цены путь `[from, to]`.
+    async fn get_price_with_path(&self, from: &str, to: &str, amount: Option<&str>) -> Result<TriangulatedPrice, PriceError> {
+        let timed = self.get_price_timed(from, to, amount).await?;
+        Ok(TriangulatedPrice {
+            price: timed.price,
+            timestamp_ms: timed.timestamp_ms,
+            path: vec![from.to_string(), to.to_string()],
+        })
+    }
+
+    /// Котировка за ровно `amount_out` (в человеческих единицах `to`). По умолчанию — цена
+    /// без объёма; источники на кривой пула (AMM) считают вход по кривой.
+    async fn get_price_exact_out(&self, from: &str, to: &str, _amount_out: &str) -> Result<ExactOutPrice, PriceError> {
+        let timed = self.get_price_timed(from, to, None).await?;
+        Ok(ExactOutPrice { price: timed.price, timestamp_ms: timed.timestamp_ms, amount_in: None })
+    }
+
+    /// Цены для многих пар за один вызов, результат в порядке `pairs`.
+    /// По умолчанию — по одной паре; источники с пакетным API переопределяют.
+    async fn get_prices(&self, pairs: &[(&str, &str)]) -> Vec<Result<f64, PriceError>> {
+        let mut prices = Vec::with_capacity(pairs.len());
+        for (from, to) in pairs {
+            prices.push(self.get_price(from, to, None).await);
+        }
+        prices
+    }
 }
 
 // --- Mock --- //
@@ -14,110 +190,422 @@ pub struct MockPriceSource;
 
 #[async_trait]
 impl PriceSource for MockPriceSource {
-    async fn get_price(&self, from: &str, to: &str, _amount: Option<&str>) -> Result<f64, String> {
+    async fn get_price(&self, from: &str, to: &str, _amount: Option<&str>) -> Result<f64, PriceError> {
         match (from, to) {
             ("ETH", "USDT") => Ok(3200.0),
             ("USDT", "ETH") => Ok(1.0 / 3200.0),
             ("WBTC", "USDT") => Ok(67000.0),
             ("USDT", "WBTC") => Ok(1.0 / 67000.0),
-            _ => Err(format!("No price for {from}/{to}")),
+            _ => Err(PriceError::no_price(from, to)),
+        }
+    }
+}
+
+// --- Static (цены из конфигурации) --- //
+#[derive(Debug, Clone, Default)]
+pub struct StaticPriceSource {
+    prices: HashMap<(String, String), f64>,
+}
+
+impl StaticPriceSource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Добавляет цену пары `from/to` и обратную ей `to/from`.
+    pub fn with_pair(mut self, from: &str, to: &str, price: f64) -> Self {
+        self.prices.insert((from.to_string(), to.to_string()), price);
+        if price > 0.0 {
+            self.prices.insert((to.to_string(), from.to_string()), 1.0 / price);
         }
+        self
+    }
+}
+
+#[async_trait]
+impl PriceSource for StaticPriceSource {
+    async fn get_price(&self, from: &str, to: &str, _amount: Option<&str>) -> Result<f64, PriceError> {
+        self.prices
+            .get(&(from.to_string(), to.to_string()))
+            .copied()
+            .ok_or_else(|| PriceError::no_price(from, to))
     }
-    fn as_any(&self) -> &dyn Any { self }
 }
 
 // --- CoinGecko --- //
-pub struct CoinGeckoPriceSource;
+pub const COINGECKO_API_URL: &str = "https://api.coingecko.com";
+pub const COINGECKO_PRO_API_URL: &str = "https://pro-api.coingecko.com";
+
+pub struct CoinGeckoPriceSource {
+    base_url: String,
+    api_key: Option<String>,
+    token_ids: HashMap<String, String>,
+    client: reqwest::Client,
+}
+
+impl Default for CoinGeckoPriceSource {
+    fn default() -> Self {
+        Self::new(COINGECKO_API_URL)
+    }
+}
+
+impl CoinGeckoPriceSource {
+    /// `base_url` без `/api/v3`, например `https://pro-api.coingecko.com` или адрес локальной заглушки.
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key: None,
+            token_ids: default_token_ids(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Ключ API: для pro-api — заголовок `x-cg-pro-api-key`, для публичного
+    /// `COINGECKO_API_URL` — demo-ключ `x-cg-demo-api-key`.
+    pub fn with_api_key(mut self, api_key: &str) -> Self {
+        self.api_key = Some(api_key.to_string());
+        self
+    }
+
+    /// Дополняет (и переопределяет) таблицу symbol → CoinGecko id.
+    pub fn with_token_ids(mut self, ids: HashMap<String, String>) -> Self {
+        for (symbol, id) in ids {
+            self.token_ids.insert(symbol.to_ascii_uppercase(), id);
+        }
+        self
+    }
+
+    /// CoinGecko id из реестра токенов, включая алиасы.
+    pub fn with_registry(self, registry: &TokenRegistry) -> Self {
+        self.with_token_ids(registry.coingecko_ids())
+    }
+
+    /// Загружает таблицу из JSON-файла вида `{"ETH": "ethereum", "ARB": "arbitrum"}`.
+    pub fn with_token_ids_file(self, path: impl AsRef<Path>) -> Result<Self, String> {
+        Ok(self.with_token_ids(load_token_ids(path)?))
+    }
+
+    /// CoinGecko id для символа. Неизвестный символ — ошибка, а не догадка: `pepe`
+    /// в нижнем регистре вполне может оказаться id другой монеты.
+    pub fn map_token(&self, symbol: &str) -> Result<String, PriceError> {
+        self.token_ids
+            .get(&symbol.to_ascii_uppercase())
+            .cloned()
+            .ok_or_else(|| PriceError::NoPrice(format!("Unknown CoinGecko token {symbol}")))
+    }
+
+    /// Pro-ключ публичному API не отправляется: там принимается только demo-ключ.
+    fn api_key_header(&self) -> Option<(&'static str, &str)> {
+        let key = self.api_key.as_deref()?;
+        let header = if self.base_url == COINGECKO_API_URL { "x-cg-demo-api-key" } else { "x-cg-pro-api-key" };
+        Some((header, key))
+    }
+
+    async fn fetch_simple_price(&self, ids: &str, vs_currencies: &str) -> Result<serde_json::Value, String> {
+        let url = format!("{}/api/v3/simple/price", self.base_url);
+        let mut req = self.client.get(&url).query(&[
+            ("ids", ids),
+            ("vs_currencies", vs_currencies),
+            ("include_last_updated_at", "true"),
+        ]);
+        if let Some((header, key)) = self.api_key_header() {
+            req = req.header(header, key);
+        }
+        let resp = req.send().await.map_err(|e| e.to_string())?;
+        if !resp.status().is_success() {
+            return Err(format!("CoinGecko responded with {}", resp.status()));
+        }
+        resp.json().await.map_err(|e| e.to_string())
+    }
+}
 
 #[async_trait]
 impl PriceSource for CoinGeckoPriceSource {
-    async fn get_price(&self, from: &str, to: &str, _amount: Option<&str>) -> Result<f64, String> {
-        let from_id = map_token(from);
-        let to_id = map_token(to);
-        let url = format!(
-            "https://api.coingecko.com/api/v3/simple/price?ids={from_id}&vs_currencies={to_id}"
-        );
-        let resp = reqwest::get(&url).await.map_err(|e| e.to_string())?;
-        let json: serde_json::Value = resp.json().await.map_err(|e| e.to_string())?;
-        let price = json.get(&from_id)
+    async fn get_price(&self, from: &str, to: &str, amount: Option<&str>) -> Result<f64, PriceError> {
+        Ok(self.get_price_timed(from, to, amount).await?.price)
+    }
+
+    async fn get_price_timed(&self, from: &str, to: &str, _amount: Option<&str>) -> Result<TimedPrice, PriceError> {
+        let from_id = self.map_token(from)?;
+        let to_id = self.map_token(to)?;
+        let json = self.fetch_simple_price(&from_id, &to_id).await?;
+        let entry = json.get(&from_id);
+        let price = entry
             .and_then(|x| x.get(&to_id))
             .and_then(|x| x.as_f64())
-            .ok_or("Price not found".to_string())?;
-        Ok(price)
+            .ok_or_else(|| PriceError::NoPrice("Price not found".to_string()))?;
+        // last_updated_at — unix time в секундах
+        let timestamp_ms = entry
+            .and_then(|x| x.get("last_updated_at"))
+            .and_then(|x| x.as_u64())
+            .map(|secs| secs * 1000)
+            .unwrap_or_else(now_ms);
+        Ok(TimedPrice { price, timestamp_ms })
+    }
+
+    /// Один запрос `simple/price` на все пары: ids и vs_currencies перечисляются через запятую.
+    async fn get_prices(&self, pairs: &[(&str, &str)]) -> Vec<Result<f64, PriceError>> {
+        // Пары с неизвестными токенами отказывают сразу, остальные идут одним запросом
+        let mapped: Vec<Result<(String, String), PriceError>> = pairs
+            .iter()
+            .map(|(from, to)| Ok((self.map_token(from)?, self.map_token(to)?)))
+            .collect();
+        let known: Vec<&(String, String)> = mapped.iter().filter_map(|m| m.as_ref().ok()).collect();
+        if known.is_empty() {
+            return mapped.into_iter().map(|m| m.map(|_| 0.0)).collect();
+        }
+        let mut ids: Vec<&str> = known.iter().map(|(id, _)| id.as_str()).collect();
+        let mut vs: Vec<&str> = known.iter().map(|(_, vs)| vs.as_str()).collect();
+        ids.sort_unstable();
+        ids.dedup();
+        vs.sort_unstable();
+        vs.dedup();
+        let json = self.fetch_simple_price(&ids.join(","), &vs.join(",")).await.map_err(PriceError::from);
+        mapped
+            .into_iter()
+            .map(|m| {
+                let (from_id, to_id) = m?;
+                json.as_ref()
+                    .map_err(Clone::clone)?
+                    .get(&from_id)
+                    .and_then(|x| x.get(&to_id))
+                    .and_then(|x| x.as_f64())
+                    .ok_or_else(|| PriceError::NoPrice("Price not found".to_string()))
+            })
+            .collect()
     }
-    fn as_any(&self) -> &dyn Any { self }
 }
 
 // --- Token mapping for CoinGecko --- //
-fn map_token(symbol: &str) -> String {
-    match symbol.to_ascii_uppercase().as_str() {
-        "ETH" => "ethereum".to_string(),
-        "WBTC" => "wrapped-bitcoin".to_string(),
-        "USDT" => "tether".to_string(),
-        "BNB" => "binancecoin".to_string(),
-        _ => symbol.to_ascii_lowercase(),
-    }
+fn default_token_ids() -> HashMap<String, String> {
+    TokenRegistry::default().coingecko_ids()
+}
+
+pub fn load_token_ids(path: impl AsRef<Path>) -> Result<HashMap<String, String>, String> {
+    let path = path.as_ref();
+    let raw = std::fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read token id registry {}: {e}", path.display()))?;
+    serde_json::from_str(&raw)
+        .map_err(|e| format!("Invalid token id registry {}: {e}", path.display()))
 }
 
-// --- Uniswap поддержка через feature ---
-#[cfg(feature = "uniswap")]
+// --- Uniswap: RPC-привязки через feature, расчёты — по снимкам из PoolStateProvider ---
 pub mod uniswap_v2 {
     use async_trait::async_trait;
+    #[cfg(feature = "uniswap")]
     use ethers::prelude::*;
-    use rust_decimal::Decimal;
-    use rust_decimal::prelude::ToPrimitive;
-    use std::sync::Arc;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+    use crate::amm::{self, AmmQuote, U256};
+    use crate::amm::uniswap_v2::{V2Cumulative, V2Reserves};
+    use crate::pool_state::PoolStateProvider;
+    use crate::tokens::TokenInfo;
+    use super::PriceError;
+
+    pub use crate::amm::uniswap_v2::DEFAULT_FEE_BPS;
 
+    /// Время блока BSC: чаще номер блока не перечитывается
+    pub const DEFAULT_BLOCK_TIME: Duration = Duration::from_secs(3);
 
+    #[cfg(feature = "uniswap")]
     abigen!(
         UniswapV2Pair,
         "abi/UniswapV2Pair.json"
     );
 
+    /// Один HTTP-провайдер на процесс: переиспользуется всеми пулами.
+    #[cfg(feature = "uniswap")]
+    pub fn connect(rpc_url: &str) -> Result<Arc<Provider<Http>>, String> {
+        Provider::<Http>::try_from(rpc_url)
+            .map(Arc::new)
+            .map_err(|e| format!("Provider error: {e}"))
+    }
+
     pub struct UniswapV2PriceSource {
-        pub rpc_url: String,
-        pub pool_address: Address,
-        pub token0_symbol: &'static str,
-        pub token1_symbol: &'static str,
+        state: Arc<dyn PoolStateProvider>,
+        pub pool_address: String,
+        pub token0_symbol: String,
+        pub token1_symbol: String,
         pub decimals0: u8,
         pub decimals1: u8,
+        pub fee_bps: u32,
+        block_time: Duration,
+        // Резервы последнего прочитанного блока и момент, когда номер блока проверялся:
+        // в пределах блока резервы не меняются
+        cache: Mutex<Option<(u64, V2Reserves, Instant)>>,
     }
 
-    #[async_trait]
-    impl super::PriceSource for UniswapV2PriceSource {
-        async fn get_price(&self, from: &str, to: &str, _amount: Option<&str>) -> Result<f64, String> {
-            let provider = Provider::<Http>::try_from(self.rpc_url.clone())
-                .map_err(|e| format!("Provider error: {e}"))?;
-            let client = Arc::new(provider);
+    /// Резервы и decimals, развёрнутые по направлению свопа from → to.
+    struct Oriented {
+        reserve_in: U256,
+        reserve_out: U256,
+        decimals_in: u8,
+        decimals_out: u8,
+    }
+
+    impl UniswapV2PriceSource {
+        pub fn new(
+            state: Arc<dyn PoolStateProvider>,
+            pool_address: impl Into<String>,
+            token0_symbol: &str,
+            token1_symbol: &str,
+            decimals0: u8,
+            decimals1: u8,
+        ) -> Self {
+            Self {
+                state,
+                pool_address: pool_address.into(),
+                token0_symbol: token0_symbol.to_string(),
+                token1_symbol: token1_symbol.to_string(),
+                decimals0,
+                decimals1,
+                fee_bps: DEFAULT_FEE_BPS,
+                block_time: DEFAULT_BLOCK_TIME,
+                cache: Mutex::new(None),
+            }
+        }
+
+        /// Символы и decimals токенов пары — из реестра.
+        pub fn for_tokens(
+            state: Arc<dyn PoolStateProvider>,
+            pool_address: impl Into<String>,
+            token0: &TokenInfo,
+            token1: &TokenInfo,
+        ) -> Self {
+            Self::new(state, pool_address, &token0.symbol, &token1.symbol, token0.decimals, token1.decimals)
+        }
+
+        /// Комиссия пула в bps; допустимы значения меньше 10 000 (100%).
+        pub fn with_fee_bps(mut self, fee_bps: u32) -> Result<Self, String> {
+            if fee_bps >= 10_000 {
+                return Err(format!("Invalid fee {fee_bps} bps for pool {}", self.pool_address));
+            }
+            self.fee_bps = fee_bps;
+            Ok(self)
+        }
 
-            let pair = UniswapV2Pair::new(self.pool_address, client);
-            let (reserve0, reserve1, _) = pair.get_reserves().call().await
-                .map_err(|e| format!("Get reserves failed: {e}"))?;
+        /// Как долго резервы считаются свежими без запроса номера блока.
+        pub fn with_block_time(mut self, block_time: Duration) -> Self {
+            self.block_time = block_time;
+            self
+        }
 
+        /// Резервы на текущем блоке. В течение `block_time` после проверки блока отдаются из кэша
+        /// без RPC; позже перечитывается только номер блока, а резервы — если блок сменился.
+        pub async fn reserves(&self) -> Result<V2Reserves, String> {
+            if let Some((_, reserves, checked_at)) = *self.cache.lock().unwrap() {
+                if checked_at.elapsed() < self.block_time {
+                    return Ok(reserves);
+                }
+            }
+            let block = self.state.block_number().await?;
+            if let Some((cached_block, reserves, checked_at)) = self.cache.lock().unwrap().as_mut() {
+                if *cached_block == block {
+                    *checked_at = Instant::now();
+                    return Ok(*reserves);
+                }
+            }
+            let reserves = self.reserves_at(block).await?;
+            let mut cache = self.cache.lock().unwrap();
+            if cache.is_none_or(|(cached_block, ..)| cached_block < block) {
+                *cache = Some((block, reserves, Instant::now()));
+            }
+            Ok(reserves)
+        }
+
+        /// Резервы на конкретном (в том числе историческом) блоке — для воспроизведения прошлых котировок.
+        pub async fn reserves_at(&self, block: u64) -> Result<V2Reserves, String> {
+            self.state.v2_reserves(&self.pool_address, Some(block)).await
+        }
+
+        /// Накопленные цены пары для TWAP; `block` — исторический блок.
+        pub async fn cumulative_prices_at(&self, block: Option<u64>) -> Result<V2Cumulative, String> {
+            self.state.v2_cumulative(&self.pool_address, block).await
+        }
+
+        fn orient(&self, from: &str, to: &str, reserves: V2Reserves) -> Result<Oriented, PriceError> {
             // Универсальная поддержка любых пар
             if from == self.token0_symbol && to == self.token1_symbol {
-                let r0 = Decimal::from(reserve0);
-                let r1 = Decimal::from(reserve1);
-                let price = (r1 / Decimal::new(10u64.pow(self.decimals1 as u32) as i64, 0))
-                    / (r0 / Decimal::new(10u64.pow(self.decimals0 as u32) as i64, 0));
-                Ok(price.to_f64().unwrap())
+                Ok(Oriented { reserve_in: reserves.reserve0, reserve_out: reserves.reserve1, decimals_in: self.decimals0, decimals_out: self.decimals1 })
             } else if from == self.token1_symbol && to == self.token0_symbol {
-                let r0 = Decimal::from(reserve0);
-                let r1 = Decimal::from(reserve1);
-                let price = (r0 / Decimal::new(10u64.pow(self.decimals0 as u32) as i64, 0))
-                    / (r1 / Decimal::new(10u64.pow(self.decimals1 as u32) as i64, 0));
-                Ok(price.to_f64().unwrap())
+                Ok(Oriented { reserve_in: reserves.reserve1, reserve_out: reserves.reserve0, decimals_in: self.decimals1, decimals_out: self.decimals0 })
             } else {
-                Err("Pair not supported".to_string())
+                Err(PriceError::unsupported_pair())
+            }
+        }
+
+        fn check_pair(&self, from: &str, to: &str) -> Result<(), PriceError> {
+            self.orient(from, to, V2Reserves { reserve0: U256::zero(), reserve1: U256::zero() }).map(|_| ())
+        }
+
+        async fn reserves_for(&self, block: Option<u64>) -> Result<V2Reserves, String> {
+            match block {
+                Some(block) => self.reserves_at(block).await,
+                None => self.reserves().await,
             }
         }
-        fn as_any(&self) -> &dyn std::any::Any { self }
+
+        /// Котировка на объём `amount` (в человеческих единицах `from`) по формуле `getAmountOut`;
+        /// `block` — прочитать резервы на историческом блоке.
+        pub async fn quote_at(&self, from: &str, to: &str, amount: &str, block: Option<u64>) -> Result<AmmQuote, PriceError> {
+            self.check_pair(from, to)?;
+            let pool = self.orient(from, to, self.reserves_for(block).await?)?;
+            let amount_in = amm::parse_units(amount, pool.decimals_in)?;
+            amm::uniswap_v2::quote(amount_in, pool.reserve_in, pool.reserve_out, pool.decimals_in, pool.decimals_out, self.fee_bps)
+                .map_err(PriceError::from)
+        }
+
+        pub async fn quote(&self, from: &str, to: &str, amount: &str) -> Result<AmmQuote, PriceError> {
+            self.quote_at(from, to, amount, None).await
+        }
+
+        /// Котировка exact-out: сколько `from` нужно за ровно `amount_out` (в человеческих единицах `to`),
+        /// по формуле `getAmountIn` с округлением вверх.
+        pub async fn quote_exact_out(&self, from: &str, to: &str, amount_out: &str) -> Result<AmmQuote, PriceError> {
+            self.check_pair(from, to)?;
+            let pool = self.orient(from, to, self.reserves().await?)?;
+            let amount_out = amm::parse_units(amount_out, pool.decimals_out)?;
+            amm::uniswap_v2::quote_exact_out(amount_out, pool.reserve_in, pool.reserve_out, pool.decimals_in, pool.decimals_out, self.fee_bps)
+                .map_err(PriceError::from)
+        }
+
+        pub async fn spot_price_at(&self, from: &str, to: &str, block: Option<u64>) -> Result<f64, PriceError> {
+            self.check_pair(from, to)?;
+            let pool = self.orient(from, to, self.reserves_for(block).await?)?;
+            amm::uniswap_v2::spot_price(pool.reserve_in, pool.reserve_out, pool.decimals_in, pool.decimals_out)
+                .map_err(PriceError::from)
+        }
+    }
+
+    #[async_trait]
+    impl super::PriceSource for UniswapV2PriceSource {
+        /// Без `amount` — спот-цена по резервам; с `amount` — эффективная цена исполнения
+        /// с комиссией пула и price impact.
+        async fn get_price(&self, from: &str, to: &str, amount: Option<&str>) -> Result<f64, PriceError> {
+            match amount {
+                Some(amount) => Ok(self.quote(from, to, amount).await?.effective_price),
+                None => self.spot_price_at(from, to, None).await,
+            }
+        }
+
+        /// Вход по формуле `getAmountIn` с комиссией пула, округлён вверх.
+        async fn get_price_exact_out(&self, from: &str, to: &str, amount_out: &str) -> Result<super::ExactOutPrice, PriceError> {
+            let quote = self.quote_exact_out(from, to, amount_out).await?;
+            let decimals_in = if from == self.token0_symbol { self.decimals0 } else { self.decimals1 };
+            Ok(super::ExactOutPrice::from_amm(&quote, decimals_in))
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::amm::uniswap_v2::{V2Cumulative, V2Reserves};
+    use crate::amm::uniswap_v3::V3PoolState;
+    use crate::pool_state::PoolStateProvider;
+    use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering::SeqCst};
+    use std::sync::Arc;
+    use std::time::Duration;
     use tokio_test::block_on;
 
     #[test]
@@ -136,10 +624,167 @@ mod tests {
         assert!(res.is_err());
     }
 
+    #[test]
+    fn test_static_price_source_inverse_pair() {
+        let source = StaticPriceSource::new().with_pair("ETH", "USDT", 3000.0);
+        assert_eq!(block_on(source.get_price("ETH", "USDT", None)).unwrap(), 3000.0);
+        let inverse = block_on(source.get_price("USDT", "ETH", None)).unwrap();
+        assert!((inverse - (1.0 / 3000.0)).abs() < 1e-12);
+        assert!(block_on(source.get_price("ETH", "WBTC", None)).is_err());
+    }
+
     #[tokio::test]
     async fn test_coingecko_price_source_unknown_pair() {
-        let cg = CoinGeckoPriceSource;
-        let res = cg.get_price("UNKNOWN", "USDT", None).await;
-        assert!(res.is_err());
+        let server = test_http::MockHttpServer::start("{}").await;
+        let cg = CoinGeckoPriceSource::new(&server.url());
+        // Неизвестный символ не угадывается и не уходит в API
+        let err = cg.get_price("UNKNOWN", "USDT", None).await.unwrap_err();
+        assert_eq!(err, PriceError::NoPrice("Unknown CoinGecko token UNKNOWN".to_string()));
+        assert!(server.requests().is_empty());
+        // Известные токены, но цены в ответе нет
+        assert!(cg.get_price("ETH", "USDT", None).await.is_err());
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_coingecko_local_stand_in() {
+        let server = test_http::MockHttpServer::start(
+            r#"{"ethereum":{"tether":3150.5,"last_updated_at":1700000000}}"#,
+        ).await;
+        let cg = CoinGeckoPriceSource::new(&server.url()).with_api_key("secret");
+        let timed = cg.get_price_timed("ETH", "USDT", None).await.unwrap();
+        assert_eq!(timed.price, 3150.5);
+        assert_eq!(timed.timestamp_ms, 1_700_000_000_000);
+
+        let request = server.requests().pop().unwrap();
+        assert!(request.starts_with("GET /api/v3/simple/price?ids=ethereum&vs_currencies=tether"));
+        assert!(request.to_ascii_lowercase().contains("x-cg-pro-api-key: secret"));
+    }
+
+    #[test]
+    fn test_coingecko_api_key_header() {
+        let public = CoinGeckoPriceSource::default().with_api_key("secret");
+        assert_eq!(public.api_key_header(), Some(("x-cg-demo-api-key", "secret")));
+        let pro = CoinGeckoPriceSource::new(COINGECKO_PRO_API_URL).with_api_key("secret");
+        assert_eq!(pro.api_key_header(), Some(("x-cg-pro-api-key", "secret")));
+        assert_eq!(CoinGeckoPriceSource::default().api_key_header(), None);
+    }
+
+    #[tokio::test]
+    async fn test_coingecko_batch_single_request() {
+        let server = test_http::MockHttpServer::start(
+            r#"{"ethereum":{"tether":3150.5},"wrapped-bitcoin":{"tether":65000.0,"ethereum":20.5}}"#,
+        ).await;
+        let cg = CoinGeckoPriceSource::new(&server.url());
+        let prices = cg.get_prices(&[("ETH", "USDT"), ("WBTC", "USDT"), ("WBTC", "ETH"), ("BNB", "USDT"), ("PEPE", "USDT")]).await;
+        assert_eq!(prices[0], Ok(3150.5));
+        assert_eq!(prices[1], Ok(65000.0));
+        assert_eq!(prices[2], Ok(20.5));
+        assert!(prices[3].is_err());
+        assert!(prices[4].is_err());
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].contains("ids=binancecoin%2Cethereum%2Cwrapped-bitcoin"));
+        assert!(requests[0].contains("vs_currencies=ethereum%2Ctether"));
+    }
+
+    #[test]
+    fn test_default_get_prices_per_pair() {
+        let prices = block_on(MockPriceSource.get_prices(&[("ETH", "USDT"), ("DOGE", "USDT")]));
+        assert_eq!(prices[0], Ok(3200.0));
+        assert!(prices[1].is_err());
+    }
+
+    #[test]
+    fn test_coingecko_token_ids_file() {
+        let path = std::env::temp_dir().join(format!("cg_ids_{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(&path, r#"{"arb": "arbitrum", "ETH": "weth"}"#).unwrap();
+        let cg = CoinGeckoPriceSource::default().with_token_ids_file(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(cg.map_token("ARB").unwrap(), "arbitrum");
+        assert_eq!(cg.map_token("eth").unwrap(), "weth");
+        assert_eq!(cg.map_token("USDT").unwrap(), "tether");
+        assert!(cg.map_token("PEPE").is_err());
+        assert!(CoinGeckoPriceSource::default().with_token_ids_file("/nonexistent.json").is_err());
+    }
+
+    #[test]
+    fn test_coingecko_ids_from_registry() {
+        use crate::tokens::TokenInfo;
+        let registry = TokenRegistry::new(vec![
+            TokenInfo::new("ARB", 18, 42161).with_coingecko_id("arbitrum").with_aliases(&["ARBITRUM"]),
+        ]).unwrap();
+        let cg = CoinGeckoPriceSource::default().with_registry(&registry);
+        assert_eq!(cg.map_token("arbitrum").unwrap(), "arbitrum");
+        assert_eq!(cg.map_token("WETH").unwrap(), "weth");
+        assert_eq!(cg.map_token("BTC").unwrap(), "wrapped-bitcoin");
+    }
+
+    /// Провайдер с одной V2-парой, считающий обращения к «ноде».
+    struct CountingPoolState {
+        block: AtomicU64,
+        block_calls: AtomicUsize,
+        reserves_calls: AtomicUsize,
     }
-}
\ No newline at end of file
+
+    #[async_trait]
+    impl PoolStateProvider for CountingPoolState {
+        async fn block_number(&self) -> Result<u64, String> {
+            self.block_calls.fetch_add(1, SeqCst);
+            Ok(self.block.load(SeqCst))
+        }
+        async fn v2_reserves(&self, _pool: &str, _block: Option<u64>) -> Result<V2Reserves, String> {
+            self.reserves_calls.fetch_add(1, SeqCst);
+            let units = |v: &str| crate::amm::parse_units(v, 18).unwrap();
+            Ok(V2Reserves { reserve0: units("1000"), reserve1: units("600000") })
+        }
+        async fn v2_cumulative(&self, _pool: &str, _block: Option<u64>) -> Result<V2Cumulative, String> {
+            Err("not needed".to_string())
+        }
+        async fn v3_state(&self, _pool: &str, _bitmap_words: i16) -> Result<(V3PoolState, (i32, i32)), String> {
+            Err("not a v3 pool".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_v2_reserves_cached_per_block() {
+        use uniswap_v2::UniswapV2PriceSource;
+        let state = Arc::new(CountingPoolState {
+            block: 100.into(),
+            block_calls: 0.into(),
+            reserves_calls: 0.into(),
+        });
+        // В пределах времени блока повторные чтения не ходят в RPC вовсе
+        let source = UniswapV2PriceSource::new(state.clone(), "0xpair", "WBNB", "USDT", 18, 18);
+        assert_eq!(source.get_price("WBNB", "USDT", None).await.unwrap(), 600.0);
+        assert_eq!(source.get_price("WBNB", "USDT", Some("1")).await.unwrap(), source.quote("WBNB", "USDT", "1").await.unwrap().effective_price);
+        assert_eq!((state.block_calls.load(SeqCst), state.reserves_calls.load(SeqCst)), (1, 1));
+
+        // После него перечитывается номер блока, а резервы — только на новом блоке
+        let source = UniswapV2PriceSource::new(state.clone(), "0xpair", "WBNB", "USDT", 18, 18)
+            .with_block_time(Duration::ZERO);
+        source.reserves().await.unwrap();
+        source.reserves().await.unwrap();
+        assert_eq!((state.block_calls.load(SeqCst), state.reserves_calls.load(SeqCst)), (3, 2));
+        state.block.store(101, SeqCst);
+        source.reserves().await.unwrap();
+        assert_eq!((state.block_calls.load(SeqCst), state.reserves_calls.load(SeqCst)), (4, 3));
+    }
+
+    #[tokio::test]
+    async fn test_v2_exact_out_priced_on_curve() {
+        use uniswap_v2::UniswapV2PriceSource;
+        let state = Arc::new(CountingPoolState { block: 100.into(), block_calls: 0.into(), reserves_calls: 0.into() });
+        let source = UniswapV2PriceSource::new(state, "0xpair", "WBNB", "USDT", 18, 18);
+        let quote = source.get_price_exact_out("WBNB", "USDT", "6000").await.unwrap();
+        let expected = source.quote_exact_out("WBNB", "USDT", "6000").await.unwrap();
+        assert_eq!(quote.amount_in, Some(crate::amm::format_units(expected.amount_in, 18)));
+        // Комиссия и price impact: входа нужно больше, чем по споту (10 WBNB)
+        assert!(quote.price < 600.0 * 0.99);
+
+        // Без кривой — цена без объёма
+        let flat = MockPriceSource.get_price_exact_out("ETH", "USDT", "1000").await.unwrap();
+        assert_eq!((flat.price, flat.amount_in), (3200.0, None));
+    }
+}
@@ -0,0 +1,401 @@
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio_tungstenite::tungstenite::Message;
+
+use super::stream::{PriceStream, PriceSubscription, PriceUpdate, DEFAULT_STREAM_BUFFER};
+use super::{now_ms, PriceError, PriceSource, TimedPrice};
+use crate::tokens::TokenRegistry;
+
+pub const BINANCE_API_URL: &str = "https://api.binance.com";
+pub const BINANCE_STREAM_URL: &str = "wss://stream.binance.com:9443";
+pub const KRAKEN_API_URL: &str = "https://api.kraken.com";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Exchange {
+    /// `GET /api/v3/ticker/bookTicker?symbol=ETHUSDT`
+    Binance,
+    /// `GET /0/public/Ticker?pair=ETHUSDT`
+    Kraken,
+}
+
+impl Exchange {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Exchange::Binance => "binance",
+            Exchange::Kraken => "kraken",
+        }
+    }
+
+    /// Публичный REST API биржи.
+    pub fn default_url(&self) -> &'static str {
+        match self {
+            Exchange::Binance => BINANCE_API_URL,
+            Exchange::Kraken => KRAKEN_API_URL,
+        }
+    }
+}
+
+/// Лучшие цены стакана в единицах `to` за единицу `from`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BookTicker {
+    pub bid: f64,
+    pub ask: f64,
+    pub timestamp_ms: u64,
+}
+
+impl BookTicker {
+    pub fn mid(&self) -> f64 {
+        (self.bid + self.ask) / 2.0
+    }
+
+    /// Тот же стакан со стороны другой валюты: bid и ask меняются местами.
+    pub fn inverse(&self) -> Self {
+        Self { bid: 1.0 / self.ask, ask: 1.0 / self.bid, timestamp_ms: self.timestamp_ms }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BinanceBookTicker {
+    bid_price: String,
+    ask_price: String,
+}
+
+#[derive(Deserialize)]
+struct KrakenResponse {
+    #[serde(default)]
+    error: Vec<String>,
+    #[serde(default)]
+    result: HashMap<String, KrakenTicker>,
+}
+
+#[derive(Deserialize)]
+struct KrakenTicker {
+    /// [цена, объём целыми лотами, объём]
+    a: Vec<String>,
+    b: Vec<String>,
+}
+
+/// Публичный REST-тикер биржи. Цена — середина спреда лучших bid/ask;
+/// пара ищется как рынок `base+quote`, где котируемая валюта — первая подходящая
+/// из `quote_assets`, обратный рынок инвертируется. Тикеры, отличные от наших символов
+/// (WBTC → XBT на Kraken), берутся из `exchange_symbols` реестра токенов.
+pub struct CexPriceSource {
+    exchange: Exchange,
+    base_url: String,
+    assets: HashMap<String, String>,
+    quote_assets: Vec<String>,
+    client: reqwest::Client,
+}
+
+impl CexPriceSource {
+    /// `base_url` — корень API биржи или адрес локальной заглушки.
+    pub fn new(exchange: Exchange, base_url: &str) -> Self {
+        Self {
+            exchange,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            assets: TokenRegistry::default().exchange_symbols(exchange.name()),
+            quote_assets: ["USDT", "USDC", "USD", "EUR", "BTC", "ETH"].iter().map(|s| s.to_string()).collect(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    pub fn binance() -> Self {
+        Self::new(Exchange::Binance, Exchange::Binance.default_url())
+    }
+
+    pub fn kraken() -> Self {
+        Self::new(Exchange::Kraken, Exchange::Kraken.default_url())
+    }
+
+    pub fn exchange(&self) -> Exchange {
+        self.exchange
+    }
+
+    /// Тикеры биржи из реестра токенов вместо встроенного.
+    pub fn with_registry(mut self, registry: &TokenRegistry) -> Self {
+        self.assets = registry.exchange_symbols(self.exchange.name());
+        self
+    }
+
+    /// Котируемые валюты биржи в порядке приоритета (в терминах актива биржи).
+    pub fn with_quote_assets(mut self, quote_assets: &[&str]) -> Self {
+        self.quote_assets = quote_assets.iter().map(|s| s.to_ascii_uppercase()).collect();
+        self
+    }
+
+    /// Актив биржи для нашего символа; без тикера в реестре — сам символ.
+    pub fn map_asset(&self, symbol: &str) -> String {
+        let symbol = symbol.to_ascii_uppercase();
+        self.assets.get(&symbol).cloned().unwrap_or(symbol)
+    }
+
+    /// Рынок для пары и признак того, что он котируется наоборот (`to/from`).
+    pub(crate) fn market(&self, from: &str, to: &str) -> (String, bool) {
+        let (from, to) = (self.map_asset(from), self.map_asset(to));
+        let rank = |asset: &str| self.quote_assets.iter().position(|q| q == asset).unwrap_or(usize::MAX);
+        if rank(&from) < rank(&to) {
+            (format!("{to}{from}"), true)
+        } else {
+            (format!("{from}{to}"), false)
+        }
+    }
+
+    /// Лучшие bid/ask для `from/to`.
+    pub async fn book_ticker(&self, from: &str, to: &str) -> Result<BookTicker, PriceError> {
+        let (market, inverted) = self.market(from, to);
+        // Неизвестный рынок — постоянная ошибка, сетевые сбои остаются переходными
+        let unknown_market = |e: String| PriceError::NoPrice(format!("No price for {from}/{to} on {}: {e}", self.exchange.name()));
+        let (bid, ask) = match self.exchange {
+            Exchange::Binance => self.fetch_binance(&market, unknown_market).await?,
+            Exchange::Kraken => self.fetch_kraken(&market, unknown_market).await?,
+        };
+        if !(bid > 0.0 && ask >= bid) {
+            return Err(PriceError::Unavailable(format!("Invalid {} book for {market}: bid {bid}, ask {ask}", self.exchange.name())));
+        }
+        let ticker = BookTicker { bid, ask, timestamp_ms: now_ms() };
+        Ok(if inverted { ticker.inverse() } else { ticker })
+    }
+
+    async fn get(&self, path: &str, query: &[(&str, &str)]) -> Result<reqwest::Response, String> {
+        self.client
+            .get(format!("{}{path}", self.base_url))
+            .query(query)
+            .send()
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    async fn fetch_binance(&self, market: &str, unknown_market: impl Fn(String) -> PriceError) -> Result<(f64, f64), PriceError> {
+        let resp = self.get("/api/v3/ticker/bookTicker", &[("symbol", market)]).await?;
+        if !resp.status().is_success() {
+            // Неизвестный рынок — 400 с {"code": -1121, "msg": "Invalid symbol."}
+            let status = resp.status();
+            let body: serde_json::Value = resp.json().await.unwrap_or_default();
+            return Err(match (body["code"].as_i64(), body["msg"].as_str()) {
+                (Some(-1121), Some(msg)) => unknown_market(msg.to_string()),
+                (_, Some(msg)) => PriceError::Unavailable(format!("Binance responded with {status}: {msg}")),
+                _ => PriceError::Unavailable(format!("Binance responded with {status}")),
+            });
+        }
+        let ticker: BinanceBookTicker = resp.json().await.map_err(|e| e.to_string())?;
+        Ok((parse_price(&ticker.bid_price)?, parse_price(&ticker.ask_price)?))
+    }
+
+    async fn fetch_kraken(&self, market: &str, unknown_market: impl Fn(String) -> PriceError) -> Result<(f64, f64), PriceError> {
+        let resp = self.get("/0/public/Ticker", &[("pair", market)]).await?;
+        if !resp.status().is_success() {
+            return Err(PriceError::Unavailable(format!("Kraken responded with {}", resp.status())));
+        }
+        let body: KrakenResponse = resp.json().await.map_err(|e| e.to_string())?;
+        if !body.error.is_empty() {
+            let error = body.error.join("; ");
+            return Err(if error.contains("Unknown asset pair") { unknown_market(error) } else { PriceError::Unavailable(error) });
+        }
+        // Ключ результата — внутреннее имя рынка (XETHZUSD), а не запрошенное
+        let ticker = body.result.into_values().next().ok_or_else(|| PriceError::Unavailable("Empty Kraken ticker".to_string()))?;
+        let price = |side: &[String]| side.first().ok_or("Empty Kraken book side".to_string()).and_then(|p| parse_price(p));
+        Ok((price(&ticker.b)?, price(&ticker.a)?))
+    }
+}
+
+fn parse_price(value: &str) -> Result<f64, String> {
+    value.parse().map_err(|_| format!("Invalid price {value}"))
+}
+
+#[async_trait]
+impl PriceSource for CexPriceSource {
+    async fn get_price(&self, from: &str, to: &str, amount: Option<&str>) -> Result<f64, PriceError> {
+        Ok(self.get_price_timed(from, to, amount).await?.price)
+    }
+
+    /// Середина спреда; время — момент получения ответа биржи.
+    async fn get_price_timed(&self, from: &str, to: &str, _amount: Option<&str>) -> Result<TimedPrice, PriceError> {
+        let ticker = self.book_ticker(from, to).await?;
+        Ok(TimedPrice { price: ticker.mid(), timestamp_ms: ticker.timestamp_ms })
+    }
+}
+
+/// Событие комбинированного потока: `{"stream": "ethusdt@bookTicker", "data": {...}}`.
+#[derive(Deserialize)]
+struct BinanceStreamEvent {
+    stream: String,
+    data: BinanceStreamTicker,
+}
+
+#[derive(Deserialize)]
+struct BinanceStreamTicker {
+    #[serde(rename = "b")]
+    bid_price: String,
+    #[serde(rename = "a")]
+    ask_price: String,
+}
+
+/// Нативный поток Binance: `<symbol>@bookTicker` для всех пар одним соединением.
+/// Рынки и активы берутся из `CexPriceSource` той же биржи. Событие приходит и при
+/// изменении одних объёмов — в канал уходят только новые середины спреда.
+/// Обрыв соединения закрывает подписку; для переподключения оберните в `ReconnectingPriceStream`.
+pub struct BinanceTickerStream {
+    markets: Arc<CexPriceSource>,
+    url: String,
+    buffer: usize,
+}
+
+impl BinanceTickerStream {
+    /// `url` — корень WebSocket API (`BINANCE_STREAM_URL`) или адрес локальной заглушки.
+    pub fn new(markets: Arc<CexPriceSource>, url: &str) -> Self {
+        Self { markets, url: url.trim_end_matches('/').to_string(), buffer: DEFAULT_STREAM_BUFFER }
+    }
+
+    pub fn with_buffer(mut self, buffer: usize) -> Self {
+        self.buffer = buffer;
+        self
+    }
+}
+
+#[async_trait]
+impl PriceStream for BinanceTickerStream {
+    async fn subscribe(&self, pairs: &[(&str, &str)]) -> Result<PriceSubscription, String> {
+        if self.markets.exchange() != Exchange::Binance {
+            return Err(format!("{} markets cannot be streamed from Binance", self.markets.exchange().name()));
+        }
+        // Имя потока → пары, которые из него считаются (прямые и обратные)
+        let mut streams: HashMap<String, Vec<(String, String, bool)>> = HashMap::new();
+        for (from, to) in pairs {
+            let (market, inverted) = self.markets.market(from, to);
+            streams
+                .entry(format!("{}@bookTicker", market.to_ascii_lowercase()))
+                .or_default()
+                .push((from.to_string(), to.to_string(), inverted));
+        }
+        let names: Vec<&str> = streams.keys().map(String::as_str).collect();
+        let url = format!("{}/stream?streams={}", self.url, names.join("/"));
+        let (mut socket, _) = tokio_tungstenite::connect_async(url)
+            .await
+            .map_err(|e| format!("Binance stream connect failed: {e}"))?;
+        Ok(PriceSubscription::spawn(self.buffer, move |tx| async move {
+            let mut last: HashMap<(String, String), f64> = HashMap::new();
+            // Ping сервера отвечается внутри tungstenite при чтении
+            while let Some(Ok(message)) = socket.next().await {
+                let Message::Text(text) = message else { continue };
+                let Ok(event) = serde_json::from_str::<BinanceStreamEvent>(&text) else { continue };
+                let (Ok(bid), Ok(ask)) = (parse_price(&event.data.bid_price), parse_price(&event.data.ask_price)) else { continue };
+                if !(bid > 0.0 && ask >= bid) {
+                    continue;
+                }
+                let ticker = BookTicker { bid, ask, timestamp_ms: now_ms() };
+                for (from, to, inverted) in streams.get(&event.stream).into_iter().flatten() {
+                    let price = if *inverted { ticker.inverse().mid() } else { ticker.mid() };
+                    if last.insert((from.clone(), to.clone()), price) == Some(price) {
+                        continue;
+                    }
+                    if tx.send(PriceUpdate::new(from, to, price, ticker.timestamp_ms)).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::price_source::test_http::MockHttpServer;
+    use crate::tokens::TokenInfo;
+
+    #[tokio::test]
+    async fn test_binance_book_ticker() {
+        let server = MockHttpServer::start_with(|line| {
+            if line.contains("symbol=ETHUSDT") {
+                (200, r#"{"symbol":"ETHUSDT","bidPrice":"3199.50","bidQty":"1.2","askPrice":"3200.50","askQty":"0.8"}"#.to_string())
+            } else {
+                (400, r#"{"code":-1121,"msg":"Invalid symbol."}"#.to_string())
+            }
+        }).await;
+        let binance = CexPriceSource::new(Exchange::Binance, &server.url());
+        let ticker = binance.book_ticker("ETH", "USDT").await.unwrap();
+        assert_eq!((ticker.bid, ticker.ask, ticker.mid()), (3199.5, 3200.5, 3200.0));
+        assert_eq!(binance.get_price("WETH", "USDT", None).await.unwrap(), 3200.0);
+
+        // USDT/ETH — тот же рынок ETHUSDT, перевёрнутый
+        let inverse = binance.book_ticker("USDT", "ETH").await.unwrap();
+        assert!((inverse.bid - 1.0 / 3200.5).abs() < 1e-15);
+        assert!(server.requests().iter().all(|r| r.starts_with("GET /api/v3/ticker/bookTicker?symbol=ETHUSDT")));
+
+        let err = binance.get_price("DOGE", "EUR", None).await.unwrap_err();
+        assert_eq!(err, PriceError::NoPrice("No price for DOGE/EUR on binance: Invalid symbol.".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_kraken_ticker_with_asset_mapping() {
+        let server = MockHttpServer::start_with(|line| {
+            if line.contains("pair=XBTUSDT") {
+                (200, r#"{"error":[],"result":{"XBTUSDT":{"a":["67010.0","1","1.000"],"b":["66990.0","2","2.000"],"c":["67000.0","0.1"]}}}"#.to_string())
+            } else {
+                (200, r#"{"error":["EQuery:Unknown asset pair"]}"#.to_string())
+            }
+        }).await;
+        let kraken = CexPriceSource::new(Exchange::Kraken, &server.url());
+        assert_eq!(kraken.map_asset("wbtc"), "XBT");
+        assert_eq!(kraken.get_price("WBTC", "USDT", None).await.unwrap(), 67000.0);
+        let err = kraken.get_price("ETH", "XYZ", None).await.unwrap_err();
+        assert_eq!(err, PriceError::NoPrice("No price for ETH/XYZ on kraken: EQuery:Unknown asset pair".to_string()));
+
+        let registry = TokenRegistry::new(vec![
+            TokenInfo::new("ARB", 18, 42161).with_exchange_symbol("kraken", "XBT"),
+            TokenInfo::new("USDT", 6, 42161),
+        ]).unwrap();
+        let custom = CexPriceSource::new(Exchange::Kraken, &server.url()).with_registry(&registry);
+        assert_eq!(custom.market("ARB", "USDT"), ("XBTUSDT".to_string(), false));
+        // Таблица реестра заменяет встроенную
+        assert_eq!(custom.map_asset("WBTC"), "WBTC");
+    }
+
+    #[tokio::test]
+    // Тип ошибки колбэка рукопожатия задан tungstenite
+    #[allow(clippy::result_large_err)]
+    async fn test_binance_ticker_stream() {
+        use futures_util::SinkExt;
+        use std::sync::Mutex;
+        use tokio_tungstenite::tungstenite::handshake::server::{Request, Response};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let path = Arc::new(Mutex::new(String::new()));
+        let server_path = path.clone();
+        tokio::spawn(async move {
+            let (tcp, _) = listener.accept().await.unwrap();
+            let mut socket = tokio_tungstenite::accept_hdr_async(tcp, |req: &Request, resp: Response| {
+                *server_path.lock().unwrap() = req.uri().to_string();
+                Ok(resp)
+            }).await.unwrap();
+            for (bid, ask, qty) in [("3199.5", "3200.5", "1.0"), ("3199.5", "3200.5", "2.0"), ("3299.5", "3300.5", "2.0")] {
+                let event = format!(r#"{{"stream":"ethusdt@bookTicker","data":{{"u":1,"s":"ETHUSDT","b":"{bid}","B":"{qty}","a":"{ask}","A":"1.0"}}}}"#);
+                socket.send(Message::Text(event)).await.unwrap();
+            }
+            socket.close(None).await.unwrap();
+        });
+
+        let stream = BinanceTickerStream::new(Arc::new(CexPriceSource::binance()), &url);
+        let mut subscription = stream.subscribe(&[("WETH", "USDT"), ("USDT", "ETH")]).await.unwrap();
+        let mut updates = Vec::new();
+        while let Some(update) = subscription.recv().await {
+            updates.push(update);
+        }
+        assert_eq!(path.lock().unwrap().as_str(), "/stream?streams=ethusdt@bookTicker");
+        // Второе событие меняет только объём и не публикуется
+        let direct: Vec<f64> = updates.iter().filter(|u| u.from == "WETH").map(|u| u.price).collect();
+        assert_eq!(direct, vec![3200.0, 3300.0]);
+        let inverse = updates.iter().rfind(|u| u.from == "USDT").unwrap();
+        assert!((inverse.price - (1.0 / 3300.5 + 1.0 / 3299.5) / 2.0).abs() < 1e-15);
+        assert_eq!(updates.len(), 4);
+
+        let kraken = BinanceTickerStream::new(Arc::new(CexPriceSource::kraken()), &url);
+        assert!(kraken.subscribe(&[("ETH", "USDT")]).await.is_err());
+    }
+}
@@ -0,0 +1,227 @@
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::{now_ms, PriceError, PriceSource, TimedPrice};
+
+#[cfg(feature = "uniswap")]
+ethers::contract::abigen!(
+    AggregatorV3,
+    r#"[
+        function decimals() external view returns (uint8)
+        function latestRoundData() external view returns (uint80 roundId, int256 answer, uint256 startedAt, uint256 updatedAt, uint80 answeredInRound)
+    ]"#
+);
+
+/// Ответ `latestRoundData` агрегатора.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RoundData {
+    pub round_id: u128,
+    pub answer: i128,
+    pub started_at: u64,
+    /// Unix time в секундах
+    pub updated_at: u64,
+    pub answered_in_round: u128,
+}
+
+/// Чтение агрегаторов; `feed` — адрес контракта. В тестах подменяется локальной заглушкой.
+#[async_trait]
+pub trait AggregatorReader: Send + Sync {
+    async fn decimals(&self, feed: &str) -> Result<u8, String>;
+    async fn latest_round_data(&self, feed: &str) -> Result<RoundData, String>;
+}
+
+/// Агрегаторы с живой ноды через `eth_call`.
+#[cfg(feature = "uniswap")]
+pub struct RpcAggregatorReader {
+    provider: Arc<ethers::providers::Provider<ethers::providers::Http>>,
+}
+
+#[cfg(feature = "uniswap")]
+impl RpcAggregatorReader {
+    pub fn new(provider: Arc<ethers::providers::Provider<ethers::providers::Http>>) -> Self {
+        Self { provider }
+    }
+
+    fn aggregator(&self, feed: &str) -> Result<AggregatorV3<ethers::providers::Provider<ethers::providers::Http>>, String> {
+        let address: ethers::types::Address = feed.parse().map_err(|e| format!("Invalid feed address {feed}: {e}"))?;
+        Ok(AggregatorV3::new(address, self.provider.clone()))
+    }
+}
+
+#[cfg(feature = "uniswap")]
+#[async_trait]
+impl AggregatorReader for RpcAggregatorReader {
+    async fn decimals(&self, feed: &str) -> Result<u8, String> {
+        self.aggregator(feed)?.decimals().call().await
+            .map_err(|e| format!("Get decimals failed: {e}"))
+    }
+
+    async fn latest_round_data(&self, feed: &str) -> Result<RoundData, String> {
+        let (round_id, answer, started_at, updated_at, answered_in_round) = self.aggregator(feed)?
+            .latest_round_data().call().await
+            .map_err(|e| format!("Get latestRoundData failed: {e}"))?;
+        let answer = i128::try_from(answer).map_err(|_| format!("Answer {answer} out of range"))?;
+        Ok(RoundData {
+            round_id,
+            answer,
+            started_at: started_at.low_u64(),
+            updated_at: updated_at.low_u64(),
+            answered_in_round,
+        })
+    }
+}
+
+struct Feed {
+    address: String,
+    heartbeat: Duration,
+}
+
+/// Опорная цена оракула: пары с фидами, обратные пары считаются как 1/answer.
+/// Ответ старше heartbeat фида, неположительный или из незавершённого раунда отклоняется.
+pub struct ChainlinkPriceSource {
+    reader: Arc<dyn AggregatorReader>,
+    feeds: HashMap<(String, String), Feed>,
+    // decimals агрегатора не меняются, читаем один раз
+    decimals: Mutex<HashMap<String, u8>>,
+}
+
+impl ChainlinkPriceSource {
+    pub fn new(reader: Arc<dyn AggregatorReader>) -> Self {
+        Self { reader, feeds: HashMap::new(), decimals: Mutex::new(HashMap::new()) }
+    }
+
+    /// Фид `base/quote` (например ETH/USD) по адресу `address` с периодом обновления `heartbeat`.
+    pub fn with_feed(mut self, base: &str, quote: &str, address: &str, heartbeat: Duration) -> Self {
+        self.feeds.insert(
+            (base.to_string(), quote.to_string()),
+            Feed { address: address.to_string(), heartbeat },
+        );
+        self
+    }
+
+    async fn feed_decimals(&self, address: &str) -> Result<u8, String> {
+        if let Some(decimals) = self.decimals.lock().unwrap().get(address) {
+            return Ok(*decimals);
+        }
+        let decimals = self.reader.decimals(address).await?;
+        self.decimals.lock().unwrap().insert(address.to_string(), decimals);
+        Ok(decimals)
+    }
+
+    async fn read_feed(&self, from: &str, to: &str, feed: &Feed) -> Result<TimedPrice, PriceError> {
+        let round = self.reader.latest_round_data(&feed.address).await?;
+        if round.answer <= 0 {
+            return Err(PriceError::Unavailable(format!("Invalid oracle answer {} for {from}/{to}", round.answer)));
+        }
+        if round.updated_at == 0 || round.answered_in_round < round.round_id {
+            return Err(PriceError::Unavailable(format!("Incomplete oracle round {} for {from}/{to}", round.round_id)));
+        }
+        let timestamp_ms = round.updated_at * 1000;
+        let age = Duration::from_millis(now_ms().saturating_sub(timestamp_ms));
+        if age > feed.heartbeat {
+            return Err(PriceError::Unavailable(format!(
+                "Stale price for {from}/{to}: oracle updated {}s ago, heartbeat {}s",
+                age.as_secs(),
+                feed.heartbeat.as_secs()
+            )));
+        }
+        let decimals = self.feed_decimals(&feed.address).await?;
+        let price = round.answer as f64 / 10f64.powi(decimals as i32);
+        Ok(TimedPrice { price, timestamp_ms })
+    }
+}
+
+#[async_trait]
+impl PriceSource for ChainlinkPriceSource {
+    async fn get_price(&self, from: &str, to: &str, amount: Option<&str>) -> Result<f64, PriceError> {
+        Ok(self.get_price_timed(from, to, amount).await?.price)
+    }
+
+    /// Время цены — `updatedAt` раунда.
+    async fn get_price_timed(&self, from: &str, to: &str, _amount: Option<&str>) -> Result<TimedPrice, PriceError> {
+        if let Some(feed) = self.feeds.get(&(from.to_string(), to.to_string())) {
+            return self.read_feed(from, to, feed).await;
+        }
+        if let Some(feed) = self.feeds.get(&(to.to_string(), from.to_string())) {
+            let timed = self.read_feed(to, from, feed).await?;
+            return Ok(TimedPrice { price: 1.0 / timed.price, ..timed });
+        }
+        Err(PriceError::no_price(from, to))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const ETH_USD: &str = "0x5f4eC3Df9cbd43714FE2740f5E3616155c5b8419";
+
+    /// Локальная заглушка агрегатора: фиксированный раунд и счётчик чтений decimals.
+    struct StandIn {
+        round: Mutex<RoundData>,
+        decimals_calls: AtomicUsize,
+    }
+
+    impl StandIn {
+        fn new(answer: i128, age_secs: u64) -> Arc<Self> {
+            Arc::new(Self {
+                round: Mutex::new(round(answer, age_secs)),
+                decimals_calls: AtomicUsize::new(0),
+            })
+        }
+    }
+
+    fn round(answer: i128, age_secs: u64) -> RoundData {
+        let updated_at = now_ms() / 1000 - age_secs;
+        RoundData { round_id: 7, answer, started_at: updated_at, updated_at, answered_in_round: 7 }
+    }
+
+    #[async_trait]
+    impl AggregatorReader for StandIn {
+        async fn decimals(&self, feed: &str) -> Result<u8, String> {
+            assert_eq!(feed, ETH_USD);
+            self.decimals_calls.fetch_add(1, Ordering::SeqCst);
+            Ok(8)
+        }
+
+        async fn latest_round_data(&self, _feed: &str) -> Result<RoundData, String> {
+            Ok(*self.round.lock().unwrap())
+        }
+    }
+
+    fn source(reader: Arc<StandIn>) -> ChainlinkPriceSource {
+        ChainlinkPriceSource::new(reader).with_feed("ETH", "USD", ETH_USD, Duration::from_secs(3600))
+    }
+
+    #[tokio::test]
+    async fn test_chainlink_scales_answer_and_inverts() {
+        let reader = StandIn::new(320_012_345_678, 60);
+        let source = source(reader.clone());
+        let timed = source.get_price_timed("ETH", "USD", None).await.unwrap();
+        assert!((timed.price - 3200.12345678).abs() < 1e-9);
+        assert!(now_ms() - timed.timestamp_ms >= 60_000);
+        let inverse = source.get_price("USD", "ETH", None).await.unwrap();
+        assert!((inverse - 1.0 / 3200.12345678).abs() < 1e-15);
+        assert_eq!(reader.decimals_calls.load(Ordering::SeqCst), 1);
+        assert!(source.get_price("BTC", "USD", None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_chainlink_rejects_stale_and_invalid_rounds() {
+        let reader = StandIn::new(320_000_000_000, 2 * 3600);
+        let source = source(reader.clone());
+        let err = source.get_price("ETH", "USD", None).await.unwrap_err();
+        assert!(err.to_string().starts_with("Stale price for ETH/USD"), "{err}");
+
+        *reader.round.lock().unwrap() = round(0, 10);
+        assert!(source.get_price("ETH", "USD", None).await.unwrap_err().to_string().starts_with("Invalid oracle answer"));
+
+        let mut incomplete = round(320_000_000_000, 10);
+        incomplete.answered_in_round = 6;
+        *reader.round.lock().unwrap() = incomplete;
+        assert!(source.get_price("ETH", "USD", None).await.unwrap_err().to_string().starts_with("Incomplete oracle round"));
+    }
+}
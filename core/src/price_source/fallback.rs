@@ -0,0 +1,229 @@
+use async_trait::async_trait;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::{ExactOutPrice, PriceError, PriceSource, TimedPrice};
+
+/// Источник в цепочке: имя (для диагностики), сам источник и его личный таймаут.
+struct FallbackEntry {
+    name: String,
+    source: Arc<dyn PriceSource>,
+    timeout: Duration,
+}
+
+/// Причина, по которой источник из цепочки не дал цену.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceFailure {
+    pub source: String,
+    pub reason: PriceError,
+}
+
+impl fmt::Display for SourceFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.source, self.reason)
+    }
+}
+
+/// Результат цепочки: цена, источник, который её дал, и отказы источников перед ним.
+#[derive(Debug, Clone)]
+pub struct FallbackPrice {
+    pub price: f64,
+    pub timestamp_ms: u64,
+    pub source: String,
+    pub failures: Vec<SourceFailure>,
+}
+
+/// Опрашивает источники по приоритету (например Uniswap → CoinGecko → статика)
+/// и возвращает первую успешную цену. Каждый источник ограничен своим таймаутом.
+#[derive(Default)]
+pub struct FallbackPriceSource {
+    sources: Vec<FallbackEntry>,
+}
+
+impl FallbackPriceSource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Добавляет источник в конец цепочки (с наименьшим приоритетом).
+    pub fn with_source(mut self, name: &str, source: Arc<dyn PriceSource>, timeout: Duration) -> Self {
+        self.sources.push(FallbackEntry {
+            name: name.to_string(),
+            source,
+            timeout,
+        });
+        self
+    }
+
+    pub fn source_names(&self) -> Vec<&str> {
+        self.sources.iter().map(|e| e.name.as_str()).collect()
+    }
+
+    /// Как `get_price`, но сообщает, какой источник ответил и почему отказали предыдущие.
+    pub async fn get_price_detailed(
+        &self,
+        from: &str,
+        to: &str,
+        amount: Option<&str>,
+    ) -> Result<FallbackPrice, Vec<SourceFailure>> {
+        let mut failures = Vec::new();
+        for entry in &self.sources {
+            let reason = match tokio::time::timeout(entry.timeout, entry.source.get_price_timed(from, to, amount)).await {
+                Ok(Ok(timed)) => {
+                    return Ok(FallbackPrice {
+                        price: timed.price,
+                        timestamp_ms: timed.timestamp_ms,
+                        source: entry.name.clone(),
+                        failures,
+                    });
+                }
+                Ok(Err(e)) => e,
+                Err(_) => PriceError::Unavailable(format!("timed out after {:?}", entry.timeout)),
+            };
+            failures.push(SourceFailure {
+                source: entry.name.clone(),
+                reason,
+            });
+        }
+        Err(failures)
+    }
+}
+
+#[async_trait]
+impl PriceSource for FallbackPriceSource {
+    async fn get_price(&self, from: &str, to: &str, amount: Option<&str>) -> Result<f64, PriceError> {
+        Ok(self.get_price_timed(from, to, amount).await?.price)
+    }
+
+    async fn get_price_timed(&self, from: &str, to: &str, amount: Option<&str>) -> Result<TimedPrice, PriceError> {
+        match self.get_price_detailed(from, to, amount).await {
+            Ok(result) => Ok(TimedPrice { price: result.price, timestamp_ms: result.timestamp_ms }),
+            Err(failures) => Err(all_failed(from, to, &failures)),
+        }
+    }
+
+    /// Котировка первого источника, который её дал.
+    async fn get_price_exact_out(&self, from: &str, to: &str, amount_out: &str) -> Result<ExactOutPrice, PriceError> {
+        let mut failures = Vec::new();
+        for entry in &self.sources {
+            let reason = match tokio::time::timeout(entry.timeout, entry.source.get_price_exact_out(from, to, amount_out)).await {
+                Ok(Ok(quote)) => return Ok(quote),
+                Ok(Err(e)) => e,
+                Err(_) => PriceError::Unavailable(format!("timed out after {:?}", entry.timeout)),
+            };
+            failures.push(SourceFailure { source: entry.name.clone(), reason });
+        }
+        Err(all_failed(from, to, &failures))
+    }
+
+    /// Пакет целиком уходит в первый источник; пары, на которых он отказал, —
+    /// следующим пакетом во второй и так далее.
+    async fn get_prices(&self, pairs: &[(&str, &str)]) -> Vec<Result<f64, PriceError>> {
+        let mut prices: Vec<Option<f64>> = vec![None; pairs.len()];
+        let mut failures: Vec<Vec<SourceFailure>> = vec![Vec::new(); pairs.len()];
+        for entry in &self.sources {
+            let pending: Vec<usize> = (0..pairs.len()).filter(|&i| prices[i].is_none()).collect();
+            if pending.is_empty() {
+                break;
+            }
+            let batch: Vec<(&str, &str)> = pending.iter().map(|&i| pairs[i]).collect();
+            let fetched = match tokio::time::timeout(entry.timeout, entry.source.get_prices(&batch)).await {
+                Ok(fetched) => fetched,
+                Err(_) => {
+                    let reason = PriceError::Unavailable(format!("timed out after {:?}", entry.timeout));
+                    batch.iter().map(|_| Err(reason.clone())).collect()
+                }
+            };
+            for (i, result) in pending.into_iter().zip(fetched) {
+                match result {
+                    Ok(price) => prices[i] = Some(price),
+                    Err(reason) => failures[i].push(SourceFailure { source: entry.name.clone(), reason }),
+                }
+            }
+        }
+        pairs
+            .iter()
+            .zip(prices.into_iter().zip(failures))
+            .map(|((from, to), (price, failures))| price.ok_or_else(|| all_failed(from, to, &failures)))
+            .collect()
+    }
+}
+
+fn all_failed(from: &str, to: &str, failures: &[SourceFailure]) -> PriceError {
+    if failures.is_empty() {
+        return PriceError::NoPrice("No price sources configured".to_string());
+    }
+    let reasons: Vec<String> = failures.iter().map(|f| f.to_string()).collect();
+    let message = format!("All price sources failed for {from}/{to}: {}", reasons.join("; "));
+    // Повтор имеет смысл, если хоть один источник отказал временно
+    if failures.iter().any(|f| f.reason.is_transient()) {
+        PriceError::Unavailable(message)
+    } else {
+        PriceError::NoPrice(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::price_source::{MockPriceSource, StaticPriceSource};
+
+    struct SlowPriceSource(Duration);
+
+    #[async_trait]
+    impl PriceSource for SlowPriceSource {
+        async fn get_price(&self, _from: &str, _to: &str, _amount: Option<&str>) -> Result<f64, PriceError> {
+            tokio::time::sleep(self.0).await;
+            Ok(1.0)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fallback_uses_first_success() {
+        let chain = FallbackPriceSource::new()
+            .with_source("mock", Arc::new(MockPriceSource), Duration::from_secs(1))
+            .with_source("static", Arc::new(StaticPriceSource::new().with_pair("ETH", "USDT", 1.0)), Duration::from_secs(1));
+        let result = chain.get_price_detailed("ETH", "USDT", None).await.unwrap();
+        assert_eq!(result.price, 3200.0);
+        assert_eq!(result.source, "mock");
+        assert!(result.failures.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_fallback_records_earlier_failures() {
+        let chain = FallbackPriceSource::new()
+            .with_source("slow", Arc::new(SlowPriceSource(Duration::from_secs(5))), Duration::from_millis(20))
+            .with_source("mock", Arc::new(MockPriceSource), Duration::from_secs(1))
+            .with_source("static", Arc::new(StaticPriceSource::new().with_pair("DOGE", "USDT", 0.1)), Duration::from_secs(1));
+        let result = chain.get_price_detailed("DOGE", "USDT", None).await.unwrap();
+        assert_eq!(result.source, "static");
+        assert_eq!(result.failures.len(), 2);
+        assert_eq!(result.failures[0].source, "slow");
+        assert!(result.failures[0].reason.to_string().contains("timed out"));
+        assert_eq!(result.failures[1].reason, PriceError::no_price("DOGE", "USDT"));
+    }
+
+    #[tokio::test]
+    async fn test_fallback_all_failed() {
+        let chain = FallbackPriceSource::new()
+            .with_source("mock", Arc::new(MockPriceSource), Duration::from_secs(1));
+        let err = chain.get_price("DOGE", "USDT", None).await.unwrap_err();
+        assert!(err.to_string().contains("mock: No price for DOGE/USDT"));
+
+        let empty = FallbackPriceSource::new();
+        assert!(empty.get_price("ETH", "USDT", None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fallback_batch_passes_failures_on() {
+        let chain = FallbackPriceSource::new()
+            .with_source("mock", Arc::new(MockPriceSource), Duration::from_secs(1))
+            .with_source("static", Arc::new(StaticPriceSource::new().with_pair("DOGE", "USDT", 0.1)), Duration::from_secs(1));
+        let prices = chain.get_prices(&[("ETH", "USDT"), ("DOGE", "USDT"), ("PEPE", "USDT")]).await;
+        assert_eq!(prices[0], Ok(3200.0));
+        assert_eq!(prices[1], Ok(0.1));
+        let err = prices[2].as_ref().unwrap_err();
+        assert!(err.to_string().contains("mock: No price for PEPE/USDT; static: No price for PEPE/USDT"), "{err}");
+    }
+}
@@ -0,0 +1,298 @@
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::{now_ms, ExactOutPrice, PriceError, PriceSource, TimedPrice, TriangulatedPrice};
+
+/// Защита котировок: отказывает, если цена старше `max_age`, или если она
+/// ушла от опорной цены пары больше чем на `max_deviation_pct` процентов и скачок
+/// не подтверждён вторым источником (`with_confirmation`). Без второго источника
+/// котирование пары стоит, пока цена не вернётся к опорной.
+/// Лучше не котировать, чем котировать мусор.
+pub struct GuardedPriceSource {
+    primary: Arc<dyn PriceSource>,
+    confirmation: Option<Arc<dyn PriceSource>>,
+    max_age: Duration,
+    max_deviation_pct: f64,
+    confirm_reads: u32,
+    // Ключ — пара (from, to): объём не должен давать обойти проверку
+    references: Mutex<HashMap<(String, String), Reference>>,
+}
+
+/// Последняя принятая цена пары и скачок, который ждёт подтверждения.
+struct Reference {
+    price: f64,
+    // Цена скачка и сколько чтений подряд она держится
+    pending: Option<(f64, u32)>,
+}
+
+impl GuardedPriceSource {
+    pub fn new(primary: Arc<dyn PriceSource>, max_age: Duration, max_deviation_pct: f64) -> Self {
+        Self {
+            primary,
+            confirmation: None,
+            max_age,
+            max_deviation_pct,
+            confirm_reads: 0,
+            references: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Источник, которым подтверждается резкий скачок цены.
+    pub fn with_confirmation(mut self, source: Arc<dyn PriceSource>) -> Self {
+        self.confirmation = Some(source);
+        self
+    }
+
+    /// Принимать скачок и без второго источника, если `primary` повторяет его `reads` чтений подряд.
+    /// По умолчанию `0` — только второй источник: сломанный фид повторит мусор и сам.
+    pub fn with_confirm_reads(mut self, reads: u32) -> Self {
+        self.confirm_reads = reads;
+        self
+    }
+
+    /// Опорная цена пары — последняя принятая.
+    pub fn last_accepted(&self, from: &str, to: &str) -> Option<f64> {
+        self.references.lock().unwrap().get(&key(from, to)).map(|r| r.price)
+    }
+
+    fn check_fresh(&self, from: &str, to: &str, timed: &TimedPrice) -> Result<(), PriceError> {
+        let age = Duration::from_millis(now_ms().saturating_sub(timed.timestamp_ms));
+        if age > self.max_age {
+            return Err(PriceError::Unavailable(format!(
+                "Stale price for {from}/{to}: age {}s exceeds max {}s",
+                age.as_secs(),
+                self.max_age.as_secs()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Засчитывает чтение скачка `price`; `true`, если он держится `confirm_reads` чтений подряд.
+    fn repeated_jump(&self, from: &str, to: &str, price: f64) -> bool {
+        if self.confirm_reads == 0 {
+            return false;
+        }
+        let mut references = self.references.lock().unwrap();
+        let Some(reference) = references.get_mut(&key(from, to)) else {
+            return false;
+        };
+        let reads = match reference.pending {
+            Some((pending, reads)) if deviation_pct(price, pending) <= self.max_deviation_pct => reads + 1,
+            _ => 1,
+        };
+        reference.pending = Some((price, reads));
+        reads >= self.confirm_reads
+    }
+
+    /// Проверки цены `primary`: корректность, свежесть, скачок от опорной. Цена на объём `amount`
+    /// отличается от спота на price impact: для неё — только корректность и свежесть, опорой она не становится.
+    async fn admit(&self, from: &str, to: &str, amount: Option<&str>, timed: TimedPrice) -> Result<TimedPrice, PriceError> {
+        if !timed.price.is_finite() || timed.price <= 0.0 {
+            return Err(PriceError::Unavailable(format!("Invalid price for {from}/{to}: {}", timed.price)));
+        }
+        self.check_fresh(from, to, &timed)?;
+        if amount.is_some() {
+            return Ok(timed);
+        }
+        if let Some(last) = self.last_accepted(from, to) {
+            let deviation = deviation_pct(timed.price, last);
+            if deviation > self.max_deviation_pct && !self.repeated_jump(from, to, timed.price) {
+                let confirmation = self.confirmation.as_ref().ok_or_else(|| PriceError::Unavailable(format!(
+                    "Price for {from}/{to} moved {deviation:.2}% from last accepted {last}, not confirmed yet"
+                )))?;
+                let confirmed = confirmation.get_price_timed(from, to, None).await
+                    .map_err(|e| e.context(&format!("Price for {from}/{to} moved {deviation:.2}%, confirmation failed")))?;
+                self.check_fresh(from, to, &confirmed)?;
+                if deviation_pct(timed.price, confirmed.price) > self.max_deviation_pct {
+                    return Err(PriceError::Unavailable(format!(
+                        "Price for {from}/{to} moved {deviation:.2}% and was not confirmed (second source: {})",
+                        confirmed.price
+                    )));
+                }
+            }
+        }
+
+        self.accept(from, to, timed.price);
+        Ok(timed)
+    }
+
+    fn accept(&self, from: &str, to: &str, price: f64) {
+        self.references.lock().unwrap().insert(
+            key(from, to),
+            Reference { price, pending: None },
+        );
+    }
+}
+
+fn key(from: &str, to: &str) -> (String, String) {
+    (from.to_string(), to.to_string())
+}
+
+fn deviation_pct(price: f64, reference: f64) -> f64 {
+    ((price - reference) / reference).abs() * 100.0
+}
+
+#[async_trait]
+impl PriceSource for GuardedPriceSource {
+    async fn get_price(&self, from: &str, to: &str, amount: Option<&str>) -> Result<f64, PriceError> {
+        Ok(self.get_price_timed(from, to, amount).await?.price)
+    }
+
+    async fn get_price_timed(&self, from: &str, to: &str, amount: Option<&str>) -> Result<TimedPrice, PriceError> {
+        let timed = self.primary.get_price_timed(from, to, amount).await?;
+        self.admit(from, to, amount, timed).await
+    }
+
+    async fn get_price_with_path(&self, from: &str, to: &str, amount: Option<&str>) -> Result<TriangulatedPrice, PriceError> {
+        let priced = self.primary.get_price_with_path(from, to, amount).await?;
+        self.admit(from, to, amount, TimedPrice { price: priced.price, timestamp_ms: priced.timestamp_ms }).await?;
+        Ok(priced)
+    }
+
+    /// Проверяются корректность и свежесть цены. Скачок от опорной не проверяется и опорой не становится:
+    /// цена на объём отличается от спота на price impact.
+    async fn get_price_exact_out(&self, from: &str, to: &str, amount_out: &str) -> Result<ExactOutPrice, PriceError> {
+        let quote = self.primary.get_price_exact_out(from, to, amount_out).await?;
+        if !quote.price.is_finite() || quote.price <= 0.0 {
+            return Err(PriceError::Unavailable(format!("Invalid price for {from}/{to}: {}", quote.price)));
+        }
+        self.check_fresh(from, to, &TimedPrice { price: quote.price, timestamp_ms: quote.timestamp_ms })?;
+        Ok(quote)
+    }
+
+    /// Пакет уходит в `primary` одним вызовом; каждая цена проходит те же проверки.
+    /// Время пакетных цен — момент ответа.
+    async fn get_prices(&self, pairs: &[(&str, &str)]) -> Vec<Result<f64, PriceError>> {
+        let fetched = self.primary.get_prices(pairs).await;
+        let timestamp_ms = now_ms();
+        let mut prices = Vec::with_capacity(pairs.len());
+        for ((from, to), result) in pairs.iter().zip(fetched) {
+            prices.push(match result {
+                Ok(price) => self.admit(from, to, None, TimedPrice { price, timestamp_ms }).await.map(|t| t.price),
+                Err(e) => Err(e),
+            });
+        }
+        prices
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::price_source::StaticPriceSource;
+
+    /// Отдаёт цену из ячейки с заданным "возрастом".
+    struct ScriptedPriceSource {
+        price: Mutex<f64>,
+        age: Duration,
+    }
+
+    impl ScriptedPriceSource {
+        fn new(price: f64, age: Duration) -> Arc<Self> {
+            Arc::new(Self { price: Mutex::new(price), age })
+        }
+        fn set(&self, price: f64) {
+            *self.price.lock().unwrap() = price;
+        }
+    }
+
+    #[async_trait]
+    impl PriceSource for ScriptedPriceSource {
+        async fn get_price(&self, _from: &str, _to: &str, _amount: Option<&str>) -> Result<f64, PriceError> {
+            Ok(*self.price.lock().unwrap())
+        }
+        async fn get_price_timed(&self, from: &str, to: &str, amount: Option<&str>) -> Result<TimedPrice, PriceError> {
+            let price = self.get_price(from, to, amount).await?;
+            Ok(TimedPrice { price, timestamp_ms: now_ms() - self.age.as_millis() as u64 })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rejects_stale_price() {
+        let source = ScriptedPriceSource::new(3200.0, Duration::from_secs(120));
+        let guard = GuardedPriceSource::new(source, Duration::from_secs(60), 5.0);
+        let err = guard.get_price("ETH", "USDT", None).await.unwrap_err();
+        assert!(err.to_string().starts_with("Stale price for ETH/USDT"));
+    }
+
+    #[tokio::test]
+    async fn test_rejects_unconfirmed_jump() {
+        let source = ScriptedPriceSource::new(3200.0, Duration::ZERO);
+        let guard = GuardedPriceSource::new(source.clone(), Duration::from_secs(60), 5.0);
+        assert_eq!(guard.get_price("ETH", "USDT", None).await.unwrap(), 3200.0);
+
+        source.set(3300.0);
+        assert_eq!(guard.get_price("ETH", "USDT", None).await.unwrap(), 3300.0);
+
+        // Без второго источника скачок не проходит и при повторе
+        source.set(32.0);
+        for _ in 0..3 {
+            assert!(guard.get_price("ETH", "USDT", None).await.is_err());
+        }
+        assert_eq!(guard.last_accepted("ETH", "USDT"), Some(3300.0));
+    }
+
+    #[tokio::test]
+    async fn test_jump_accepted_after_consecutive_reads() {
+        let source = ScriptedPriceSource::new(3200.0, Duration::ZERO);
+        let guard = GuardedPriceSource::new(source.clone(), Duration::from_secs(60), 5.0).with_confirm_reads(2);
+        guard.get_price("ETH", "USDT", None).await.unwrap();
+
+        // Одиночные выбросы не накапливаются
+        source.set(2500.0);
+        assert!(guard.get_price("ETH", "USDT", None).await.is_err());
+        source.set(1000.0);
+        assert!(guard.get_price("ETH", "USDT", None).await.is_err());
+
+        source.set(2500.0);
+        assert!(guard.get_price("ETH", "USDT", None).await.is_err());
+        assert_eq!(guard.get_price("ETH", "USDT", None).await.unwrap(), 2500.0);
+        assert_eq!(guard.last_accepted("ETH", "USDT"), Some(2500.0));
+    }
+
+    #[tokio::test]
+    async fn test_amount_price_checked_for_freshness_only() {
+        let source = ScriptedPriceSource::new(3200.0, Duration::ZERO);
+        let guard = GuardedPriceSource::new(source.clone(), Duration::from_secs(60), 5.0);
+        guard.get_price("ETH", "USDT", None).await.unwrap();
+
+        // Цена на большой объём ниже спота на price impact — не скачок и не новая опора
+        source.set(2900.0);
+        assert_eq!(guard.get_price("ETH", "USDT", Some("500")).await.unwrap(), 2900.0);
+        assert_eq!(guard.last_accepted("ETH", "USDT"), Some(3200.0));
+        assert!(guard.get_price("ETH", "USDT", None).await.is_err());
+
+        let stale = GuardedPriceSource::new(ScriptedPriceSource::new(3200.0, Duration::from_secs(120)), Duration::from_secs(60), 5.0);
+        assert!(stale.get_price("ETH", "USDT", Some("1")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_batch_goes_through_checks() {
+        let source = ScriptedPriceSource::new(3200.0, Duration::ZERO);
+        let guard = GuardedPriceSource::new(source.clone(), Duration::from_secs(60), 5.0);
+        let prices = guard.get_prices(&[("ETH", "USDT"), ("WBTC", "USDT")]).await;
+        assert_eq!(prices, vec![Ok(3200.0), Ok(3200.0)]);
+
+        source.set(32.0);
+        assert!(guard.get_prices(&[("ETH", "USDT")]).await[0].is_err());
+        assert_eq!(guard.last_accepted("ETH", "USDT"), Some(3200.0));
+    }
+
+    #[tokio::test]
+    async fn test_jump_confirmed_by_second_source() {
+        let source = ScriptedPriceSource::new(3200.0, Duration::ZERO);
+        let confirm = Arc::new(StaticPriceSource::new().with_pair("ETH", "USDT", 2500.0));
+        let guard = GuardedPriceSource::new(source.clone(), Duration::from_secs(60), 5.0)
+            .with_confirmation(confirm);
+        guard.get_price("ETH", "USDT", None).await.unwrap();
+
+        source.set(2510.0);
+        assert_eq!(guard.get_price("ETH", "USDT", None).await.unwrap(), 2510.0);
+
+        source.set(100.0);
+        let err = guard.get_price("ETH", "USDT", None).await.unwrap_err();
+        assert!(err.to_string().contains("not confirmed"));
+    }
+}
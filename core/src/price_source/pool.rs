@@ -0,0 +1,129 @@
+use async_trait::async_trait;
+use std::sync::RwLock;
+
+use super::{ExactOutPrice, PriceError, PriceSource};
+use crate::amm::{self, AmmError, AmmQuote, U256};
+
+/// Математика снимка пула для `PoolPriceSource`. `i`, `j` — индексы токенов в порядке контракта.
+pub trait PoolModel: Send + Sync {
+    /// Decimals токенов пула; длина — число токенов.
+    fn decimals(&self) -> &[u8];
+    /// Цена `j` за единицу `i` без объёма.
+    fn spot(&self, i: usize, j: usize) -> Result<f64, AmmError>;
+    /// Своп ровно `amount_in` base units токена `i`.
+    fn quote(&self, i: usize, j: usize, amount_in: U256) -> Result<AmmQuote, AmmError>;
+    /// Вход за ровно `amount_out` base units токена `j`, округлён вверх.
+    fn quote_exact_out(&self, i: usize, j: usize, amount_out: U256) -> Result<AmmQuote, AmmError>;
+}
+
+/// `PriceSource` поверх снимка пула. Снимок обновляется снаружи через `update_pool`.
+pub struct PoolPriceSource<P> {
+    symbols: Vec<String>,
+    pool: RwLock<P>,
+}
+
+impl<P: PoolModel> PoolPriceSource<P> {
+    /// `symbols` — токены пула в порядке индексов контракта.
+    pub fn new(symbols: &[&str], pool: P) -> Result<Self, String> {
+        if symbols.len() != pool.decimals().len() {
+            return Err(format!("Pool has {} tokens, got {} symbols", pool.decimals().len(), symbols.len()));
+        }
+        Ok(Self {
+            symbols: symbols.iter().map(|s| s.to_string()).collect(),
+            pool: RwLock::new(pool),
+        })
+    }
+
+    pub fn update_pool(&self, pool: P) -> Result<(), String> {
+        if pool.decimals().len() != self.symbols.len() {
+            return Err("Pool token count changed".to_string());
+        }
+        *self.pool.write().unwrap() = pool;
+        Ok(())
+    }
+
+    fn index(&self, symbol: &str) -> Option<usize> {
+        self.symbols.iter().position(|s| s == symbol)
+    }
+
+    fn indices(&self, from: &str, to: &str) -> Result<(usize, usize), PriceError> {
+        match (self.index(from), self.index(to)) {
+            (Some(i), Some(j)) if i != j => Ok((i, j)),
+            _ => Err(PriceError::unsupported_pair()),
+        }
+    }
+}
+
+#[async_trait]
+impl<P: PoolModel + 'static> PriceSource for PoolPriceSource<P> {
+    /// Без `amount` — спот пула; с `amount` — эффективная цена свопа по кривой.
+    async fn get_price(&self, from: &str, to: &str, amount: Option<&str>) -> Result<f64, PriceError> {
+        let (i, j) = self.indices(from, to)?;
+        let pool = self.pool.read().unwrap();
+        match amount {
+            Some(amount) => {
+                let amount_in = amm::parse_units(amount, pool.decimals()[i])?;
+                Ok(pool.quote(i, j, amount_in)?.effective_price)
+            }
+            None => Ok(pool.spot(i, j)?),
+        }
+    }
+
+    /// Вход по кривой за ровно `amount_out`.
+    async fn get_price_exact_out(&self, from: &str, to: &str, amount_out: &str) -> Result<ExactOutPrice, PriceError> {
+        let (i, j) = self.indices(from, to)?;
+        let pool = self.pool.read().unwrap();
+        let amount_out = amm::parse_units(amount_out, pool.decimals()[j])?;
+        Ok(ExactOutPrice::from_amm(&pool.quote_exact_out(i, j, amount_out)?, pool.decimals()[i]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Пул с фиксированным курсом токена `1` к токену `0`, без проскальзывания
+    struct FixedRate {
+        decimals: Vec<u8>,
+        rate: f64,
+    }
+
+    impl PoolModel for FixedRate {
+        fn decimals(&self) -> &[u8] {
+            &self.decimals
+        }
+
+        fn spot(&self, i: usize, _j: usize) -> Result<f64, AmmError> {
+            Ok(if i == 0 { self.rate } else { 1.0 / self.rate })
+        }
+
+        fn quote(&self, i: usize, j: usize, amount_in: U256) -> Result<AmmQuote, AmmError> {
+            let amount_out = amm::parse_units(&(amm::to_f64(amount_in, self.decimals[i]) * self.spot(i, j)?).to_string(), self.decimals[j])?;
+            Ok(AmmQuote::new(amount_in, amount_out, self.spot(i, j)?, self.decimals[i], self.decimals[j]))
+        }
+
+        fn quote_exact_out(&self, i: usize, j: usize, amount_out: U256) -> Result<AmmQuote, AmmError> {
+            let amount_in = amm::parse_units(&(amm::to_f64(amount_out, self.decimals[j]) / self.spot(i, j)?).to_string(), self.decimals[i])?;
+            Ok(AmmQuote::new(amount_in, amount_out, self.spot(i, j)?, self.decimals[i], self.decimals[j]))
+        }
+    }
+
+    fn pool(rate: f64) -> FixedRate {
+        FixedRate { decimals: vec![18, 6], rate }
+    }
+
+    #[tokio::test]
+    async fn test_any_pool_model_is_a_price_source() {
+        let source = PoolPriceSource::new(&["ETH", "USDT"], pool(3000.0)).unwrap();
+        assert_eq!(source.get_price("ETH", "USDT", None).await.unwrap(), 3000.0);
+        assert_eq!(source.get_price("ETH", "USDT", Some("2")).await.unwrap(), 3000.0);
+        let quote = source.get_price_exact_out("ETH", "USDT", "6000").await.unwrap();
+        assert_eq!(quote.amount_in.as_deref(), Some("2"));
+        assert!(source.get_price("ETH", "BTC", None).await.is_err());
+
+        source.update_pool(pool(2500.0)).unwrap();
+        assert_eq!(source.get_price("ETH", "USDT", None).await.unwrap(), 2500.0);
+        assert!(source.update_pool(FixedRate { decimals: vec![18, 6, 6], rate: 1.0 }).is_err());
+        assert!(PoolPriceSource::new(&["ETH"], pool(1.0)).is_err());
+    }
+}
@@ -0,0 +1,365 @@
+use async_trait::async_trait;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Instant;
+
+use super::replay::PriceRecord;
+use super::{now_ms, ExactOutPrice, PriceError, PriceSource, TimedPrice, TriangulatedPrice};
+
+/// Сколько записей ждёт потока записи; сверх — запись отбрасывается как ошибка.
+const QUEUE_CAPACITY: usize = 4096;
+
+/// Журнал `PriceRecord` в JSONL: `<dir>/<name>.jsonl`, при превышении `max_bytes`
+/// файл сдвигается в `<name>.1.jsonl` (старые — `.2`, `.3`, …), хранится `max_files` архивов.
+/// Каждый файл читается `ReplayPriceSource::load`.
+///
+/// Пишет отдельный поток: `record` только ставит запись в очередь и не блокирует запрос цены.
+pub struct PriceRecorder {
+    file: RecordFile,
+    writer: OnceLock<SyncSender<Command>>,
+    errors: Arc<Mutex<WriteErrors>>,
+}
+
+enum Command {
+    Write(PriceRecord),
+    Flush(mpsc::Sender<()>),
+}
+
+#[derive(Default)]
+struct WriteErrors {
+    count: u64,
+    last: Option<String>,
+}
+
+impl WriteErrors {
+    fn push(&mut self, error: String) {
+        self.count += 1;
+        self.last = Some(error);
+    }
+}
+
+impl PriceRecorder {
+    /// По умолчанию файлы по 64 МиБ, 10 архивов.
+    pub fn new(dir: impl AsRef<Path>, name: &str) -> Result<Self, String> {
+        let dir = dir.as_ref().to_path_buf();
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| format!("Cannot create price record dir {}: {e}", dir.display()))?;
+        Ok(Self {
+            file: RecordFile { dir, name: name.to_string(), max_bytes: 64 * 1024 * 1024, max_files: 10, file: None },
+            writer: OnceLock::new(),
+            errors: Arc::default(),
+        })
+    }
+
+    pub fn with_rotation(mut self, max_bytes: u64, max_files: usize) -> Self {
+        self.file.max_bytes = max_bytes;
+        self.file.max_files = max_files;
+        self
+    }
+
+    /// Текущий файл журнала.
+    pub fn path(&self) -> PathBuf {
+        self.file.archive_path(0)
+    }
+
+    /// Сколько записей не удалось сохранить.
+    pub fn write_errors(&self) -> u64 {
+        self.errors.lock().unwrap().count
+    }
+
+    /// Последняя ошибка записи.
+    pub fn last_write_error(&self) -> Option<String> {
+        self.errors.lock().unwrap().last.clone()
+    }
+
+    /// Ставит запись в очередь потока записи. Ошибка записи или переполненная очередь
+    /// не прерывают запрос цены: они считаются в `write_errors`.
+    pub fn record(&self, record: PriceRecord) {
+        let error = match self.writer().try_send(Command::Write(record)) {
+            Ok(()) => return,
+            Err(TrySendError::Full(_)) => "record queue is full".to_string(),
+            Err(TrySendError::Disconnected(_)) => "record writer stopped".to_string(),
+        };
+        self.errors.lock().unwrap().push(format!("Price record not written to {}: {error}", self.path().display()));
+    }
+
+    /// Ждёт, пока поток записи сохранит всё, что уже в очереди.
+    pub fn flush(&self) {
+        let (done, flushed) = mpsc::channel();
+        if self.writer().send(Command::Flush(done)).is_ok() {
+            let _ = flushed.recv();
+        }
+    }
+
+    /// Поток записи запускается при первой записи и живёт, пока жив журнал.
+    fn writer(&self) -> &SyncSender<Command> {
+        self.writer.get_or_init(|| {
+            let (sender, commands) = mpsc::sync_channel(QUEUE_CAPACITY);
+            let mut file = self.file.clone();
+            let errors = self.errors.clone();
+            std::thread::spawn(move || {
+                for command in commands {
+                    match command {
+                        Command::Write(record) => {
+                            if let Err(e) = file.append(&record) {
+                                let path = file.archive_path(0);
+                                errors.lock().unwrap().push(format!("Price record not written to {}: {e}", path.display()));
+                            }
+                        }
+                        Command::Flush(done) => {
+                            let _ = done.send(());
+                        }
+                    }
+                }
+            });
+            sender
+        })
+    }
+}
+
+/// Файл журнала с ротацией; открытый файл есть только у копии в потоке записи.
+struct RecordFile {
+    dir: PathBuf,
+    name: String,
+    max_bytes: u64,
+    max_files: usize,
+    file: Option<(File, u64)>,
+}
+
+impl Clone for RecordFile {
+    fn clone(&self) -> Self {
+        Self { dir: self.dir.clone(), name: self.name.clone(), max_bytes: self.max_bytes, max_files: self.max_files, file: None }
+    }
+}
+
+impl RecordFile {
+    fn archive_path(&self, n: usize) -> PathBuf {
+        match n {
+            0 => self.dir.join(format!("{}.jsonl", self.name)),
+            n => self.dir.join(format!("{}.{n}.jsonl", self.name)),
+        }
+    }
+
+    fn append(&mut self, record: &PriceRecord) -> std::io::Result<()> {
+        let mut line = serde_json::to_string(record)?;
+        line.push('\n');
+        if self.file.as_ref().is_some_and(|(_, size)| *size > 0 && size + line.len() as u64 > self.max_bytes) {
+            self.file = None;
+            self.rotate()?;
+        }
+        if self.file.is_none() {
+            let opened = OpenOptions::new().create(true).append(true).open(self.archive_path(0))?;
+            let size = opened.metadata()?.len();
+            self.file = Some((opened, size));
+        }
+        let (file, size) = self.file.as_mut().expect("opened above");
+        file.write_all(line.as_bytes())?;
+        *size += line.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&self) -> std::io::Result<()> {
+        if self.max_files == 0 {
+            return std::fs::remove_file(self.archive_path(0));
+        }
+        let _ = std::fs::remove_file(self.archive_path(self.max_files));
+        for n in (0..self.max_files).rev() {
+            let from = self.archive_path(n);
+            if from.exists() {
+                std::fs::rename(&from, self.archive_path(n + 1))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Записывает каждый запрос к `inner` — цену или ошибку, объём, задержку и имя источника —
+/// в `PriceRecorder`. Результат `inner` возвращается без изменений.
+pub struct RecordingPriceSource {
+    inner: Arc<dyn PriceSource>,
+    source: String,
+    recorder: Arc<PriceRecorder>,
+}
+
+impl RecordingPriceSource {
+    pub fn new(inner: Arc<dyn PriceSource>, source: &str, recorder: Arc<PriceRecorder>) -> Self {
+        Self { inner, source: source.to_string(), recorder }
+    }
+
+    fn record(&self, from: &str, to: &str, amount: Option<&str>, result: &Result<f64, PriceError>, started: (u64, Instant)) {
+        self.recorder.record(self.entry(from, to, amount, result, started));
+    }
+
+    fn entry(&self, from: &str, to: &str, amount: Option<&str>, result: &Result<f64, PriceError>, started: (u64, Instant)) -> PriceRecord {
+        let (timestamp_ms, instant) = started;
+        PriceRecord {
+            timestamp_ms,
+            from: from.to_string(),
+            to: to.to_string(),
+            price: result.as_ref().ok().copied(),
+            error: result.as_ref().err().map(ToString::to_string),
+            amount: amount.map(str::to_string),
+            latency_ms: Some(instant.elapsed().as_millis() as u64),
+            source: Some(self.source.clone()),
+            amount_out: None,
+            amount_in: None,
+        }
+    }
+}
+
+#[async_trait]
+impl PriceSource for RecordingPriceSource {
+    async fn get_price(&self, from: &str, to: &str, amount: Option<&str>) -> Result<f64, PriceError> {
+        Ok(self.get_price_timed(from, to, amount).await?.price)
+    }
+
+    /// Время записи — начало запроса: replay на этот момент вернёт тот же ответ.
+    async fn get_price_timed(&self, from: &str, to: &str, amount: Option<&str>) -> Result<TimedPrice, PriceError> {
+        let started = (now_ms(), Instant::now());
+        let result = self.inner.get_price_timed(from, to, amount).await;
+        self.record(from, to, amount, &result.as_ref().map(|t| t.price).map_err(Clone::clone), started);
+        result
+    }
+
+    async fn get_price_with_path(&self, from: &str, to: &str, amount: Option<&str>) -> Result<TriangulatedPrice, PriceError> {
+        let started = (now_ms(), Instant::now());
+        let result = self.inner.get_price_with_path(from, to, amount).await;
+        self.record(from, to, amount, &result.as_ref().map(|p| p.price).map_err(Clone::clone), started);
+        result
+    }
+
+    /// Записывается с `amount_out` и входом по кривой; replay такие записи пропускает.
+    async fn get_price_exact_out(&self, from: &str, to: &str, amount_out: &str) -> Result<ExactOutPrice, PriceError> {
+        let started = (now_ms(), Instant::now());
+        let result = self.inner.get_price_exact_out(from, to, amount_out).await;
+        let mut entry = self.entry(from, to, None, &result.as_ref().map(|q| q.price).map_err(Clone::clone), started);
+        entry.amount_out = Some(amount_out.to_string());
+        entry.amount_in = result.as_ref().ok().and_then(|q| q.amount_in.clone());
+        self.recorder.record(entry);
+        result
+    }
+
+    /// Пакет уходит в `inner` целиком; у всех записей общая задержка пакета.
+    async fn get_prices(&self, pairs: &[(&str, &str)]) -> Vec<Result<f64, PriceError>> {
+        let started = (now_ms(), Instant::now());
+        let prices = self.inner.get_prices(pairs).await;
+        for ((from, to), result) in pairs.iter().zip(&prices) {
+            self.record(from, to, None, result, started);
+        }
+        prices
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::price_source::{MockPriceSource, ReplayPriceSource};
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("price_records_{}", uuid::Uuid::new_v4()))
+    }
+
+    #[tokio::test]
+    async fn test_recorded_lookups_replay_identically() {
+        let dir = temp_dir();
+        let recorder = Arc::new(PriceRecorder::new(&dir, "prices").unwrap());
+        let source = RecordingPriceSource::new(Arc::new(MockPriceSource), "mock", recorder.clone());
+        assert_eq!(source.get_price("ETH", "USDT", Some("2.5")).await.unwrap(), 3200.0);
+        assert!(source.get_price("DOGE", "USDT", None).await.is_err());
+        let batch = source.get_prices(&[("WBTC", "USDT"), ("ETH", "BNB")]).await;
+        assert_eq!(batch[0], Ok(67000.0));
+
+        recorder.flush();
+        let raw = std::fs::read_to_string(recorder.path()).unwrap();
+        let records: Vec<PriceRecord> = raw.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(records.len(), 4);
+        assert_eq!((records[0].amount.as_deref(), records[0].price), (Some("2.5"), Some(3200.0)));
+        assert_eq!(records[1].error.as_deref(), Some("No price for DOGE/USDT"));
+        assert!(records.iter().all(|r| r.source.as_deref() == Some("mock") && r.latency_ms.is_some()));
+
+        // Журнал читается replay-источником: те же цены и те же ошибки, цена на объём — для того же объёма
+        let replay = ReplayPriceSource::load(recorder.path()).unwrap();
+        replay.clock().set(now_ms());
+        assert_eq!(replay.get_price("ETH", "USDT", Some("2.50")).await.unwrap(), 3200.0);
+        assert!(replay.get_price("ETH", "USDT", None).await.is_err());
+        assert_eq!(replay.get_price("WBTC", "USDT", Some("1")).await.unwrap(), 67000.0);
+        assert_eq!(replay.get_price("DOGE", "USDT", None).await.unwrap_err(), PriceError::no_price("DOGE", "USDT"));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_exact_out_recorded_for_audit() {
+        use crate::amm::{parse_units, stableswap::StableSwapPool};
+        use crate::price_source::StableSwapPriceSource;
+
+        let dir = temp_dir();
+        let recorder = Arc::new(PriceRecorder::new(&dir, "prices").unwrap());
+        let pool = StableSwapPool::new(
+            vec![parse_units("1000000", 6).unwrap(), parse_units("1000000", 6).unwrap()],
+            vec![6, 6],
+            200,
+            4_000_000,
+        ).unwrap();
+        let curve = StableSwapPriceSource::new(&["USDT", "USDC"], pool).unwrap();
+        let source = RecordingPriceSource::new(Arc::new(curve), "curve", recorder.clone());
+        let quote = source.get_price_exact_out("USDT", "USDC", "1000").await.unwrap();
+        assert!(source.get_price_exact_out("USDT", "DAI", "1000").await.is_err());
+
+        recorder.flush();
+        let raw = std::fs::read_to_string(recorder.path()).unwrap();
+        let records: Vec<PriceRecord> = raw.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].amount_out.as_deref(), Some("1000"));
+        assert!((records[0].price.unwrap() - quote.price).abs() < 1e-12);
+        assert_eq!(records[0].amount_in, quote.amount_in);
+        assert_eq!(records[0].amount, None);
+        assert_eq!((records[1].amount_out.as_deref(), records[1].amount_in.as_deref()), (Some("1000"), None));
+        assert!(records[1].error.is_some());
+
+        // Replay воспроизводит цены по входу: записи exact-out пропускаются
+        let replay = ReplayPriceSource::load(recorder.path()).unwrap();
+        replay.clock().set(now_ms());
+        assert!(replay.get_price("USDT", "USDC", None).await.is_err());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_rotation_keeps_max_files() {
+        let dir = temp_dir();
+        let recorder = Arc::new(PriceRecorder::new(&dir, "prices").unwrap().with_rotation(300, 2));
+        let source = RecordingPriceSource::new(Arc::new(MockPriceSource), "mock", recorder.clone());
+        for _ in 0..20 {
+            source.get_price("ETH", "USDT", None).await.unwrap();
+        }
+        recorder.flush();
+        let mut files: Vec<String> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        files.sort();
+        assert_eq!(files, vec!["prices.1.jsonl", "prices.2.jsonl", "prices.jsonl"]);
+        for file in &files {
+            let size = std::fs::metadata(dir.join(file)).unwrap().len();
+            assert!(size <= 300, "{file}: {size}");
+            assert!(ReplayPriceSource::load(dir.join(file)).is_ok());
+        }
+        assert_eq!(recorder.write_errors(), 0);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_write_errors_counted() {
+        let dir = temp_dir();
+        let recorder = Arc::new(PriceRecorder::new(&dir, "prices").unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+        let source = RecordingPriceSource::new(Arc::new(MockPriceSource), "mock", recorder.clone());
+        // Цена отдаётся, хотя записать её некуда
+        assert_eq!(source.get_price("ETH", "USDT", None).await.unwrap(), 3200.0);
+        recorder.flush();
+        assert_eq!(recorder.write_errors(), 1);
+        assert!(recorder.last_write_error().unwrap().starts_with("Price record not written to"));
+    }
+}
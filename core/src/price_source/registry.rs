@@ -0,0 +1,70 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use super::PriceSource;
+
+/// Именованные источники цен и источник по умолчанию. Вызывающий выбирает источник
+/// по имени (`?source=uniswap`), не зная его конкретного типа.
+#[derive(Clone)]
+pub struct PriceSourceRegistry {
+    sources: BTreeMap<String, Arc<dyn PriceSource>>,
+    default: String,
+}
+
+impl PriceSourceRegistry {
+    pub fn new(default: &str, source: Arc<dyn PriceSource>) -> Self {
+        Self { sources: BTreeMap::from([(default.to_string(), source)]), default: default.to_string() }
+    }
+
+    /// Добавляет (или заменяет) источник `name`.
+    pub fn with_source(mut self, name: &str, source: Arc<dyn PriceSource>) -> Self {
+        self.sources.insert(name.to_string(), source);
+        self
+    }
+
+    pub fn with_default(mut self, name: &str) -> Result<Self, String> {
+        if !self.sources.contains_key(name) {
+            return Err(format!("Unknown price source: {name}"));
+        }
+        self.default = name.to_string();
+        Ok(self)
+    }
+
+    /// Источник `name` или, без имени, источник по умолчанию.
+    pub fn get(&self, name: Option<&str>) -> Result<Arc<dyn PriceSource>, String> {
+        let name = name.unwrap_or(&self.default);
+        self.sources.get(name).cloned().ok_or_else(|| format!("Unknown price source: {name}"))
+    }
+
+    pub fn default_source(&self) -> Arc<dyn PriceSource> {
+        self.sources[&self.default].clone()
+    }
+
+    pub fn default_name(&self) -> &str {
+        &self.default
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.sources.keys().map(String::as_str).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::price_source::{MockPriceSource, StaticPriceSource};
+
+    #[tokio::test]
+    async fn test_select_by_name() {
+        let registry = PriceSourceRegistry::new("mock", Arc::new(MockPriceSource))
+            .with_source("static", Arc::new(StaticPriceSource::new().with_pair("ETH", "USDT", 3000.0)));
+        assert_eq!(registry.names(), vec!["mock", "static"]);
+        assert_eq!(registry.get(None).unwrap().get_price("ETH", "USDT", None).await.unwrap(), 3200.0);
+        assert_eq!(registry.get(Some("static")).unwrap().get_price("ETH", "USDT", None).await.unwrap(), 3000.0);
+        assert_eq!(registry.get(Some("uniswap")).err().unwrap(), "Unknown price source: uniswap");
+
+        let registry = registry.with_default("static").unwrap();
+        assert_eq!(registry.default_name(), "static");
+        assert!(registry.with_default("uniswap").is_err());
+    }
+}
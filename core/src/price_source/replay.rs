@@ -0,0 +1,279 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::{PriceError, PriceSource, TimedPrice};
+
+/// Строка истории цен: JSONL-объект или CSV `timestamp_ms,from,to,price`.
+/// Записи `RecordingPriceSource` дополнительно несут ошибку и сведения о запросе.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PriceRecord {
+    pub timestamp_ms: u64,
+    pub from: String,
+    pub to: String,
+    /// `None` — запрос завершился ошибкой `error`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub price: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amount: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// Котировка exact-out: точный выход. Replay такие записи пропускает
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amount_out: Option<String>,
+    /// Вход exact-out по кривой пула
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amount_in: Option<String>,
+}
+
+impl PriceRecord {
+    pub fn new(timestamp_ms: u64, from: &str, to: &str, price: f64) -> Self {
+        Self {
+            timestamp_ms,
+            from: from.to_string(),
+            to: to.to_string(),
+            price: Some(price),
+            error: None,
+            amount: None,
+            latency_ms: None,
+            source: None,
+            amount_out: None,
+            amount_in: None,
+        }
+    }
+
+    /// Записанная ошибка воспроизводится как `NoPrice`: повтор в replay даст тот же ответ.
+    fn result(self) -> Result<f64, PriceError> {
+        match (self.price, self.error) {
+            (Some(price), _) => Ok(price),
+            (None, Some(error)) => Err(PriceError::NoPrice(error)),
+            (None, None) => Err(PriceError::NoPrice(format!("Price record for {}/{} has neither price nor error", self.from, self.to))),
+        }
+    }
+}
+
+/// Управляемое время воспроизведения (unix time, миллисекунды).
+/// По умолчанию стоит на месте и двигается только `set`/`advance`;
+/// со скоростью `speed` > 0 идёт в `speed` раз быстрее реального.
+#[derive(Debug)]
+pub struct SimClock {
+    state: Mutex<ClockState>,
+}
+
+#[derive(Debug)]
+struct ClockState {
+    at_anchor_ms: u64,
+    anchor: Instant,
+    speed: f64,
+}
+
+impl ClockState {
+    fn now_ms(&self) -> u64 {
+        self.at_anchor_ms + (self.anchor.elapsed().as_millis() as f64 * self.speed) as u64
+    }
+}
+
+impl SimClock {
+    pub fn new(start_ms: u64) -> Self {
+        Self { state: Mutex::new(ClockState { at_anchor_ms: start_ms, anchor: Instant::now(), speed: 0.0 }) }
+    }
+
+    pub fn now_ms(&self) -> u64 {
+        self.state.lock().unwrap().now_ms()
+    }
+
+    pub fn set(&self, now_ms: u64) {
+        let mut state = self.state.lock().unwrap();
+        state.at_anchor_ms = now_ms;
+        state.anchor = Instant::now();
+    }
+
+    pub fn advance(&self, by: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.at_anchor_ms = state.now_ms() + by.as_millis() as u64;
+        state.anchor = Instant::now();
+    }
+
+    /// 0 — остановить, 1 — реальное время, 60 — минута истории за секунду.
+    pub fn set_speed(&self, speed: f64) {
+        let mut state = self.state.lock().unwrap();
+        state.at_anchor_ms = state.now_ms();
+        state.anchor = Instant::now();
+        state.speed = speed.max(0.0);
+    }
+}
+
+/// Записи пары по возрастанию времени.
+type Series = Vec<(u64, Result<f64, PriceError>)>;
+
+/// Пара и объём записи (`None` — цена пары без объёма).
+type SeriesKey = (String, String, Option<String>);
+
+/// Исторические цены по времени `SimClock`: для пары отдаётся последняя запись
+/// не позже текущего момента часов (записанная ошибка воспроизводится как ошибка).
+/// Записи с `amount` (котировки AMM на объём) воспроизводятся только для того же объёма,
+/// запрос с объёмом без таких записей получает цену пары без объёма. Записи exact-out
+/// (`amount_out`) журнал хранит для аудита, replay их не воспроизводит.
+/// Для бэктестов и детерминированных интеграционных тестов.
+pub struct ReplayPriceSource {
+    prices: HashMap<SeriesKey, Series>,
+    clock: Arc<SimClock>,
+}
+
+impl ReplayPriceSource {
+    /// Часы стоят на первой записи.
+    pub fn new(records: Vec<PriceRecord>) -> Self {
+        let mut prices: HashMap<SeriesKey, Series> = HashMap::new();
+        for record in records.into_iter().filter(|r| r.amount_out.is_none()) {
+            let key = (record.from.clone(), record.to.clone(), record.amount.as_deref().map(normalize_amount));
+            prices.entry(key).or_default().push((record.timestamp_ms, record.result()));
+        }
+        prices.values_mut().for_each(|series| series.sort_by_key(|(timestamp, _)| *timestamp));
+        let start = prices.values().filter_map(|series| series.first()).map(|(t, _)| *t).min().unwrap_or(0);
+        Self { prices, clock: Arc::new(SimClock::new(start)) }
+    }
+
+    /// `.csv` — CSV с необязательным заголовком, иначе JSONL.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let raw = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read price history {}: {e}", path.display()))?;
+        let is_csv = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+        let mut records = Vec::new();
+        for (i, line) in raw.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            let record = if is_csv {
+                // Заголовок — первая строка, у которой время не число
+                if i == 0 && line.split(',').next().is_some_and(|t| t.trim().parse::<u64>().is_err()) {
+                    continue;
+                }
+                parse_csv_record(line)
+            } else {
+                serde_json::from_str(line).map_err(|e| e.to_string())
+            };
+            records.push(record.map_err(|e| format!("Invalid price record {}:{}: {e}", path.display(), i + 1))?);
+        }
+        Ok(Self::new(records))
+    }
+
+    /// Общие часы, например одни на несколько источников.
+    pub fn with_clock(mut self, clock: Arc<SimClock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn clock(&self) -> Arc<SimClock> {
+        self.clock.clone()
+    }
+
+    /// Первая и последняя отметки времени истории.
+    pub fn time_range(&self) -> Option<(u64, u64)> {
+        let first = self.prices.values().filter_map(|s| s.first()).map(|(t, _)| *t).min()?;
+        let last = self.prices.values().filter_map(|s| s.last()).map(|(t, _)| *t).max()?;
+        Some((first, last))
+    }
+}
+
+/// "2.5" и "2.50" — один объём; нечисловой объём сравнивается как строка.
+fn normalize_amount(amount: &str) -> String {
+    match amount.trim().parse::<rust_decimal::Decimal>() {
+        Ok(amount) => amount.normalize().to_string(),
+        Err(_) => amount.trim().to_string(),
+    }
+}
+
+fn parse_csv_record(line: &str) -> Result<PriceRecord, String> {
+    let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+    let [timestamp_ms, from, to, price] = fields[..] else {
+        return Err(format!("expected 4 columns timestamp_ms,from,to,price, got {}", fields.len()));
+    };
+    Ok(PriceRecord::new(
+        timestamp_ms.parse().map_err(|_| format!("Invalid timestamp {timestamp_ms}"))?,
+        from,
+        to,
+        price.parse().map_err(|_| format!("Invalid price {price}"))?,
+    ))
+}
+
+#[async_trait]
+impl PriceSource for ReplayPriceSource {
+    async fn get_price(&self, from: &str, to: &str, amount: Option<&str>) -> Result<f64, PriceError> {
+        Ok(self.get_price_timed(from, to, amount).await?.price)
+    }
+
+    /// Время цены — отметка записи, а не часов.
+    async fn get_price_timed(&self, from: &str, to: &str, amount: Option<&str>) -> Result<TimedPrice, PriceError> {
+        let now = self.clock.now_ms();
+        let series = |amount: Option<String>| self.prices.get(&(from.to_string(), to.to_string(), amount));
+        let series = amount
+            .and_then(|amount| series(Some(normalize_amount(amount))))
+            .or_else(|| series(None))
+            .ok_or_else(|| PriceError::no_price(from, to))?;
+        let seen = series.partition_point(|(timestamp, _)| *timestamp <= now);
+        let (timestamp_ms, price) = seen
+            .checked_sub(1)
+            .map(|i| &series[i])
+            .ok_or_else(|| PriceError::NoPrice(format!("No price for {from}/{to} at {now}")))?;
+        Ok(TimedPrice { price: price.clone()?, timestamp_ms: *timestamp_ms })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file(ext: &str, content: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("prices_{}.{ext}", uuid::Uuid::new_v4()));
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn test_replay_follows_clock() {
+        let path = temp_file("csv", "timestamp_ms,from,to,price\n1000,ETH,USDT,3000\n3000,ETH,USDT,3100\n2000,WBTC,USDT,67000\n");
+        let replay = ReplayPriceSource::load(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(replay.time_range(), Some((1000, 3000)));
+
+        let clock = replay.clock();
+        assert_eq!(replay.get_price_timed("ETH", "USDT", None).await.unwrap(), TimedPrice { price: 3000.0, timestamp_ms: 1000 });
+        assert_eq!(replay.get_price("WBTC", "USDT", None).await.unwrap_err().to_string(), "No price for WBTC/USDT at 1000");
+
+        clock.advance(Duration::from_millis(1500));
+        assert_eq!(replay.get_price("ETH", "USDT", None).await.unwrap(), 3000.0);
+        assert_eq!(replay.get_price("WBTC", "USDT", None).await.unwrap(), 67000.0);
+        clock.set(3000);
+        assert_eq!(replay.get_price("ETH", "USDT", None).await.unwrap(), 3100.0);
+        // После конца истории держится последняя цена
+        clock.set(1_000_000);
+        assert_eq!(replay.get_price("ETH", "USDT", None).await.unwrap(), 3100.0);
+        assert!(replay.get_price("USDT", "ETH", None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_replay_jsonl_and_running_clock() {
+        let path = temp_file("jsonl", concat!(
+            r#"{"timestamp_ms": 0, "from": "ETH", "to": "USDT", "price": 3000.0}"#, "\n",
+            r#"{"timestamp_ms": 60000, "from": "ETH", "to": "USDT", "price": 3050.0}"#, "\n",
+        ));
+        let replay = ReplayPriceSource::load(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(replay.get_price("ETH", "USDT", None).await.unwrap(), 3000.0);
+
+        // Минута истории за ~10 мс
+        replay.clock().set_speed(10_000.0);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(replay.get_price("ETH", "USDT", None).await.unwrap(), 3050.0);
+
+        let bad = temp_file("csv", "1000,ETH,USDT\n");
+        let err = ReplayPriceSource::load(&bad).err().unwrap();
+        std::fs::remove_file(&bad).ok();
+        assert!(err.contains(":1: expected 4 columns"), "{err}");
+    }
+}
@@ -0,0 +1,441 @@
+use async_trait::async_trait;
+use rand::Rng;
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::{ExactOutPrice, PriceError, PriceSource, TimedPrice};
+
+/// Политика повторов: экспоненциальная задержка с "full jitter" и таймаутом на попытку.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    pub attempt_timeout: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 2,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(2),
+            attempt_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Задержка перед повтором номер `retry` (с нуля): случайная в `[0, min(max, base * 2^retry)]`.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let cap = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_delay);
+        if cap.is_zero() {
+            return cap;
+        }
+        rand::thread_rng().gen_range(Duration::ZERO..=cap)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+impl CircuitState {
+    /// Числовое значение для метрик: 0 — closed, 1 — half-open, 2 — open.
+    pub fn as_gauge(self) -> i64 {
+        match self {
+            CircuitState::Closed => 0,
+            CircuitState::HalfOpen => 1,
+            CircuitState::Open => 2,
+        }
+    }
+}
+
+/// Снимок состояния автомата для health/metrics.
+#[derive(Debug, Clone, Serialize)]
+pub struct CircuitSnapshot {
+    pub name: String,
+    pub state: CircuitState,
+    pub consecutive_failures: u32,
+    pub times_opened: u64,
+}
+
+struct BreakerInner {
+    state: CircuitState,
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    trial_in_flight: bool,
+    times_opened: u64,
+}
+
+/// Circuit breaker: размыкается после `failure_threshold` подряд неудачных вызовов,
+/// через `cooldown` пропускает один пробный вызов (half-open).
+pub struct CircuitBreaker {
+    name: String,
+    failure_threshold: u32,
+    cooldown: Duration,
+    inner: Mutex<BreakerInner>,
+}
+
+impl CircuitBreaker {
+    pub fn new(name: &str, failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            name: name.to_string(),
+            failure_threshold: failure_threshold.max(1),
+            cooldown,
+            inner: Mutex::new(BreakerInner {
+                state: CircuitState::Closed,
+                consecutive_failures: 0,
+                opened_at: None,
+                trial_in_flight: false,
+                times_opened: 0,
+            }),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn state(&self) -> CircuitState {
+        let mut inner = self.inner.lock().unwrap();
+        self.refresh(&mut inner);
+        inner.state
+    }
+
+    pub fn snapshot(&self) -> CircuitSnapshot {
+        let mut inner = self.inner.lock().unwrap();
+        self.refresh(&mut inner);
+        CircuitSnapshot {
+            name: self.name.clone(),
+            state: inner.state,
+            consecutive_failures: inner.consecutive_failures,
+            times_opened: inner.times_opened,
+        }
+    }
+
+    /// Разрешение на вызов или `None`, если цепь разомкнута. В half-open выдаётся ровно
+    /// одно пробное разрешение; исход вызова сообщается через `success`/`failure`.
+    pub fn try_acquire(&self) -> Option<CircuitPermit<'_>> {
+        let mut inner = self.inner.lock().unwrap();
+        self.refresh(&mut inner);
+        let trial = match inner.state {
+            CircuitState::Closed => false,
+            CircuitState::Open => return None,
+            CircuitState::HalfOpen if inner.trial_in_flight => return None,
+            CircuitState::HalfOpen => {
+                inner.trial_in_flight = true;
+                true
+            }
+        };
+        Some(CircuitPermit { breaker: self, trial, settled: false })
+    }
+
+    fn record_success(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.state = CircuitState::Closed;
+        inner.consecutive_failures = 0;
+        inner.opened_at = None;
+        inner.trial_in_flight = false;
+    }
+
+    fn record_failure(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.consecutive_failures = inner.consecutive_failures.saturating_add(1);
+        let trial_failed = inner.state == CircuitState::HalfOpen;
+        if trial_failed || inner.consecutive_failures >= self.failure_threshold {
+            if inner.state != CircuitState::Open {
+                inner.times_opened += 1;
+            }
+            inner.state = CircuitState::Open;
+            inner.opened_at = Some(Instant::now());
+        }
+        inner.trial_in_flight = false;
+    }
+
+    fn refresh(&self, inner: &mut BreakerInner) {
+        if inner.state == CircuitState::Open
+            && inner.opened_at.is_some_and(|t| t.elapsed() >= self.cooldown)
+        {
+            inner.state = CircuitState::HalfOpen;
+            inner.trial_in_flight = false;
+        }
+    }
+}
+
+/// Разрешение `CircuitBreaker::try_acquire` на один вызов. Пробное разрешение, брошенное
+/// без исхода (например, future отменён по таймауту), считается неудачей — иначе цепь
+/// навсегда осталась бы в half-open с занятым пробным вызовом.
+#[must_use = "исход вызова нужно сообщить через success или failure"]
+pub struct CircuitPermit<'a> {
+    breaker: &'a CircuitBreaker,
+    trial: bool,
+    settled: bool,
+}
+
+impl CircuitPermit<'_> {
+    pub fn success(mut self) {
+        self.settled = true;
+        self.breaker.record_success();
+    }
+
+    pub fn failure(mut self) {
+        self.settled = true;
+        self.breaker.record_failure();
+    }
+}
+
+impl Drop for CircuitPermit<'_> {
+    fn drop(&mut self) {
+        if self.trial && !self.settled {
+            self.breaker.record_failure();
+        }
+    }
+}
+
+/// Обёртка над любым `PriceSource`: повторы с backoff и circuit breaker.
+/// Постоянные ошибки (`PriceError::NoPrice`) не повторяются и не размыкают цепь.
+pub struct ResilientPriceSource {
+    inner: Arc<dyn PriceSource>,
+    breaker: Arc<CircuitBreaker>,
+    retry: RetryPolicy,
+    is_transient: fn(&PriceError) -> bool,
+}
+
+impl ResilientPriceSource {
+    pub fn new(inner: Arc<dyn PriceSource>, breaker: Arc<CircuitBreaker>) -> Self {
+        Self {
+            inner,
+            breaker,
+            retry: RetryPolicy::default(),
+            is_transient: PriceError::is_transient,
+        }
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn with_transient_classifier(mut self, is_transient: fn(&PriceError) -> bool) -> Self {
+        self.is_transient = is_transient;
+        self
+    }
+
+    pub fn breaker(&self) -> Arc<CircuitBreaker> {
+        self.breaker.clone()
+    }
+}
+
+#[async_trait]
+impl PriceSource for ResilientPriceSource {
+    async fn get_price(&self, from: &str, to: &str, amount: Option<&str>) -> Result<f64, PriceError> {
+        Ok(self.get_price_timed(from, to, amount).await?.price)
+    }
+
+    async fn get_price_timed(&self, from: &str, to: &str, amount: Option<&str>) -> Result<TimedPrice, PriceError> {
+        self.with_retries(|| self.inner.get_price_timed(from, to, amount)).await
+    }
+
+    async fn get_price_exact_out(&self, from: &str, to: &str, amount_out: &str) -> Result<ExactOutPrice, PriceError> {
+        self.with_retries(|| self.inner.get_price_exact_out(from, to, amount_out)).await
+    }
+
+    /// Пакет уходит во внутренний источник одним вызовом, без повторов:
+    /// отказом считается только ответ, где все пары упали с временной ошибкой.
+    async fn get_prices(&self, pairs: &[(&str, &str)]) -> Vec<Result<f64, PriceError>> {
+        let Some(permit) = self.breaker.try_acquire() else {
+            let error = self.circuit_open();
+            return pairs.iter().map(|_| Err(error.clone())).collect();
+        };
+        match tokio::time::timeout(self.retry.attempt_timeout, self.inner.get_prices(pairs)).await {
+            Ok(results) => {
+                let all_transient = !results.is_empty()
+                    && results.iter().all(|r| matches!(r, Err(e) if (self.is_transient)(e)));
+                if all_transient {
+                    permit.failure();
+                } else {
+                    permit.success();
+                }
+                results
+            }
+            Err(_) => {
+                permit.failure();
+                let error = self.timed_out();
+                pairs.iter().map(|_| Err(error.clone())).collect()
+            }
+        }
+    }
+}
+
+impl ResilientPriceSource {
+    /// Запрос `call` через circuit breaker, с таймаутом попытки и повторами временных ошибок.
+    async fn with_retries<T, F, Fut>(&self, call: F) -> Result<T, PriceError>
+    where
+        F: Fn() -> Fut,
+        Fut: std::future::Future<Output = Result<T, PriceError>>,
+    {
+        let mut retry = 0;
+        loop {
+            let Some(permit) = self.breaker.try_acquire() else {
+                return Err(self.circuit_open());
+            };
+            let error = match tokio::time::timeout(self.retry.attempt_timeout, call()).await {
+                Ok(Ok(value)) => {
+                    permit.success();
+                    return Ok(value);
+                }
+                Ok(Err(e)) if !(self.is_transient)(&e) => {
+                    permit.success();
+                    return Err(e);
+                }
+                Ok(Err(e)) => e,
+                Err(_) => self.timed_out(),
+            };
+            permit.failure();
+            if retry >= self.retry.max_retries {
+                return Err(error);
+            }
+            tokio::time::sleep(self.retry.backoff(retry)).await;
+            retry += 1;
+        }
+    }
+
+    fn circuit_open(&self) -> PriceError {
+        PriceError::Unavailable(format!("Circuit open for price source {}", self.breaker.name()))
+    }
+
+    fn timed_out(&self) -> PriceError {
+        PriceError::Unavailable(format!("timed out after {:?}", self.retry.attempt_timeout))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::price_source::MockPriceSource;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Падает `fail_first` раз, затем отвечает ценой 1.0.
+    struct FlakyPriceSource {
+        calls: AtomicU32,
+        fail_first: u32,
+    }
+
+    #[async_trait]
+    impl PriceSource for FlakyPriceSource {
+        async fn get_price(&self, _from: &str, _to: &str, _amount: Option<&str>) -> Result<f64, PriceError> {
+            let n = self.calls.fetch_add(1, Ordering::SeqCst);
+            if n < self.fail_first {
+                Err(PriceError::Unavailable("connection reset".to_string()))
+            } else {
+                Ok(1.0)
+            }
+        }
+    }
+
+    fn fast_retry(max_retries: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_retries,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(5),
+            attempt_timeout: Duration::from_secs(1),
+        }
+    }
+
+    #[test]
+    fn test_backoff_is_capped() {
+        let policy = fast_retry(3);
+        for retry in 0..10 {
+            assert!(policy.backoff(retry) <= Duration::from_millis(5));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_recovers_transient_failure() {
+        let flaky = Arc::new(FlakyPriceSource { calls: AtomicU32::new(0), fail_first: 2 });
+        let breaker = Arc::new(CircuitBreaker::new("flaky", 5, Duration::from_secs(60)));
+        let source = ResilientPriceSource::new(flaky.clone(), breaker.clone()).with_retry(fast_retry(2));
+        assert_eq!(source.get_price("ETH", "USDT", None).await.unwrap(), 1.0);
+        assert_eq!(flaky.calls.load(Ordering::SeqCst), 3);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[tokio::test]
+    async fn test_circuit_opens_and_half_opens() {
+        let flaky = Arc::new(FlakyPriceSource { calls: AtomicU32::new(0), fail_first: 3 });
+        let breaker = Arc::new(CircuitBreaker::new("flaky", 3, Duration::from_millis(30)));
+        let source = ResilientPriceSource::new(flaky.clone(), breaker.clone()).with_retry(fast_retry(5));
+
+        let err = source.get_price("ETH", "USDT", None).await.unwrap_err();
+        assert!(err.to_string().contains("Circuit open"));
+        assert_eq!(flaky.calls.load(Ordering::SeqCst), 3);
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert_eq!(breaker.snapshot().times_opened, 1);
+
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        assert_eq!(source.get_price("ETH", "USDT", None).await.unwrap(), 1.0);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[test]
+    fn test_half_open_failure_reopens() {
+        let breaker = CircuitBreaker::new("test", 1, Duration::ZERO);
+        breaker.try_acquire().unwrap().failure();
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        let trial = breaker.try_acquire().unwrap();
+        assert!(breaker.try_acquire().is_none());
+        trial.failure();
+        assert_eq!(breaker.snapshot().times_opened, 2);
+    }
+
+    /// Отвечает через `delay`.
+    struct SlowPriceSource {
+        delay: Duration,
+    }
+
+    #[async_trait]
+    impl PriceSource for SlowPriceSource {
+        async fn get_price(&self, _from: &str, _to: &str, _amount: Option<&str>) -> Result<f64, PriceError> {
+            tokio::time::sleep(self.delay).await;
+            Ok(1.0)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_trial_reopens_circuit() {
+        let breaker = Arc::new(CircuitBreaker::new("slow", 1, Duration::from_millis(20)));
+        breaker.try_acquire().unwrap().failure();
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+
+        let slow = Arc::new(SlowPriceSource { delay: Duration::from_secs(1) });
+        let source = ResilientPriceSource::new(slow, breaker.clone()).with_retry(fast_retry(0));
+        // Вызывающий бросает future на середине пробного вызова
+        let cancelled = tokio::time::timeout(Duration::from_millis(10), source.get_price("ETH", "USDT", None)).await;
+        assert!(cancelled.is_err());
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert_eq!(breaker.snapshot().times_opened, 2);
+
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert!(breaker.try_acquire().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_permanent_error_not_retried() {
+        let breaker = Arc::new(CircuitBreaker::new("mock", 1, Duration::from_secs(60)));
+        let source = ResilientPriceSource::new(Arc::new(MockPriceSource), breaker.clone()).with_retry(fast_retry(3));
+        let err = source.get_price("DOGE", "USDT", None).await.unwrap_err();
+        assert_eq!(err, PriceError::no_price("DOGE", "USDT"));
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+}
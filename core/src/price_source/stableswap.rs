@@ -0,0 +1,76 @@
+use super::pool::{PoolModel, PoolPriceSource};
+use crate::amm::{stableswap::StableSwapPool, AmmError, AmmQuote, U256};
+
+/// `PriceSource` поверх снимка Curve-пула: спот — предельная цена по инварианту, объём — `get_dy`, exact-out — `get_dx`.
+pub type StableSwapPriceSource = PoolPriceSource<StableSwapPool>;
+
+impl PoolModel for StableSwapPool {
+    fn decimals(&self) -> &[u8] {
+        &self.decimals
+    }
+
+    fn spot(&self, i: usize, j: usize) -> Result<f64, AmmError> {
+        self.spot_price(i, j)
+    }
+
+    fn quote(&self, i: usize, j: usize, amount_in: U256) -> Result<AmmQuote, AmmError> {
+        StableSwapPool::quote(self, i, j, amount_in)
+    }
+
+    fn quote_exact_out(&self, i: usize, j: usize, amount_out: U256) -> Result<AmmQuote, AmmError> {
+        StableSwapPool::quote_exact_out(self, i, j, amount_out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amm::{format_units, parse_units};
+    use crate::price_source::PriceSource;
+
+    fn pool() -> StableSwapPool {
+        StableSwapPool::new(
+            vec![parse_units("1000000", 6).unwrap(), parse_units("1000000", 6).unwrap()],
+            vec![6, 6],
+            200,
+            4_000_000,
+        ).unwrap()
+    }
+
+    fn source() -> StableSwapPriceSource {
+        StableSwapPriceSource::new(&["USDC", "USDT"], pool()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_stableswap_source_prices() {
+        let source = source();
+        let spot = source.get_price("USDC", "USDT", None).await.unwrap();
+        assert!((spot - 1.0).abs() < 1e-12);
+        let effective = source.get_price("USDC", "USDT", Some("1000")).await.unwrap();
+        assert!(effective < 1.0 && effective > 0.9995);
+        assert!(source.get_price("USDC", "DAI", None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stableswap_source_exact_out() {
+        let source = source();
+        let quote = source.get_price_exact_out("USDC", "USDT", "1000").await.unwrap();
+        let dx = pool().get_dx(0, 1, parse_units("1000", 6).unwrap()).unwrap();
+        assert_eq!(quote.amount_in, Some(format_units(dx, 6)));
+        assert!(quote.price < 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_stableswap_source_update_pool() {
+        let source = source();
+        let skewed = StableSwapPool::new(
+            vec![parse_units("100000", 6).unwrap(), parse_units("1900000", 6).unwrap()],
+            vec![6, 6],
+            200,
+            4_000_000,
+        ).unwrap();
+        source.update_pool(skewed).unwrap();
+        // USDT в избытке: за USDC дают больше одного USDT
+        assert!(source.get_price("USDC", "USDT", None).await.unwrap() > 1.0);
+    }
+}
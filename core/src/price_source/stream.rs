@@ -0,0 +1,430 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::future::Future;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
+
+use super::{now_ms, PriceSource, RetryPolicy};
+
+/// Размер канала подписки по умолчанию.
+pub const DEFAULT_STREAM_BUFFER: usize = 64;
+
+/// Новая цена пары `from/to` (в единицах `to` за единицу `from`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PriceUpdate {
+    pub from: String,
+    pub to: String,
+    pub price: f64,
+    pub timestamp_ms: u64,
+}
+
+impl PriceUpdate {
+    pub fn new(from: &str, to: &str, price: f64, timestamp_ms: u64) -> Self {
+        Self { from: from.to_string(), to: to.to_string(), price, timestamp_ms }
+    }
+}
+
+/// Активная подписка: ограниченный канал обновлений и задача, которая его наполняет.
+/// Пока потребитель не читает, задача ждёт на `send` — источник не опрашивается впрок.
+/// Drop подписки останавливает задачу.
+pub struct PriceSubscription {
+    updates: mpsc::Receiver<PriceUpdate>,
+    task: JoinHandle<()>,
+}
+
+impl PriceSubscription {
+    /// Запускает `producer` с отправляющей стороной канала размера `buffer`.
+    pub fn spawn<F, Fut>(buffer: usize, producer: F) -> Self
+    where
+        F: FnOnce(mpsc::Sender<PriceUpdate>) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let (tx, updates) = mpsc::channel(buffer.max(1));
+        Self { updates, task: tokio::spawn(producer(tx)) }
+    }
+
+    /// Следующее обновление; `None` — поток закончился (соединение закрыто).
+    pub async fn recv(&mut self) -> Option<PriceUpdate> {
+        self.updates.recv().await
+    }
+}
+
+impl Drop for PriceSubscription {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Подписка на изменения цен. REST-источники реализуют её опросом
+/// (`PollingPriceStream`), WebSocket-источники — нативно.
+#[async_trait]
+pub trait PriceStream: Send + Sync + 'static {
+    /// Обновления для `pairs`. Ошибка — только если подписку не удалось открыть;
+    /// обрыв после открытия закрывает канал (см. `ReconnectingPriceStream`).
+    async fn subscribe(&self, pairs: &[(&str, &str)]) -> Result<PriceSubscription, String>;
+}
+
+fn owned_pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+    pairs.iter().map(|(from, to)| (from.to_string(), to.to_string())).collect()
+}
+
+/// Поток поверх обычного `PriceSource`: `get_prices` раз в `interval`,
+/// в канал уходят только изменившиеся цены. Ошибки пары пропускаются до следующего опроса.
+pub struct PollingPriceStream {
+    source: Arc<dyn PriceSource>,
+    interval: Duration,
+    buffer: usize,
+}
+
+impl PollingPriceStream {
+    pub fn new(source: Arc<dyn PriceSource>, interval: Duration) -> Self {
+        Self { source, interval, buffer: DEFAULT_STREAM_BUFFER }
+    }
+
+    pub fn with_buffer(mut self, buffer: usize) -> Self {
+        self.buffer = buffer;
+        self
+    }
+}
+
+#[async_trait]
+impl PriceStream for PollingPriceStream {
+    async fn subscribe(&self, pairs: &[(&str, &str)]) -> Result<PriceSubscription, String> {
+        let source = self.source.clone();
+        let interval = self.interval;
+        let pairs = owned_pairs(pairs);
+        Ok(PriceSubscription::spawn(self.buffer, move |tx| async move {
+            let mut ticker = tokio::time::interval(interval);
+            // Медленный потребитель не накапливает пропущенные опросы
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            let mut last: HashMap<(String, String), f64> = HashMap::new();
+            loop {
+                ticker.tick().await;
+                let refs: Vec<(&str, &str)> = pairs.iter().map(|(f, t)| (f.as_str(), t.as_str())).collect();
+                let prices = source.get_prices(&refs).await;
+                for ((from, to), price) in pairs.iter().zip(prices) {
+                    let Ok(price) = price else { continue };
+                    if last.insert((from.clone(), to.clone()), price) == Some(price) {
+                        continue;
+                    }
+                    if tx.send(PriceUpdate::new(from, to, price, now_ms())).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        }))
+    }
+}
+
+/// Переподписывается на `inner` при обрыве с задержкой `RetryPolicy::backoff`;
+/// счётчик попыток сбрасывается после первого обновления нового соединения.
+/// Первая подписка открывается сразу, её ошибка возвращается вызывающему.
+pub struct ReconnectingPriceStream {
+    inner: Arc<dyn PriceStream>,
+    retry: RetryPolicy,
+    buffer: usize,
+}
+
+impl ReconnectingPriceStream {
+    pub fn new(inner: Arc<dyn PriceStream>) -> Self {
+        Self { inner, retry: RetryPolicy::default(), buffer: DEFAULT_STREAM_BUFFER }
+    }
+
+    /// Используются только задержки политики: переподключения не ограничены числом.
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn with_buffer(mut self, buffer: usize) -> Self {
+        self.buffer = buffer;
+        self
+    }
+}
+
+#[async_trait]
+impl PriceStream for ReconnectingPriceStream {
+    async fn subscribe(&self, pairs: &[(&str, &str)]) -> Result<PriceSubscription, String> {
+        let mut current = self.inner.subscribe(pairs).await?;
+        let inner = self.inner.clone();
+        let retry = self.retry.clone();
+        let pairs = owned_pairs(pairs);
+        Ok(PriceSubscription::spawn(self.buffer, move |tx| async move {
+            let mut attempt = 0;
+            loop {
+                while let Some(update) = current.recv().await {
+                    attempt = 0;
+                    if tx.send(update).await.is_err() {
+                        return;
+                    }
+                }
+                let refs: Vec<(&str, &str)> = pairs.iter().map(|(f, t)| (f.as_str(), t.as_str())).collect();
+                current = loop {
+                    if tx.is_closed() {
+                        return;
+                    }
+                    tokio::time::sleep(retry.backoff(attempt)).await;
+                    attempt = attempt.saturating_add(1);
+                    if let Ok(subscription) = inner.subscribe(&refs).await {
+                        break subscription;
+                    }
+                };
+            }
+        }))
+    }
+}
+
+/// Последние цены из подписок и рассылка изменений. Производитель никогда не ждёт
+/// подписчиков: отставший получатель теряет старые события (`RecvError::Lagged`)
+/// и догоняет по `latest`.
+pub struct PriceBoard {
+    latest: RwLock<HashMap<(String, String), PriceUpdate>>,
+    changes: broadcast::Sender<PriceUpdate>,
+}
+
+impl Default for PriceBoard {
+    fn default() -> Self {
+        Self::new(256)
+    }
+}
+
+impl PriceBoard {
+    /// `capacity` — сколько изменений хранится для отстающих подписчиков.
+    pub fn new(capacity: usize) -> Self {
+        Self { latest: RwLock::new(HashMap::new()), changes: broadcast::channel(capacity.max(1)).0 }
+    }
+
+    pub fn publish(&self, update: PriceUpdate) {
+        self.latest
+            .write()
+            .unwrap()
+            .insert((update.from.clone(), update.to.clone()), update.clone());
+        let _ = self.changes.send(update);
+    }
+
+    pub fn latest(&self, from: &str, to: &str) -> Option<PriceUpdate> {
+        self.latest.read().unwrap().get(&(from.to_string(), to.to_string())).cloned()
+    }
+
+    pub fn snapshot(&self) -> Vec<PriceUpdate> {
+        self.latest.read().unwrap().values().cloned().collect()
+    }
+
+    pub fn changes(&self) -> broadcast::Receiver<PriceUpdate> {
+        self.changes.subscribe()
+    }
+
+    /// Изменения цен `pairs` начиная с текущих значений.
+    pub fn watch(self: &Arc<Self>, pairs: &[(&str, &str)]) -> PriceWatch {
+        let pairs: HashSet<(String, String)> = owned_pairs(pairs).into_iter().collect();
+        let changes = self.changes();
+        let pending = pairs.iter().filter_map(|(from, to)| self.latest(from, to)).collect();
+        PriceWatch { board: Arc::clone(self), changes, pairs, pending }
+    }
+
+    /// Переносит обновления подписки на доску, пока подписка не закончится.
+    pub fn follow(self: &Arc<Self>, mut subscription: PriceSubscription) -> JoinHandle<()> {
+        let board = Arc::clone(self);
+        tokio::spawn(async move {
+            while let Some(update) = subscription.recv().await {
+                board.publish(update);
+            }
+        })
+    }
+
+    /// Ждёт цену `from/to`, удовлетворяющую `condition` (например, срабатывание стоп-ордера).
+    /// Текущая цена проверяется сразу.
+    pub async fn wait_for(&self, from: &str, to: &str, condition: impl Fn(f64) -> bool) -> PriceUpdate {
+        let mut changes = self.changes();
+        let matches = |u: &PriceUpdate| u.from == from && u.to == to && condition(u.price);
+        if let Some(update) = self.latest(from, to).filter(|u| matches(u)) {
+            return update;
+        }
+        loop {
+            match changes.recv().await {
+                Ok(update) if matches(&update) => return update,
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Lagged(_)) => {
+                    if let Some(update) = self.latest(from, to).filter(|u| matches(u)) {
+                        return update;
+                    }
+                }
+                // Отправитель живёт вместе с доской
+                Err(broadcast::error::RecvError::Closed) => unreachable!("board outlives its receivers"),
+            }
+        }
+    }
+}
+
+/// Наблюдение за частью пар доски. Отставший наблюдатель не получает
+/// пропущенные промежуточные цены, а сразу переходит к последним.
+pub struct PriceWatch {
+    board: Arc<PriceBoard>,
+    changes: broadcast::Receiver<PriceUpdate>,
+    pairs: HashSet<(String, String)>,
+    pending: VecDeque<PriceUpdate>,
+}
+
+impl PriceWatch {
+    pub async fn next(&mut self) -> Option<PriceUpdate> {
+        loop {
+            if let Some(update) = self.pending.pop_front() {
+                return Some(update);
+            }
+            match self.changes.recv().await {
+                Ok(update) if self.pairs.contains(&(update.from.clone(), update.to.clone())) => return Some(update),
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Lagged(_)) => {
+                    self.pending = self.pairs.iter().filter_map(|(from, to)| self.board.latest(from, to)).collect();
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::price_source::PriceError;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    /// Цена задаётся вручную, опросы считаются.
+    struct Ticker {
+        price: Mutex<f64>,
+        polls: AtomicUsize,
+    }
+
+    impl Ticker {
+        fn new(price: f64) -> Arc<Self> {
+            Arc::new(Self { price: Mutex::new(price), polls: AtomicUsize::new(0) })
+        }
+    }
+
+    #[async_trait]
+    impl PriceSource for Ticker {
+        async fn get_price(&self, from: &str, _to: &str, _amount: Option<&str>) -> Result<f64, PriceError> {
+            self.polls.fetch_add(1, Ordering::SeqCst);
+            match from {
+                "ETH" => Ok(*self.price.lock().unwrap()),
+                _ => Err(PriceError::NoPrice(format!("No price for {from}"))),
+            }
+        }
+    }
+
+    const TICK: Duration = Duration::from_millis(2);
+    const WAIT: Duration = Duration::from_secs(2);
+
+    #[tokio::test]
+    async fn test_polling_sends_only_changes() {
+        let ticker = Ticker::new(2000.0);
+        let stream = PollingPriceStream::new(ticker.clone(), TICK);
+        let mut subscription = stream.subscribe(&[("ETH", "USDT"), ("XYZ", "USDT")]).await.unwrap();
+        let first = subscription.recv().await.unwrap();
+        assert_eq!((first.from.as_str(), first.to.as_str(), first.price), ("ETH", "USDT", 2000.0));
+
+        // Цена не менялась несколько опросов — тишина
+        assert!(tokio::time::timeout(Duration::from_millis(30), subscription.recv()).await.is_err());
+        *ticker.price.lock().unwrap() = 2100.0;
+        let second = tokio::time::timeout(WAIT, subscription.recv()).await.unwrap().unwrap();
+        assert_eq!(second.price, 2100.0);
+    }
+
+    /// Каждый опрос даёт новую цену.
+    struct Rising(AtomicUsize);
+
+    #[async_trait]
+    impl PriceSource for Rising {
+        async fn get_price(&self, _from: &str, _to: &str, _amount: Option<&str>) -> Result<f64, PriceError> {
+            Ok(self.0.fetch_add(1, Ordering::SeqCst) as f64)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_polling_backpressure_and_stop_on_drop() {
+        let source = Arc::new(Rising(AtomicUsize::new(0)));
+        let stream = PollingPriceStream::new(source.clone(), TICK).with_buffer(1);
+        let subscription = stream.subscribe(&[("ETH", "USDT")]).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        // Один ответ в канале, второй ждёт места: дальше источник не опрашивается
+        assert_eq!(source.0.load(Ordering::SeqCst), 2);
+
+        drop(subscription);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(source.0.load(Ordering::SeqCst), 2);
+    }
+
+    /// Каждое соединение отдаёт одно обновление и обрывается; нечётные подключения падают.
+    struct Flaky {
+        connects: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl PriceStream for Flaky {
+        async fn subscribe(&self, pairs: &[(&str, &str)]) -> Result<PriceSubscription, String> {
+            let n = self.connects.fetch_add(1, Ordering::SeqCst);
+            if n % 2 == 1 {
+                return Err("connection refused".to_string());
+            }
+            let (from, to) = (pairs[0].0.to_string(), pairs[0].1.to_string());
+            Ok(PriceSubscription::spawn(1, move |tx| async move {
+                let _ = tx.send(PriceUpdate::new(&from, &to, n as f64, now_ms())).await;
+            }))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_after_disconnect() {
+        let flaky = Arc::new(Flaky { connects: AtomicUsize::new(0) });
+        let retry = RetryPolicy { base_delay: Duration::from_millis(1), max_delay: Duration::from_millis(5), ..RetryPolicy::default() };
+        let stream = ReconnectingPriceStream::new(flaky.clone()).with_retry(retry);
+        let mut subscription = stream.subscribe(&[("ETH", "USDT")]).await.unwrap();
+        let mut prices = Vec::new();
+        for _ in 0..3 {
+            prices.push(tokio::time::timeout(WAIT, subscription.recv()).await.unwrap().unwrap().price);
+        }
+        assert_eq!(prices, vec![0.0, 2.0, 4.0]);
+    }
+
+    #[tokio::test]
+    async fn test_board_wait_for_trigger() {
+        let ticker = Ticker::new(2000.0);
+        let board = Arc::new(PriceBoard::default());
+        let subscription = PollingPriceStream::new(ticker.clone(), TICK).subscribe(&[("ETH", "USDT")]).await.unwrap();
+        let pump = board.follow(subscription);
+
+        let stop = {
+            let board = board.clone();
+            tokio::spawn(async move { board.wait_for("ETH", "USDT", |p| p <= 1800.0).await })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(board.latest("ETH", "USDT").unwrap().price, 2000.0);
+        assert!(!stop.is_finished());
+
+        *ticker.price.lock().unwrap() = 1750.0;
+        let triggered = tokio::time::timeout(WAIT, stop).await.unwrap().unwrap();
+        assert_eq!(triggered.price, 1750.0);
+        // Условие уже выполнено — ответ без ожидания
+        assert_eq!(board.wait_for("ETH", "USDT", |p| p < 1800.0).await.price, 1750.0);
+        pump.abort();
+    }
+
+    #[tokio::test]
+    async fn test_watch_skips_to_latest_when_lagging() {
+        let board = Arc::new(PriceBoard::new(2));
+        board.publish(PriceUpdate::new("ETH", "USDT", 2000.0, 1));
+        let mut watch = board.watch(&[("ETH", "USDT")]);
+        assert_eq!(watch.next().await.unwrap().price, 2000.0);
+
+        for i in 0..5 {
+            board.publish(PriceUpdate::new("WBTC", "USDT", 60000.0, i));
+            board.publish(PriceUpdate::new("ETH", "USDT", 2001.0 + i as f64, i));
+        }
+        let update = watch.next().await.unwrap();
+        assert_eq!((update.from.as_str(), update.price), ("ETH", 2005.0));
+    }
+}
@@ -0,0 +1,501 @@
+use async_trait::async_trait;
+use rust_decimal::prelude::*;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use serde::Serialize;
+use std::sync::Arc;
+
+use super::{ExactOutPrice, PriceError, PriceSource, TimedPrice};
+use crate::swap_engine::{Rounding, SwapEngine, EXACT_OUT_SCALE};
+use crate::tokens::TokenRegistry;
+
+/// Цена, путь, по которому она получена (`["ETH", "USDT", "WBTC"]`), и время
+/// самого старого звена.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TriangulatedPrice {
+    pub price: f64,
+    pub timestamp_ms: u64,
+    pub path: Vec<String>,
+}
+
+/// Курсы, различающиеся меньше чем на эту долю, считаются равными:
+/// тогда остаётся путь выше по предпочтению, а не выигравший на округлении.
+const RATE_TOLERANCE: f64 = 1e-9;
+
+fn is_better(candidate: f64, best: f64) -> bool {
+    candidate > best * (1.0 + RATE_TOLERANCE)
+}
+
+/// Выводит кросс-курсы для пар, которых нет у источника напрямую,
+/// через промежуточные токены из графа известных пар (ETH/WBTC = ETH/USDT × USDT/WBTC).
+pub struct TriangulatingPriceSource {
+    inner: Arc<dyn PriceSource>,
+    graph: HashMap<String, BTreeSet<String>>,
+    intermediates: Vec<String>,
+    max_hops: usize,
+    // Объём звена округляется до decimals его токена; неизвестного — до `EXACT_OUT_SCALE`
+    decimals: HashMap<String, u8>,
+}
+
+impl TriangulatingPriceSource {
+    pub fn new(inner: Arc<dyn PriceSource>) -> Self {
+        Self {
+            inner,
+            graph: HashMap::new(),
+            intermediates: Vec::new(),
+            max_hops: 3,
+            decimals: HashMap::new(),
+        }
+    }
+
+    /// Пара, которую источник котирует напрямую (в обе стороны).
+    pub fn with_pair(mut self, a: &str, b: &str) -> Self {
+        self.graph.entry(a.to_string()).or_default().insert(b.to_string());
+        self.graph.entry(b.to_string()).or_default().insert(a.to_string());
+        self
+    }
+
+    /// Разрешённые промежуточные токены в порядке предпочтения (USDT, ETH, ...).
+    /// Пустой список — промежуточным может быть любой токен графа.
+    pub fn with_intermediates(mut self, tokens: &[&str]) -> Self {
+        self.intermediates = tokens.iter().map(|t| t.to_string()).collect();
+        self
+    }
+
+    /// Decimals токенов из реестра: объёмы звеньев округляются до них.
+    pub fn with_registry(mut self, registry: &TokenRegistry) -> Self {
+        self.decimals = registry.tokens().into_iter().map(|t| (t.symbol.clone(), t.decimals)).collect();
+        self
+    }
+
+    pub fn with_max_hops(mut self, max_hops: usize) -> Self {
+        self.max_hops = max_hops.max(1);
+        self
+    }
+
+    /// Все простые пути `from → to` не длиннее `max_hops`, от коротких к длинным;
+    /// при равной длине выше пути через более предпочтительные промежуточные токены.
+    pub fn candidate_paths(&self, from: &str, to: &str) -> Vec<Vec<String>> {
+        let mut paths = Vec::new();
+        let mut queue = VecDeque::from([vec![from.to_string()]]);
+        while let Some(path) = queue.pop_front() {
+            let last = path.last().unwrap();
+            if path.len() > self.max_hops {
+                continue;
+            }
+            for next in self.graph.get(last).into_iter().flatten() {
+                if path.contains(next) {
+                    continue;
+                }
+                let mut extended = path.clone();
+                extended.push(next.clone());
+                if next == to {
+                    paths.push(extended);
+                } else if self.is_allowed_intermediate(next) {
+                    queue.push_back(extended);
+                }
+            }
+        }
+        paths.sort_by_key(|p| (p.len(), self.path_rank(p)));
+        paths
+    }
+
+    /// Перемножает курсы по звеньям пути; объём каждого звена — выход предыдущего,
+    /// округлённый вниз до decimals его токена.
+    async fn price_along(&self, path: &[String], amount: Option<&str>) -> Result<TriangulatedPrice, PriceError> {
+        let mut price = 1.0;
+        let mut timestamp_ms = u64::MAX;
+        let mut leg_amount = amount.map(parse_amount).transpose()?;
+        for leg in path.windows(2) {
+            let leg_amount_str = leg_amount.map(|a| a.to_string());
+            let timed = self.inner.get_price_timed(&leg[0], &leg[1], leg_amount_str.as_deref()).await?;
+            price *= timed.price;
+            timestamp_ms = timestamp_ms.min(timed.timestamp_ms);
+            leg_amount = match leg_amount {
+                Some(amount) => {
+                    let out = Decimal::from_f64(timed.price).and_then(|p| amount.checked_mul(p));
+                    Some(self.round_leg(out, &leg[1], Rounding::Down)?)
+                }
+                None => None,
+            };
+        }
+        Ok(TriangulatedPrice { price, timestamp_ms, path: path.to_vec() })
+    }
+
+    /// Exact-out вдоль пути: звенья с конца, вход звена — выход предыдущего.
+    /// Цена пути — произведение цен звеньев, вход по кривой — если хоть одно звено на кривой.
+    async fn exact_out_along(&self, path: &[String], amount_out: &str) -> Result<ExactOutPrice, PriceError> {
+        let mut price = 1.0;
+        let mut timestamp_ms = u64::MAX;
+        let mut on_curve = false;
+        let mut leg_amount = parse_amount(amount_out)?;
+        for leg in path.windows(2).rev() {
+            let quote = self.inner.get_price_exact_out(&leg[0], &leg[1], &leg_amount.to_string()).await?;
+            price *= quote.price;
+            timestamp_ms = timestamp_ms.min(quote.timestamp_ms);
+            // Вход звена — округлённый вверх до decimals его токена: его не должно не хватить
+            let amount_in = match quote.amount_in {
+                Some(amount_in) => {
+                    on_curve = true;
+                    Some(parse_amount(&amount_in)?)
+                }
+                None => Decimal::from_f64(quote.price).and_then(|p| leg_amount.checked_div(p)),
+            };
+            leg_amount = self.round_leg(amount_in, &leg[0], Rounding::Up)?;
+        }
+        Ok(ExactOutPrice { price, timestamp_ms, amount_in: on_curve.then(|| leg_amount.to_string()) })
+    }
+
+    /// Объём звена в токене `token`, округлённый до его decimals; `None` — переполнение.
+    fn round_leg(&self, amount: Option<Decimal>, token: &str, rounding: Rounding) -> Result<Decimal, PriceError> {
+        let decimals = self.decimals.get(token).copied().unwrap_or(EXACT_OUT_SCALE as u8);
+        amount
+            .and_then(|amount| SwapEngine::round_to_decimals(amount, decimals, rounding).ok())
+            .ok_or_else(|| PriceError::NoPrice(format!("Leg amount in {token} out of range")))
+    }
+
+    fn is_allowed_intermediate(&self, token: &str) -> bool {
+        self.intermediates.is_empty() || self.intermediates.iter().any(|t| t == token)
+    }
+
+    fn path_rank(&self, path: &[String]) -> Vec<usize> {
+        path[1..path.len() - 1]
+            .iter()
+            .map(|t| self.intermediates.iter().position(|i| i == t).unwrap_or(usize::MAX))
+            .collect()
+    }
+}
+
+fn parse_amount(amount: &str) -> Result<Decimal, PriceError> {
+    Decimal::from_str(amount).map_err(|_| PriceError::NoPrice(format!("Invalid amount {amount}")))
+}
+
+/// Ни один путь не дал цены. Если хоть одно звено отказало временно — ошибка тоже временная.
+fn no_path(from: &str, to: &str, direct_error: &PriceError, transient: bool) -> PriceError {
+    let message = format!("No price for {from}/{to} (direct: {direct_error}; no priced path through known pairs)");
+    if transient {
+        PriceError::Unavailable(message)
+    } else {
+        PriceError::NoPrice(message)
+    }
+}
+
+#[async_trait]
+impl PriceSource for TriangulatingPriceSource {
+    async fn get_price(&self, from: &str, to: &str, amount: Option<&str>) -> Result<f64, PriceError> {
+        Ok(self.get_price_with_path(from, to, amount).await?.price)
+    }
+
+    async fn get_price_timed(&self, from: &str, to: &str, amount: Option<&str>) -> Result<TimedPrice, PriceError> {
+        let priced = self.get_price_with_path(from, to, amount).await?;
+        Ok(TimedPrice { price: priced.price, timestamp_ms: priced.timestamp_ms })
+    }
+
+    /// Прямая цена, если она есть. Только если у источника нет пары (`NoPrice`) — лучший курс
+    /// среди путей через промежуточные токены; при равном курсе — путь выше по предпочтению.
+    async fn get_price_with_path(&self, from: &str, to: &str, amount: Option<&str>) -> Result<TriangulatedPrice, PriceError> {
+        let direct_error = match self.inner.get_price_timed(from, to, amount).await {
+            Ok(timed) => {
+                return Ok(TriangulatedPrice {
+                    price: timed.price,
+                    timestamp_ms: timed.timestamp_ms,
+                    path: vec![from.to_string(), to.to_string()],
+                })
+            }
+            Err(e @ PriceError::Unavailable(_)) => return Err(e),
+            Err(e) => e,
+        };
+        let mut best: Option<TriangulatedPrice> = None;
+        let mut transient = false;
+        // Прямую пару уже пробовали
+        for path in self.candidate_paths(from, to).into_iter().filter(|p| p.len() > 2) {
+            match self.price_along(&path, amount).await {
+                Ok(priced) if best.as_ref().is_none_or(|b| is_better(priced.price, b.price)) => best = Some(priced),
+                Ok(_) => {}
+                Err(e) => transient |= e.is_transient(),
+            }
+        }
+        best.ok_or_else(|| no_path(from, to, &direct_error, transient))
+    }
+
+    /// Прямая котировка или, если у источника нет пары, лучший курс (наименьший вход) среди путей,
+    /// как в `get_price_with_path`.
+    async fn get_price_exact_out(&self, from: &str, to: &str, amount_out: &str) -> Result<ExactOutPrice, PriceError> {
+        let direct_error = match self.inner.get_price_exact_out(from, to, amount_out).await {
+            Ok(quote) => return Ok(quote),
+            Err(e @ PriceError::Unavailable(_)) => return Err(e),
+            Err(e) => e,
+        };
+        let mut best: Option<ExactOutPrice> = None;
+        let mut transient = false;
+        for path in self.candidate_paths(from, to).into_iter().filter(|p| p.len() > 2) {
+            match self.exact_out_along(&path, amount_out).await {
+                Ok(quote) if best.as_ref().is_none_or(|b| is_better(quote.price, b.price)) => best = Some(quote),
+                Ok(_) => {}
+                Err(e) => transient |= e.is_transient(),
+            }
+        }
+        best.ok_or_else(|| no_path(from, to, &direct_error, transient))
+    }
+
+    /// Прямые пары — одним пакетом во `inner`, звенья путей для пар без прямой цены (`NoPrice`) —
+    /// вторым. Без объёма звенья независимы и перемножаются; выбор пути — как в `get_price_with_path`.
+    async fn get_prices(&self, pairs: &[(&str, &str)]) -> Vec<Result<f64, PriceError>> {
+        let direct = self.inner.get_prices(pairs).await;
+        let paths: Vec<Vec<Vec<String>>> = pairs
+            .iter()
+            .zip(&direct)
+            .map(|((from, to), direct)| match direct {
+                Err(PriceError::NoPrice(_)) => self.candidate_paths(from, to).into_iter().filter(|p| p.len() > 2).collect(),
+                _ => Vec::new(),
+            })
+            .collect();
+        let mut legs: Vec<(&str, &str)> = paths
+            .iter()
+            .flatten()
+            .flat_map(|p| p.windows(2).map(|w| (w[0].as_str(), w[1].as_str())))
+            .collect();
+        legs.sort_unstable();
+        legs.dedup();
+        let leg_prices: HashMap<(&str, &str), Result<f64, PriceError>> = if legs.is_empty() {
+            HashMap::new()
+        } else {
+            let fetched = self.inner.get_prices(&legs).await;
+            legs.iter().copied().zip(fetched).collect()
+        };
+        pairs
+            .iter()
+            .zip(direct)
+            .zip(&paths)
+            .map(|(((from, to), direct), paths)| {
+                let direct_error = match direct {
+                    Err(e @ PriceError::NoPrice(_)) => e,
+                    direct => return direct,
+                };
+                let mut best: Option<f64> = None;
+                let mut transient = false;
+                for path in paths {
+                    let price = path.windows(2).try_fold(1.0, |price, w| {
+                        leg_prices[&(w[0].as_str(), w[1].as_str())].as_ref().map(|leg| price * leg)
+                    });
+                    match price {
+                        Ok(price) if best.is_none_or(|b| is_better(price, b)) => best = Some(price),
+                        Ok(_) => {}
+                        Err(e) => transient |= e.is_transient(),
+                    }
+                }
+                best.ok_or_else(|| no_path(from, to, &direct_error, transient))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::price_source::{MockPriceSource, StaticPriceSource};
+
+    fn mock_graph() -> TriangulatingPriceSource {
+        TriangulatingPriceSource::new(Arc::new(MockPriceSource))
+            .with_pair("ETH", "USDT")
+            .with_pair("WBTC", "USDT")
+    }
+
+    #[tokio::test]
+    async fn test_direct_pair_path() {
+        let result = mock_graph().get_price_with_path("ETH", "USDT", None).await.unwrap();
+        assert_eq!(result.price, 3200.0);
+        assert_eq!(result.path, vec!["ETH", "USDT"]);
+    }
+
+    #[tokio::test]
+    async fn test_cross_rate_through_usdt() {
+        let result = mock_graph().get_price_with_path("ETH", "WBTC", None).await.unwrap();
+        assert!((result.price - 3200.0 / 67000.0).abs() < 1e-12);
+        assert_eq!(result.path, vec!["ETH", "USDT", "WBTC"]);
+    }
+
+    #[tokio::test]
+    async fn test_exact_out_along_path_uses_curve() {
+        use crate::amm::{parse_units, stableswap::StableSwapPool};
+        use crate::price_source::{FallbackPriceSource, StableSwapPriceSource};
+        use std::time::Duration;
+
+        let pool = StableSwapPool::new(
+            vec![parse_units("1000000", 6).unwrap(), parse_units("1000000", 6).unwrap()],
+            vec![6, 6],
+            200,
+            4_000_000,
+        ).unwrap();
+        let curve = StableSwapPriceSource::new(&["USDT", "USDC"], pool).unwrap();
+        let inner = FallbackPriceSource::new()
+            .with_source("curve", Arc::new(curve), Duration::from_secs(1))
+            .with_source("fixed", Arc::new(StaticPriceSource::new().with_pair("ETH", "USDT", 2000.0)), Duration::from_secs(1));
+        let tri = TriangulatingPriceSource::new(Arc::new(inner))
+            .with_pair("ETH", "USDT")
+            .with_pair("USDT", "USDC");
+
+        // 1000 USDC: USDT по кривой (больше 1000 на комиссию), ETH по цене ETH/USDT
+        let quote = tri.get_price_exact_out("ETH", "USDC", "1000").await.unwrap();
+        let amount_in: f64 = quote.amount_in.unwrap().parse().unwrap();
+        assert!(amount_in > 0.5 && amount_in < 0.501, "{amount_in}");
+        assert!((quote.price - 1000.0 / amount_in).abs() < 1e-6);
+        // Без кривой на пути — цена без объёма
+        let flat = mock_graph().get_price_exact_out("ETH", "WBTC", "1").await.unwrap();
+        assert_eq!(flat.amount_in, None);
+        assert!((flat.price - 3200.0 / 67000.0).abs() < 1e-12);
+    }
+
+    #[tokio::test]
+    async fn test_leg_amounts_rounded_to_token_decimals() {
+        use crate::amm::{parse_units, stableswap::StableSwapPool};
+        use crate::price_source::{FallbackPriceSource, StableSwapPriceSource};
+        use crate::tokens::TokenInfo;
+        use std::time::Duration;
+
+        let pool = StableSwapPool::new(
+            vec![parse_units("1000000", 6).unwrap(), parse_units("1000000", 6).unwrap()],
+            vec![6, 6],
+            200,
+            4_000_000,
+        ).unwrap();
+        let curve = StableSwapPriceSource::new(&["USDT", "USDC"], pool).unwrap();
+        let inner = FallbackPriceSource::new()
+            .with_source("curve", Arc::new(curve), Duration::from_secs(1))
+            .with_source("fixed", Arc::new(StaticPriceSource::new().with_pair("ETH", "USDT", 3000.1234567)), Duration::from_secs(1));
+        let registry = TokenRegistry::new(vec![
+            TokenInfo::new("ETH", 18, 1),
+            TokenInfo::new("USDT", 6, 1),
+            TokenInfo::new("USDC", 6, 1),
+        ]).unwrap();
+        let tri = TriangulatingPriceSource::new(Arc::new(inner))
+            .with_pair("ETH", "USDT")
+            .with_pair("USDT", "USDC")
+            .with_registry(&registry);
+
+        // 1 ETH → 3000.1234567 USDT: на кривую уходит 3000.123456, а не лишний седьмой знак
+        let priced = tri.get_price_with_path("ETH", "USDC", Some("1")).await.unwrap();
+        assert_eq!(priced.path, vec!["ETH", "USDT", "USDC"]);
+        assert!(priced.price > 2990.0 && priced.price < 3000.1234567, "{}", priced.price);
+        // Ровно 1 ETH за USDC: вход USDT (1 / цена) — вверх до 6 знаков, дальше по кривой
+        let quote = tri.get_price_exact_out("USDC", "ETH", "1").await.unwrap();
+        let amount_in = quote.amount_in.unwrap();
+        assert!(amount_in.split('.').nth(1).is_none_or(|frac| frac.len() <= 6), "{amount_in}");
+        assert!(amount_in.parse::<f64>().unwrap() > 3000.1234567);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_token_has_no_path() {
+        let err = mock_graph().get_price("DOGE", "WBTC", None).await.unwrap_err();
+        assert!(err.to_string().starts_with("No price for DOGE/WBTC"));
+    }
+
+    #[tokio::test]
+    async fn test_prefers_configured_intermediate() {
+        let source = StaticPriceSource::new()
+            .with_pair("ARB", "USDT", 2.0)
+            .with_pair("ARB", "ETH", 0.001)
+            .with_pair("ETH", "USDT", 2000.0)
+            .with_pair("DAI", "USDT", 1.0)
+            .with_pair("DAI", "ETH", 0.0005);
+        let tri = TriangulatingPriceSource::new(Arc::new(source))
+            .with_pair("ARB", "USDT")
+            .with_pair("ARB", "ETH")
+            .with_pair("DAI", "USDT")
+            .with_pair("DAI", "ETH")
+            .with_intermediates(&["ETH", "USDT"]);
+        let result = tri.get_price_with_path("ARB", "DAI", None).await.unwrap();
+        assert_eq!(result.path, vec!["ARB", "ETH", "DAI"]);
+        assert!((result.price - 2.0).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_batch_prices_cross_rates() {
+        let prices = mock_graph().get_prices(&[("ETH", "USDT"), ("ETH", "WBTC"), ("DOGE", "WBTC")]).await;
+        assert_eq!(prices[0], Ok(3200.0));
+        assert!((prices[1].as_ref().unwrap() - 3200.0 / 67000.0).abs() < 1e-12);
+        assert!(prices[2].as_ref().unwrap_err().to_string().starts_with("No price for DOGE/WBTC"));
+    }
+
+    #[tokio::test]
+    async fn test_picks_best_rate_path() {
+        // Прямой пары нет, путь через USDT хуже пути через ETH
+        let source = StaticPriceSource::new()
+            .with_pair("ARB", "USDT", 1.95)
+            .with_pair("DAI", "USDT", 1.0)
+            .with_pair("ARB", "ETH", 0.001)
+            .with_pair("DAI", "ETH", 0.0005);
+        let tri = TriangulatingPriceSource::new(Arc::new(source))
+            .with_pair("ARB", "USDT")
+            .with_pair("DAI", "USDT")
+            .with_pair("ARB", "ETH")
+            .with_pair("DAI", "ETH")
+            .with_intermediates(&["USDT", "ETH"]);
+        let result = tri.get_price_with_path("ARB", "DAI", None).await.unwrap();
+        assert_eq!(result.path, vec!["ARB", "ETH", "DAI"]);
+        assert!((result.price - 2.0).abs() < 1e-9);
+        let batch = tri.get_prices(&[("ARB", "DAI")]).await;
+        assert!((batch[0].as_ref().unwrap() - 2.0).abs() < 1e-9);
+    }
+
+    /// Считает обращения к `inner`; прямая пара ARB/DAI — временно недоступна.
+    struct CountingSource {
+        inner: StaticPriceSource,
+        calls: std::sync::atomic::AtomicUsize,
+        unavailable: (&'static str, &'static str),
+    }
+
+    #[async_trait]
+    impl PriceSource for CountingSource {
+        async fn get_price(&self, from: &str, to: &str, amount: Option<&str>) -> Result<f64, PriceError> {
+            self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            if (from, to) == self.unavailable {
+                return Err(PriceError::Unavailable("timeout".to_string()));
+            }
+            self.inner.get_price(from, to, amount).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_direct_price_wins_over_paths() {
+        use std::sync::atomic::Ordering::SeqCst;
+        // Путь через ETH дал бы 2.0, но прямая цена есть — она и остаётся, без обращений за звеньями
+        let source = Arc::new(CountingSource {
+            inner: StaticPriceSource::new()
+                .with_pair("ARB", "DAI", 1.9)
+                .with_pair("ARB", "ETH", 0.001)
+                .with_pair("DAI", "ETH", 0.0005)
+                .with_pair("ETH", "USDT", 2000.0)
+                .with_pair("WBTC", "USDT", 60000.0),
+            calls: 0.into(),
+            unavailable: ("ETH", "WBTC"),
+        });
+        let tri = TriangulatingPriceSource::new(source.clone())
+            .with_pair("ARB", "DAI")
+            .with_pair("ARB", "ETH")
+            .with_pair("DAI", "ETH")
+            .with_pair("ETH", "USDT")
+            .with_pair("WBTC", "USDT")
+            .with_intermediates(&["ETH", "USDT"]);
+        let result = tri.get_price_with_path("ARB", "DAI", None).await.unwrap();
+        assert_eq!((result.price, result.path), (1.9, vec!["ARB".to_string(), "DAI".to_string()]));
+        assert_eq!(source.calls.load(SeqCst), 1);
+        assert_eq!(tri.get_prices(&[("ARB", "DAI")]).await, vec![Ok(1.9)]);
+        assert_eq!(source.calls.load(SeqCst), 2);
+
+        // Временный отказ прямой пары — не повод подменять её кросс-курсом
+        let err = tri.get_price("ETH", "WBTC", None).await.unwrap_err();
+        assert_eq!(err, PriceError::Unavailable("timeout".to_string()));
+        assert!(tri.get_prices(&[("ETH", "WBTC")]).await[0].as_ref().unwrap_err().is_transient());
+    }
+
+    #[test]
+    fn test_max_hops_limits_paths() {
+        let tri = TriangulatingPriceSource::new(Arc::new(MockPriceSource))
+            .with_pair("A", "B")
+            .with_pair("B", "C")
+            .with_pair("C", "D")
+            .with_max_hops(2);
+        assert_eq!(tri.candidate_paths("A", "C").len(), 1);
+        assert!(tri.candidate_paths("A", "D").is_empty());
+    }
+}
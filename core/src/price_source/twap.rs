@@ -0,0 +1,293 @@
+use async_trait::async_trait;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::uniswap_v2::UniswapV2PriceSource;
+use super::{PriceError, PriceSource, TimedPrice};
+use crate::amm::uniswap_v2::{self, V2Cumulative};
+
+/// Средняя по времени цена V2-пары за окно `window` по `price{0,1}CumulativeLast`.
+/// Наблюдения собирает `sample` (вручную или задачей `spawn_sampler`); цена — от
+/// наблюдения не моложе окна до текущего накопителя, так что манипуляция резервами
+/// внутри блока сдвигает её лишь пропорционально своей длительности.
+pub struct TwapPriceSource {
+    pair: Arc<UniswapV2PriceSource>,
+    window: Duration,
+    observations: Mutex<VecDeque<V2Cumulative>>,
+    sampling: Mutex<SamplingStatus>,
+}
+
+/// Итог чтений накопителей: сколько раз `sample` не удался и последняя ошибка,
+/// если не удалось и последнее чтение.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SamplingStatus {
+    pub errors: u64,
+    pub last_error: Option<String>,
+}
+
+impl TwapPriceSource {
+    pub fn new(pair: Arc<UniswapV2PriceSource>, window: Duration) -> Self {
+        Self {
+            pair,
+            window,
+            observations: Mutex::new(VecDeque::new()),
+            sampling: Mutex::new(SamplingStatus::default()),
+        }
+    }
+
+    pub fn sampling_status(&self) -> SamplingStatus {
+        self.sampling.lock().unwrap().clone()
+    }
+
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    fn window_secs(&self) -> u32 {
+        self.window.as_secs().min(u32::MAX as u64) as u32
+    }
+
+    /// Записывает текущие накопленные цены. Хранится только то, что нужно для окна:
+    /// самое свежее наблюдение не моложе `window` и всё, что новее него.
+    pub async fn sample(&self) -> Result<V2Cumulative, String> {
+        let current = match self.pair.cumulative_prices_at(None).await {
+            Ok(current) => current,
+            Err(e) => {
+                let mut sampling = self.sampling.lock().unwrap();
+                sampling.errors += 1;
+                sampling.last_error = Some(e.clone());
+                return Err(e);
+            }
+        };
+        self.sampling.lock().unwrap().last_error = None;
+        let window = self.window_secs();
+        let mut observations = self.observations.lock().unwrap();
+        if observations.back().is_none_or(|last| current.timestamp.wrapping_sub(last.timestamp) > 0) {
+            observations.push_back(current);
+        }
+        while observations.len() > 1 && current.timestamp.wrapping_sub(observations[1].timestamp) >= window {
+            observations.pop_front();
+        }
+        Ok(current)
+    }
+
+    /// Фоновая задача: `sample` каждые `interval`. Ошибки чтения считаются в `sampling_status`,
+    /// и пока чтения не восстановятся, `twap` отказывает: без новых наблюдений начало окна
+    /// незаметно уезжало бы в прошлое.
+    pub fn spawn_sampler(self: &Arc<Self>, interval: Duration) -> tokio::task::JoinHandle<()> {
+        let twap = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                // Ошибка уже учтена в `sampling_status`
+                twap.sample().await.ok();
+            }
+        })
+    }
+
+    /// (token1 за token0, token0 за token1) за окно и время конца окна (unix-секунды).
+    pub async fn twap(&self) -> Result<(f64, f64, u32), String> {
+        let sampling = self.sampling_status();
+        if let Some(e) = sampling.last_error {
+            return Err(format!(
+                "TWAP sampling failing for {}/{} ({} errors): {e}",
+                self.pair.token0_symbol, self.pair.token1_symbol, sampling.errors
+            ));
+        }
+        let current = self.pair.cumulative_prices_at(None).await?;
+        let window = self.window_secs();
+        let start = {
+            let observations = self.observations.lock().unwrap();
+            let oldest_age = observations.front().map(|o| current.timestamp.wrapping_sub(o.timestamp)).unwrap_or(0);
+            observations
+                .iter()
+                .rev()
+                .find(|o| current.timestamp.wrapping_sub(o.timestamp) >= window)
+                .copied()
+                .ok_or_else(|| format!(
+                    "TWAP window not filled for {}/{}: have {oldest_age}s of {window}s",
+                    self.pair.token0_symbol, self.pair.token1_symbol
+                ))?
+        };
+        let (price0, price1) = uniswap_v2::twap(&start, &current, self.pair.decimals0, self.pair.decimals1)
+            .map_err(|e| e.to_string())?;
+        Ok((price0, price1, current.timestamp))
+    }
+}
+
+#[async_trait]
+impl PriceSource for TwapPriceSource {
+    async fn get_price(&self, from: &str, to: &str, amount: Option<&str>) -> Result<f64, PriceError> {
+        Ok(self.get_price_timed(from, to, amount).await?.price)
+    }
+
+    /// Средняя цена не зависит от объёма: `amount` игнорируется.
+    async fn get_price_timed(&self, from: &str, to: &str, _amount: Option<&str>) -> Result<TimedPrice, PriceError> {
+        let zero_for_one = if from == self.pair.token0_symbol && to == self.pair.token1_symbol {
+            true
+        } else if from == self.pair.token1_symbol && to == self.pair.token0_symbol {
+            false
+        } else {
+            return Err(PriceError::unsupported_pair());
+        };
+        let (price0, price1, timestamp) = self.twap().await?;
+        let price = if zero_for_one { price0 } else { price1 };
+        Ok(TimedPrice { price, timestamp_ms: timestamp as u64 * 1000 })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amm::uniswap_v2::V2Reserves;
+    use crate::amm::uniswap_v3::V3PoolState;
+    use crate::amm::{parse_units, U256};
+    use crate::pool_state::PoolStateProvider;
+    use crate::price_source::{now_ms, GuardedPriceSource};
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// Пара, время которой двигается вручную: накопители растут как в контракте.
+    struct ChainStandIn {
+        state: Mutex<(V2Cumulative, V2Reserves)>,
+        offline: AtomicBool,
+    }
+
+    impl ChainStandIn {
+        fn new(usdt_per_eth: &str) -> Arc<Self> {
+            let start = V2Cumulative {
+                price0_cumulative: U256::zero(),
+                price1_cumulative: U256::zero(),
+                timestamp: (now_ms() / 1000) as u32,
+            };
+            Arc::new(Self { state: Mutex::new((start, reserves(usdt_per_eth))), offline: AtomicBool::new(false) })
+        }
+
+        fn advance(&self, secs: u32) {
+            let mut state = self.state.lock().unwrap();
+            let (c, r) = *state;
+            state.0 = uniswap_v2::current_cumulative(c.price0_cumulative, c.price1_cumulative, c.timestamp, r, c.timestamp + secs);
+        }
+
+        fn set_price(&self, usdt_per_eth: &str) {
+            self.state.lock().unwrap().1 = reserves(usdt_per_eth);
+        }
+    }
+
+    fn reserves(usdt_per_eth: &str) -> V2Reserves {
+        let usdt: f64 = usdt_per_eth.parse().unwrap();
+        V2Reserves {
+            reserve0: parse_units("100", 18).unwrap(),
+            reserve1: parse_units(&(usdt * 100.0).to_string(), 6).unwrap(),
+        }
+    }
+
+    #[async_trait]
+    impl PoolStateProvider for ChainStandIn {
+        async fn block_number(&self) -> Result<u64, String> {
+            Ok(self.state.lock().unwrap().0.timestamp as u64)
+        }
+        async fn v2_reserves(&self, _pool: &str, _block: Option<u64>) -> Result<V2Reserves, String> {
+            Ok(self.state.lock().unwrap().1)
+        }
+        async fn v2_cumulative(&self, _pool: &str, _block: Option<u64>) -> Result<V2Cumulative, String> {
+            if self.offline.load(Ordering::SeqCst) {
+                return Err("node unavailable".to_string());
+            }
+            Ok(self.state.lock().unwrap().0)
+        }
+        async fn v3_state(&self, _pool: &str, _bitmap_words: i16) -> Result<(V3PoolState, (i32, i32)), String> {
+            Err("not a v3 pool".to_string())
+        }
+    }
+
+    fn pair(chain: Arc<ChainStandIn>) -> Arc<UniswapV2PriceSource> {
+        // Блоки стенда сменяются мгновенно — кэш резервов держится только в пределах блока
+        Arc::new(UniswapV2PriceSource::new(chain, "0xpair", "ETH", "USDT", 18, 6).with_block_time(Duration::ZERO))
+    }
+
+    #[tokio::test]
+    async fn test_twap_over_window() {
+        let chain = ChainStandIn::new("2000");
+        let twap = TwapPriceSource::new(pair(chain.clone()), Duration::from_secs(600));
+        twap.sample().await.unwrap();
+        chain.advance(300);
+        twap.sample().await.unwrap();
+        let err = twap.get_price("ETH", "USDT", None).await.unwrap_err();
+        assert_eq!(err.to_string(), "TWAP window not filled for ETH/USDT: have 300s of 600s");
+
+        chain.set_price("4000");
+        chain.advance(300);
+        twap.sample().await.unwrap();
+        let price = twap.get_price("ETH", "USDT", None).await.unwrap();
+        assert!((price - 3000.0).abs() < 1e-6, "got {price}");
+        let inverse = twap.get_price("USDT", "ETH", None).await.unwrap();
+        assert!((inverse - (1.0 / 2000.0 + 1.0 / 4000.0) / 2.0).abs() < 1e-12);
+
+        // Старые наблюдения отбрасываются: окно скользит
+        chain.advance(600);
+        twap.sample().await.unwrap();
+        assert!((twap.get_price("ETH", "USDT", None).await.unwrap() - 4000.0).abs() < 1e-6);
+        assert!(twap.observations.lock().unwrap().len() <= 2);
+    }
+
+    #[tokio::test]
+    async fn test_guard_confirms_spot_against_twap() {
+        let chain = ChainStandIn::new("2000");
+        let spot = pair(chain.clone());
+        let twap = Arc::new(TwapPriceSource::new(spot.clone(), Duration::from_secs(600)));
+        let guard = GuardedPriceSource::new(spot, Duration::from_secs(60), 5.0).with_confirmation(twap.clone());
+        twap.sample().await.unwrap();
+        chain.advance(600);
+        twap.sample().await.unwrap();
+        assert_eq!(guard.get_price("ETH", "USDT", None).await.unwrap(), 2000.0);
+
+        // Резервы сдвинуты в текущем блоке: спот 4000, средняя почти не изменилась
+        chain.set_price("4000");
+        chain.advance(12);
+        let err = guard.get_price("ETH", "USDT", None).await.unwrap_err();
+        assert!(err.to_string().contains("was not confirmed"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_sampler_task_collects_observations() {
+        let chain = ChainStandIn::new("2000");
+        let twap = Arc::new(TwapPriceSource::new(pair(chain.clone()), Duration::from_secs(600)));
+        let handle = twap.spawn_sampler(Duration::from_millis(5));
+        for _ in 0..3 {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            chain.advance(100);
+        }
+        handle.abort();
+        assert!(twap.observations.lock().unwrap().len() >= 2);
+    }
+
+    #[tokio::test]
+    async fn test_sampling_errors_surface_in_price() {
+        let chain = ChainStandIn::new("2000");
+        let twap = Arc::new(TwapPriceSource::new(pair(chain.clone()), Duration::from_secs(600)));
+        twap.sample().await.unwrap();
+        chain.advance(600);
+        twap.sample().await.unwrap();
+        assert!((twap.get_price("ETH", "USDT", None).await.unwrap() - 2000.0).abs() < 1e-9);
+
+        // Нода недоступна: сэмплер считает ошибки, а цена не отдаётся по застывшему окну
+        chain.offline.store(true, Ordering::SeqCst);
+        let handle = twap.spawn_sampler(Duration::from_millis(5));
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        let status = twap.sampling_status();
+        assert!(status.errors >= 2, "{status:?}");
+        assert_eq!(status.last_error.as_deref(), Some("node unavailable"));
+        let err = twap.get_price("ETH", "USDT", None).await.unwrap_err();
+        assert!(err.is_transient() && err.to_string().contains("TWAP sampling failing"), "{err}");
+        chain.offline.store(false, Ordering::SeqCst);
+
+        // После удачного чтения цена снова доступна, счётчик ошибок сохраняется
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        handle.abort();
+        assert!((twap.get_price("ETH", "USDT", None).await.unwrap() - 2000.0).abs() < 1e-9);
+        assert!(twap.sampling_status().last_error.is_none());
+        assert!(twap.sampling_status().errors >= status.errors);
+    }
+}
@@ -0,0 +1,139 @@
+use async_trait::async_trait;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::amm::{self, AmmQuote};
+use crate::amm::uniswap_v3::{SwapAmount, V3PoolState};
+use crate::pool_state::PoolStateProvider;
+use crate::tokens::TokenInfo;
+
+use super::{ExactOutPrice, PriceError};
+
+#[cfg(feature = "uniswap")]
+ethers::contract::abigen!(
+    UniswapV3Pool,
+    r#"[
+        function slot0() external view returns (uint160 sqrtPriceX96, int24 tick, uint16 observationIndex, uint16 observationCardinality, uint16 observationCardinalityNext, uint8 feeProtocol, bool unlocked)
+        function liquidity() external view returns (uint128)
+        function fee() external view returns (uint24)
+        function tickSpacing() external view returns (int24)
+        function tickBitmap(int16 wordPosition) external view returns (uint256)
+        function ticks(int24 tick) external view returns (uint128 liquidityGross, int128 liquidityNet, uint256 feeGrowthOutside0X128, uint256 feeGrowthOutside1X128, int56 tickCumulativeOutside, uint160 secondsPerLiquidityOutsideX128, uint32 secondsOutside, bool initialized)
+    ]"#
+);
+
+/// V3-пул: спот-цена из `slot0`, котировки на объём — симуляцией свопа
+/// по тикам, прочитанным из `tickBitmap` вокруг текущей цены.
+pub struct UniswapV3PriceSource {
+    state: Arc<dyn PoolStateProvider>,
+    pub pool_address: String,
+    pub token0_symbol: String,
+    pub token1_symbol: String,
+    pub decimals0: u8,
+    pub decimals1: u8,
+    /// Сколько 256-битных слов bitmap читать в каждую сторону от текущего тика
+    pub bitmap_words: i16,
+}
+
+impl UniswapV3PriceSource {
+    pub fn new(
+        state: Arc<dyn PoolStateProvider>,
+        pool_address: impl Into<String>,
+        token0_symbol: &str,
+        token1_symbol: &str,
+        decimals0: u8,
+        decimals1: u8,
+    ) -> Self {
+        Self {
+            state,
+            pool_address: pool_address.into(),
+            token0_symbol: token0_symbol.to_string(),
+            token1_symbol: token1_symbol.to_string(),
+            decimals0,
+            decimals1,
+            bitmap_words: 2,
+        }
+    }
+
+    /// Символы и decimals токенов пула — из реестра.
+    pub fn for_tokens(
+        state: Arc<dyn PoolStateProvider>,
+        pool_address: impl Into<String>,
+        token0: &TokenInfo,
+        token1: &TokenInfo,
+    ) -> Self {
+        Self::new(state, pool_address, &token0.symbol, &token1.symbol, token0.decimals, token1.decimals)
+    }
+
+    pub fn with_bitmap_words(mut self, words: i16) -> Self {
+        self.bitmap_words = words.max(0);
+        self
+    }
+
+    fn zero_for_one(&self, from: &str, to: &str) -> Result<bool, PriceError> {
+        if from == self.token0_symbol && to == self.token1_symbol {
+            Ok(true)
+        } else if from == self.token1_symbol && to == self.token0_symbol {
+            Ok(false)
+        } else {
+            Err(PriceError::unsupported_pair())
+        }
+    }
+
+    /// Снимок пула и диапазон тиков, для которого известны все инициализированные тики.
+    pub async fn pool_state(&self) -> Result<(V3PoolState, (i32, i32)), String> {
+        self.state.v3_state(&self.pool_address, self.bitmap_words).await
+    }
+
+    /// Котировка exact-in на объём `amount` (в человеческих единицах `from`).
+    pub async fn quote(&self, from: &str, to: &str, amount: &str) -> Result<AmmQuote, PriceError> {
+        let zero_for_one = self.zero_for_one(from, to)?;
+        let decimals_in = if zero_for_one { self.decimals0 } else { self.decimals1 };
+        let amount_in = amm::parse_units(amount, decimals_in)?;
+        self.quote_swap(zero_for_one, SwapAmount::ExactIn(amount_in)).await
+    }
+
+    /// Котировка exact-out: сколько `from` нужно за ровно `amount_out` (в человеческих единицах `to`).
+    pub async fn quote_exact_out(&self, from: &str, to: &str, amount_out: &str) -> Result<AmmQuote, PriceError> {
+        let zero_for_one = self.zero_for_one(from, to)?;
+        let decimals_out = if zero_for_one { self.decimals1 } else { self.decimals0 };
+        let amount_out = amm::parse_units(amount_out, decimals_out)?;
+        self.quote_swap(zero_for_one, SwapAmount::ExactOut(amount_out)).await
+    }
+
+    /// Симулирует своп `amount` один раз; ошибка, если он уводит цену за загруженный диапазон тиков.
+    async fn quote_swap(&self, zero_for_one: bool, amount: SwapAmount) -> Result<AmmQuote, PriceError> {
+        let (state, (lowest, highest)) = self.pool_state().await?;
+        let result = state.swap(zero_for_one, amount)?;
+        if result.tick_after < lowest || result.tick_after > highest {
+            return Err(PriceError::Unavailable("Swap moves price beyond loaded tick range".to_string()));
+        }
+        Ok(state.quote_swap(zero_for_one, &result, self.decimals0, self.decimals1))
+    }
+
+    pub async fn spot_price(&self, from: &str, to: &str) -> Result<f64, PriceError> {
+        let zero_for_one = self.zero_for_one(from, to)?;
+        let (sqrt_price_x96, tick) = self.state.v3_slot0(&self.pool_address).await?;
+        let state = V3PoolState { sqrt_price_x96, tick, liquidity: 0, fee_pips: 0, ticks: BTreeMap::new() };
+        let price = state.price_1_per_0(self.decimals0, self.decimals1);
+        Ok(if zero_for_one { price } else { 1.0 / price })
+    }
+}
+
+#[async_trait]
+impl super::PriceSource for UniswapV3PriceSource {
+    /// Без `amount` — спот из `slot0`; с `amount` — эффективная цена симулированного свопа.
+    async fn get_price(&self, from: &str, to: &str, amount: Option<&str>) -> Result<f64, PriceError> {
+        match amount {
+            Some(amount) => Ok(self.quote(from, to, amount).await?.effective_price),
+            None => self.spot_price(from, to).await,
+        }
+    }
+
+    /// Вход симулированного exact-out свопа.
+    async fn get_price_exact_out(&self, from: &str, to: &str, amount_out: &str) -> Result<ExactOutPrice, PriceError> {
+        let quote = self.quote_exact_out(from, to, amount_out).await?;
+        let decimals_in = if from == self.token0_symbol { self.decimals0 } else { self.decimals1 };
+        Ok(ExactOutPrice::from_amm(&quote, decimals_in))
+    }
+}
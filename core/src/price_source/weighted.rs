@@ -0,0 +1,67 @@
+use super::pool::{PoolModel, PoolPriceSource};
+use crate::amm::{weighted::WeightedPool, AmmError, AmmQuote, U256};
+
+/// `PriceSource` поверх снимка взвешенного пула Balancer: спот с учётом весов, объём — `calcOutGivenIn`, exact-out — `calcInGivenOut`.
+pub type WeightedPoolPriceSource = PoolPriceSource<WeightedPool>;
+
+impl PoolModel for WeightedPool {
+    fn decimals(&self) -> &[u8] {
+        &self.decimals
+    }
+
+    fn spot(&self, i: usize, j: usize) -> Result<f64, AmmError> {
+        self.spot_price(i, j)
+    }
+
+    fn quote(&self, i: usize, j: usize, amount_in: U256) -> Result<AmmQuote, AmmError> {
+        WeightedPool::quote(self, i, j, amount_in)
+    }
+
+    fn quote_exact_out(&self, i: usize, j: usize, amount_out: U256) -> Result<AmmQuote, AmmError> {
+        WeightedPool::quote_exact_out(self, i, j, amount_out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amm::{format_units, parse_units};
+    use crate::amm::weighted::ONE;
+    use crate::price_source::PriceSource;
+
+    fn pool(bal: &str, weth: &str) -> WeightedPool {
+        WeightedPool::new(
+            vec![parse_units(bal, 18).unwrap(), parse_units(weth, 18).unwrap()],
+            vec![18, 18],
+            vec![ONE / 10 * 8, ONE / 10 * 2],
+            ONE / 100,
+        ).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_weighted_source_prices() {
+        let source = WeightedPoolPriceSource::new(&["BAL", "WETH"], pool("800000", "1000")).unwrap();
+        let spot = source.get_price("WETH", "BAL", None).await.unwrap();
+        assert!((spot - 200.0).abs() < 1e-9);
+        let effective = source.get_price("WETH", "BAL", Some("10")).await.unwrap();
+        assert!(effective < 200.0 * 0.99 && effective > 190.0);
+        assert!(source.get_price("WETH", "USDC", None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_weighted_source_update_pool() {
+        let source = WeightedPoolPriceSource::new(&["BAL", "WETH"], pool("800000", "1000")).unwrap();
+        source.update_pool(pool("400000", "1000")).unwrap();
+        assert!((source.get_price("WETH", "BAL", None).await.unwrap() - 100.0).abs() < 1e-9);
+        assert!(WeightedPoolPriceSource::new(&["BAL"], pool("1", "1")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_weighted_source_exact_out() {
+        let source = WeightedPoolPriceSource::new(&["BAL", "WETH"], pool("800000", "1000")).unwrap();
+        let quote = source.get_price_exact_out("WETH", "BAL", "2000").await.unwrap();
+        let amount_in = pool("800000", "1000").in_given_out(1, 0, parse_units("2000", 18).unwrap()).unwrap();
+        assert_eq!(quote.amount_in, Some(format_units(amount_in, 18)));
+        assert!(quote.price < 200.0 * 0.99);
+    }
+}
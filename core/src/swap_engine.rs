@@ -1,12 +1,69 @@
-use rust_decimal::Decimal;
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
+use crate::amm::{self, U256};
+
 pub struct SwapEngine;
 
+/// Допуск проскальзывания по умолчанию — 0.5%.
+pub const DEFAULT_SLIPPAGE_BPS: u32 = 50;
+/// 100% в базисных пунктах.
+pub const MAX_SLIPPAGE_BPS: u32 = 10_000;
+/// Знаков после запятой у входа exact-out — максимум decimals EVM-токенов.
+pub const EXACT_OUT_SCALE: u32 = 18;
+/// Больше знаков `Decimal` не хранит.
+pub const MAX_DECIMALS: u8 = 28;
+
 #[derive(Debug)]
 pub enum SwapError {
     InvalidAmount,
     InvalidPrice,
+    InvalidSlippage,
+    /// У суммы больше знаков после запятой, чем у токена
+    InvalidPrecision { decimals: u8 },
+    /// Фактический выход свопа меньше минимального из котировки
+    SlippageExceeded { amount_out: Decimal, min_amount_out: Decimal },
+}
+
+/// Что задано точно: вход (`exact_in`, по умолчанию) или выход (`exact_out`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SwapMode {
+    #[default]
+    ExactIn,
+    ExactOut,
+}
+
+/// Округление суммы до decimals токена.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rounding {
+    /// К нулю — для того, что протокол отдаёт
+    Down,
+    /// От нуля — для того, что протокол получает
+    Up,
+}
+
+impl Rounding {
+    fn strategy(self) -> RoundingStrategy {
+        match self {
+            Rounding::Down => RoundingStrategy::ToZero,
+            Rounding::Up => RoundingStrategy::AwayFromZero,
+        }
+    }
+}
+
+/// Котировка с защитой от проскальзывания. Для `exact_in` ограничение — `min_amount_out`
+/// (вход точный, `max_amount_in == amount_in`), для `exact_out` — `max_amount_in`
+/// (выход точный, `min_amount_out == amount_out`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SwapQuote {
+    pub mode: SwapMode,
+    pub amount_in: Decimal,
+    pub amount_out: Decimal,
+    pub min_amount_out: Decimal,
+    pub max_amount_in: Decimal,
+    pub slippage_bps: u32,
 }
 
 impl fmt::Display for SwapError {
@@ -14,6 +71,11 @@ impl fmt::Display for SwapError {
         match self {
             SwapError::InvalidAmount => write!(f, "Invalid amount"),
             SwapError::InvalidPrice => write!(f, "Invalid price"),
+            SwapError::InvalidSlippage => write!(f, "Invalid slippage: must be at most {MAX_SLIPPAGE_BPS} bps"),
+            SwapError::InvalidPrecision { decimals } => write!(f, "Invalid amount: more than {decimals} decimal places"),
+            SwapError::SlippageExceeded { amount_out, min_amount_out } => {
+                write!(f, "Slippage exceeded: amount_out {amount_out} is below min_amount_out {min_amount_out}")
+            }
         }
     }
 }
@@ -26,7 +88,182 @@ impl SwapEngine {
         if price <= Decimal::ZERO {
             return Err(SwapError::InvalidPrice);
         }
-        Ok(amount_in * price)
+        amount_in.checked_mul(price).ok_or(SwapError::InvalidAmount)
+    }
+
+    /// Сколько отдать за ровно `amount_out` по цене `price`: частное, округлённое вверх
+    /// до `EXACT_OUT_SCALE` знаков. Вход никогда не даёт меньше `amount_out` — в пользу протокола.
+    pub fn get_quote_exact_out(amount_out: Decimal, price: Decimal) -> Result<Decimal, SwapError> {
+        Self::div_ceil(amount_out, price, EXACT_OUT_SCALE)
+    }
+
+    /// `amount_out / price`, округлённое вверх до `scale` знаков.
+    fn div_ceil(amount_out: Decimal, price: Decimal, scale: u32) -> Result<Decimal, SwapError> {
+        if amount_out <= Decimal::ZERO {
+            return Err(SwapError::InvalidAmount);
+        }
+        if price <= Decimal::ZERO {
+            return Err(SwapError::InvalidPrice);
+        }
+        // Точно, в целых: out / price = m_out · 10^s_price / (m_price · 10^s_out)
+        let pow10 = |n: u32| U256::exp10(n as usize);
+        // До 10^56 · 2^96 при scale 28 у цены и decimals — больше U256
+        let numerator = U256::from(amount_out.mantissa() as u128)
+            .checked_mul(pow10(price.scale() + scale))
+            .ok_or(SwapError::InvalidAmount)?;
+        let denominator = U256::from(price.mantissa() as u128)
+            .checked_mul(pow10(amount_out.scale()))
+            .ok_or(SwapError::InvalidAmount)?;
+        let (quotient, remainder) = numerator.div_mod(denominator);
+        let quotient = if remainder.is_zero() { quotient } else { quotient + 1 };
+        // Мантисса Decimal — 96 бит
+        if quotient.bits() > 96 {
+            return Err(SwapError::InvalidAmount);
+        }
+        Ok(Decimal::from_i128_with_scale(quotient.as_u128() as i128, scale).normalize())
+    }
+
+    /// Котировка в режиме `mode` (`amount` — вход для `exact_in`, выход для `exact_out`)
+    /// с ограничением проскальзывания `slippage_bps`, в decimals токенов входа и выхода.
+    /// Отдаваемое протоколом (`amount_out`, `min_amount_out`) округляется вниз, получаемое
+    /// (`amount_in`, `max_amount_in`) — вверх; `amount` точнее decimals своего токена — ошибка.
+    pub fn quote(
+        mode: SwapMode,
+        amount: Decimal,
+        price: Decimal,
+        slippage_bps: u32,
+        decimals_in: u8,
+        decimals_out: u8,
+    ) -> Result<SwapQuote, SwapError> {
+        if decimals_in > MAX_DECIMALS || decimals_out > MAX_DECIMALS {
+            return Err(SwapError::InvalidPrecision { decimals: decimals_in.max(decimals_out) });
+        }
+        match mode {
+            SwapMode::ExactIn => {
+                Self::check_precision(amount, decimals_in)?;
+                let amount_out = Self::round_to_decimals(Self::get_quote(amount, price)?, decimals_out, Rounding::Down)?;
+                if amount_out.is_zero() {
+                    return Err(SwapError::InvalidAmount);
+                }
+                let min_amount_out = Self::min_amount_out(amount_out, slippage_bps)?;
+                Ok(SwapQuote {
+                    mode,
+                    amount_in: amount,
+                    amount_out,
+                    min_amount_out: Self::round_to_decimals(min_amount_out, decimals_out, Rounding::Down)?,
+                    max_amount_in: amount,
+                    slippage_bps,
+                })
+            }
+            SwapMode::ExactOut => {
+                Self::check_precision(amount, decimals_out)?;
+                let amount_in = Self::div_ceil(amount, price, decimals_in as u32)?;
+                Self::quote_exact_out_with_input(amount, amount_in, slippage_bps, decimals_in, decimals_out)
+            }
+        }
+    }
+
+    /// Котировка exact-out за ровно `amount_out` со входом `amount_in`, уже посчитанным
+    /// (например, по кривой пула); вход округляется вверх до decimals своего токена.
+    pub fn quote_exact_out_with_input(
+        amount_out: Decimal,
+        amount_in: Decimal,
+        slippage_bps: u32,
+        decimals_in: u8,
+        decimals_out: u8,
+    ) -> Result<SwapQuote, SwapError> {
+        if decimals_in > MAX_DECIMALS || decimals_out > MAX_DECIMALS {
+            return Err(SwapError::InvalidPrecision { decimals: decimals_in.max(decimals_out) });
+        }
+        Self::check_precision(amount_out, decimals_out)?;
+        if amount_out <= Decimal::ZERO || amount_in <= Decimal::ZERO {
+            return Err(SwapError::InvalidAmount);
+        }
+        let amount_in = Self::round_to_decimals(amount_in, decimals_in, Rounding::Up)?;
+        let max_amount_in = Self::max_amount_in(amount_in, slippage_bps)?;
+        Ok(SwapQuote {
+            mode: SwapMode::ExactOut,
+            amount_in,
+            amount_out,
+            min_amount_out: amount_out,
+            max_amount_in: Self::round_to_decimals(max_amount_in, decimals_in, Rounding::Up)?,
+            slippage_bps,
+        })
+    }
+
+    fn check_precision(amount: Decimal, decimals: u8) -> Result<(), SwapError> {
+        if amount.normalize().scale() > decimals as u32 {
+            return Err(SwapError::InvalidPrecision { decimals });
+        }
+        Ok(())
+    }
+
+    /// `amount`, округлённая до `decimals` знаков в сторону `rounding`, без хвостовых нулей.
+    pub fn round_to_decimals(amount: Decimal, decimals: u8, rounding: Rounding) -> Result<Decimal, SwapError> {
+        if decimals > MAX_DECIMALS {
+            return Err(SwapError::InvalidPrecision { decimals });
+        }
+        Ok(amount.round_dp_with_strategy(decimals as u32, rounding.strategy()).normalize())
+    }
+
+    /// Человеческая сумма ("1.5" ETH) в base units (wei) для AMM-математики через `amm::parse_units`;
+    /// лишние знаки округляются в сторону `rounding`.
+    pub fn to_base_units(amount: Decimal, decimals: u8, rounding: Rounding) -> Result<U256, SwapError> {
+        if amount.is_sign_negative() {
+            return Err(SwapError::InvalidAmount);
+        }
+        let rounded = Self::round_to_decimals(amount, decimals, rounding)?;
+        amm::parse_units(&rounded.to_string(), decimals).map_err(|_| SwapError::InvalidAmount)
+    }
+
+    /// Base units обратно в человеческую сумму через `amm::format_units`; точно, пока сумма помещается в `Decimal`.
+    pub fn from_base_units(amount: U256, decimals: u8) -> Result<Decimal, SwapError> {
+        if decimals > MAX_DECIMALS {
+            return Err(SwapError::InvalidPrecision { decimals });
+        }
+        Decimal::from_str_exact(&amm::format_units(amount, decimals)).map_err(|_| SwapError::InvalidAmount)
+    }
+
+    /// `amount_out × (1 − slippage_bps / 10000)`.
+    pub fn min_amount_out(amount_out: Decimal, slippage_bps: u32) -> Result<Decimal, SwapError> {
+        if slippage_bps > MAX_SLIPPAGE_BPS {
+            return Err(SwapError::InvalidSlippage);
+        }
+        let max = Decimal::from(MAX_SLIPPAGE_BPS);
+        amount_out
+            .checked_mul(max - Decimal::from(slippage_bps))
+            .and_then(|scaled| scaled.checked_div(max))
+            .ok_or(SwapError::InvalidAmount)
+    }
+
+    /// `amount_in × (1 + slippage_bps / 10000)`.
+    pub fn max_amount_in(amount_in: Decimal, slippage_bps: u32) -> Result<Decimal, SwapError> {
+        if slippage_bps > MAX_SLIPPAGE_BPS {
+            return Err(SwapError::InvalidSlippage);
+        }
+        let max = Decimal::from(MAX_SLIPPAGE_BPS);
+        amount_in
+            .checked_mul(max + Decimal::from(slippage_bps))
+            .and_then(|scaled| scaled.checked_div(max))
+            .ok_or(SwapError::InvalidAmount)
+    }
+
+    /// Своп `amount_in` по цене исполнения `price`; выход округляется до decimals токена, как в `quote`.
+    /// Без `min_amount_out` — без проверки проскальзывания.
+    pub fn execute(
+        amount_in: Decimal,
+        price: Decimal,
+        min_amount_out: Option<Decimal>,
+        decimals_in: u8,
+        decimals_out: u8,
+    ) -> Result<Decimal, SwapError> {
+        let amount_out = Self::quote(SwapMode::ExactIn, amount_in, price, 0, decimals_in, decimals_out)?.amount_out;
+        match min_amount_out {
+            Some(min_amount_out) if amount_out < min_amount_out => {
+                Err(SwapError::SlippageExceeded { amount_out, min_amount_out })
+            }
+            _ => Ok(amount_out),
+        }
     }
 }
 
@@ -58,4 +295,128 @@ mod tests {
         let result = SwapEngine::get_quote(amount_in, price);
         assert!(matches!(result, Err(SwapError::InvalidPrice)));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_quote_min_amount_out() {
+        let quote = SwapEngine::quote(SwapMode::ExactIn, dec!(2), dec!(3000), 50, 18, 6).unwrap();
+        assert_eq!((quote.amount_in, quote.max_amount_in), (dec!(2), dec!(2)));
+        assert_eq!(quote.amount_out, dec!(6000));
+        assert_eq!(quote.min_amount_out, dec!(5970));
+        assert_eq!(SwapEngine::min_amount_out(dec!(6000), 0).unwrap(), dec!(6000));
+        assert_eq!(SwapEngine::min_amount_out(dec!(6000), MAX_SLIPPAGE_BPS).unwrap(), dec!(0));
+        assert!(matches!(SwapEngine::min_amount_out(dec!(6000), 10_001), Err(SwapError::InvalidSlippage)));
+    }
+
+    #[test]
+    fn test_execute_checks_min_amount_out() {
+        let quote = SwapEngine::quote(SwapMode::ExactIn, dec!(2), dec!(3000), 50, 18, 6).unwrap();
+        // Цена ушла на 0.4% — в пределах допуска
+        assert_eq!(SwapEngine::execute(dec!(2), dec!(2988), Some(quote.min_amount_out), 18, 6).unwrap(), dec!(5976));
+        // На 1% — своп отклоняется
+        let err = SwapEngine::execute(dec!(2), dec!(2970), Some(quote.min_amount_out), 18, 6).unwrap_err();
+        assert!(matches!(
+            err,
+            SwapError::SlippageExceeded { amount_out, min_amount_out } if amount_out == dec!(5940) && min_amount_out == dec!(5970)
+        ));
+        assert_eq!(err.to_string(), "Slippage exceeded: amount_out 5940 is below min_amount_out 5970");
+        assert_eq!(SwapEngine::execute(dec!(2), dec!(2970), None, 18, 6).unwrap(), dec!(5940));
+        // Выход — вниз до decimals токена, как в котировке
+        let quote = SwapEngine::quote(SwapMode::ExactIn, dec!(1), dec!(3000.1234567), 0, 18, 6).unwrap();
+        assert_eq!(SwapEngine::execute(dec!(1), dec!(3000.1234567), Some(quote.min_amount_out), 18, 6).unwrap(), dec!(3000.123456));
+        assert!(SwapEngine::execute(dec!(1.0000001), dec!(3000), None, 6, 6).is_err());
+    }
+
+    #[test]
+    fn test_exact_out_rounds_input_up() {
+        // 1000 / 3 = 333.33…3 — 18-й знак вверх, иначе 3 × вход < 1000
+        let amount_in = SwapEngine::get_quote_exact_out(dec!(1000), dec!(3)).unwrap();
+        assert_eq!(amount_in, dec!(333.333333333333333334));
+        assert!(amount_in * dec!(3) >= dec!(1000));
+        // Делится нацело — без округления
+        assert_eq!(SwapEngine::get_quote_exact_out(dec!(1000), dec!(2000)).unwrap(), dec!(0.5));
+        assert!(matches!(SwapEngine::get_quote_exact_out(dec!(0), dec!(3)), Err(SwapError::InvalidAmount)));
+        assert!(matches!(SwapEngine::get_quote_exact_out(dec!(1000), dec!(0)), Err(SwapError::InvalidPrice)));
+
+        let quote = SwapEngine::quote(SwapMode::ExactOut, dec!(1000), dec!(2000), 100, 18, 6).unwrap();
+        assert_eq!((quote.amount_in, quote.max_amount_in), (dec!(0.5), dec!(0.505)));
+        assert_eq!((quote.amount_out, quote.min_amount_out), (dec!(1000), dec!(1000)));
+
+        // Вход по кривой пула: вверх до decimals входа, предел — от него
+        let quote = SwapEngine::quote_exact_out_with_input(dec!(1000), dec!(0.5015045135406218651), 100, 18, 6).unwrap();
+        assert_eq!(quote.amount_in, dec!(0.501504513540621866));
+        assert_eq!(quote.max_amount_in, dec!(0.506519558676028085));
+        assert!(matches!(
+            SwapEngine::quote_exact_out_with_input(dec!(1000), dec!(0), 100, 18, 6),
+            Err(SwapError::InvalidAmount)
+        ));
+    }
+
+    #[test]
+    fn test_quote_rounds_to_token_decimals() {
+        // 1 ETH по 3000.1234567 → USDT (6 знаков): выход вниз
+        let quote = SwapEngine::quote(SwapMode::ExactIn, dec!(1), dec!(3000.1234567), 50, 18, 6).unwrap();
+        assert_eq!(quote.amount_out, dec!(3000.123456));
+        assert_eq!(quote.min_amount_out, dec!(2985.122838));
+        // Ровно 1000 USDT за WBTC (8 знаков) по 0.0000149: вход и его предел вверх
+        let quote = SwapEngine::quote(SwapMode::ExactOut, dec!(1000), dec!(67000), 30, 8, 6).unwrap();
+        assert_eq!(quote.amount_in, dec!(0.01492538));
+        assert_eq!(quote.max_amount_in, dec!(0.01497016));
+        assert!(quote.amount_in * dec!(67000) >= dec!(1000));
+
+        // Сумма точнее токена и выход, округлившийся в ноль
+        let err = SwapEngine::quote(SwapMode::ExactIn, dec!(0.123456789), dec!(3000), 50, 8, 6).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid amount: more than 8 decimal places");
+        assert!(SwapEngine::quote(SwapMode::ExactOut, dec!(1.0000001), dec!(3000), 50, 18, 6).is_err());
+        assert!(matches!(
+            SwapEngine::quote(SwapMode::ExactIn, dec!(0.00000001), dec!(0.0001), 50, 8, 6),
+            Err(SwapError::InvalidAmount)
+        ));
+    }
+
+    #[test]
+    fn test_base_units_round_trip() {
+        let wei = SwapEngine::to_base_units(dec!(1.5), 18, Rounding::Down).unwrap();
+        assert_eq!(wei, crate::amm::parse_units("1.5", 18).unwrap());
+        assert_eq!(SwapEngine::from_base_units(wei, 18).unwrap(), dec!(1.5));
+        assert_eq!(SwapEngine::to_base_units(dec!(1.2345678), 6, Rounding::Down).unwrap(), U256::from(1_234_567u64));
+        assert_eq!(SwapEngine::to_base_units(dec!(1.2345671), 6, Rounding::Up).unwrap(), U256::from(1_234_568u64));
+        assert_eq!(SwapEngine::from_base_units(U256::from(5u64), 3).unwrap(), dec!(0.005));
+        assert_eq!(SwapEngine::round_to_decimals(dec!(2.50), 6, Rounding::Up).unwrap().to_string(), "2.5");
+        assert!(SwapEngine::to_base_units(dec!(-1), 18, Rounding::Down).is_err());
+        assert!(SwapEngine::from_base_units(U256::MAX, 18).is_err());
+    }
+
+    #[test]
+    fn test_exact_out_overflow_is_an_error() {
+        // Цена со scale 28 и 28 знаков токена: 10^56 · 2^96 не помещается в U256
+        let price = Decimal::from_i128_with_scale(79_228_162_514_264_337_593_543_950_335, 28);
+        let amount_out = Decimal::from_i128_with_scale(79_228_162_514_264_337_593_543_950_335, 0);
+        assert!(matches!(
+            SwapEngine::quote(SwapMode::ExactOut, amount_out, price, 50, 28, 0),
+            Err(SwapError::InvalidAmount)
+        ));
+    }
+
+    #[test]
+    fn test_decimal_overflow_is_an_error() {
+        // Выход помещается в Decimal, а выход × (10000 − bps) — уже нет
+        assert!(matches!(
+            SwapEngine::quote(SwapMode::ExactIn, dec!(10000000000000000000000000), dec!(1), 50, 18, 6),
+            Err(SwapError::InvalidAmount)
+        ));
+        assert!(matches!(SwapEngine::get_quote(Decimal::MAX, dec!(2)), Err(SwapError::InvalidAmount)));
+        assert!(matches!(
+            SwapEngine::execute(Decimal::MAX, dec!(2), None, 0, 0),
+            Err(SwapError::InvalidAmount)
+        ));
+        // То же для предела входа exact-out
+        assert!(matches!(
+            SwapEngine::quote(SwapMode::ExactOut, dec!(10000000000000000000000000), dec!(1), 50, 0, 0),
+            Err(SwapError::InvalidAmount)
+        ));
+        assert!(matches!(
+            SwapEngine::quote_exact_out_with_input(dec!(1), Decimal::MAX, 50, 0, 0),
+            Err(SwapError::InvalidAmount)
+        ));
+    }
+}
@@ -0,0 +1,265 @@
+//! Реестр токенов: символ, decimals, сеть, адрес контракта, CoinGecko id и алиасы.
+//! Единая точка, где символ из запроса превращается в известный токен или отклоняется.
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenInfo {
+    pub symbol: String,
+    pub decimals: u8,
+    pub chain_id: u64,
+    /// Адрес контракта; `None` — нативная монета сети
+    #[serde(default)]
+    pub address: Option<String>,
+    #[serde(default)]
+    pub coingecko_id: Option<String>,
+    /// Другие написания того же токена, например BTC для WBTC
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    /// Тикер на бирже, если он отличается от символа: `{"kraken": "XBT"}`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub exchange_symbols: BTreeMap<String, String>,
+}
+
+impl TokenInfo {
+    pub fn new(symbol: &str, decimals: u8, chain_id: u64) -> Self {
+        Self {
+            symbol: symbol.to_ascii_uppercase(),
+            decimals,
+            chain_id,
+            address: None,
+            coingecko_id: None,
+            aliases: Vec::new(),
+            exchange_symbols: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_address(mut self, address: &str) -> Self {
+        self.address = Some(address.to_string());
+        self
+    }
+
+    pub fn with_coingecko_id(mut self, id: &str) -> Self {
+        self.coingecko_id = Some(id.to_string());
+        self
+    }
+
+    pub fn with_aliases(mut self, aliases: &[&str]) -> Self {
+        self.aliases = aliases.iter().map(|a| a.to_ascii_uppercase()).collect();
+        self
+    }
+
+    pub fn with_exchange_symbol(mut self, exchange: &str, symbol: &str) -> Self {
+        self.exchange_symbols.insert(exchange.to_ascii_lowercase(), symbol.to_ascii_uppercase());
+        self
+    }
+}
+
+/// Токены нескольких сетей. Символы и алиасы уникальны в пределах сети и ищутся без учёта
+/// регистра в сети развёртывания (`chain_id`): один и тот же USDT в Ethereum и BSC —
+/// разные токены с разными decimals.
+#[derive(Debug, Clone)]
+pub struct TokenRegistry {
+    tokens: Vec<TokenInfo>,
+    index: HashMap<(u64, String), usize>,
+    chain_id: u64,
+}
+
+impl Default for TokenRegistry {
+    /// Токены, которые котирует backend из коробки; сеть развёртывания — Ethereum.
+    fn default() -> Self {
+        Self::new(vec![
+            TokenInfo::new("ETH", 18, 1)
+                .with_coingecko_id("ethereum"),
+            TokenInfo::new("WETH", 18, 1)
+                .with_address("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2")
+                .with_coingecko_id("weth")
+                .with_exchange_symbol("binance", "ETH")
+                .with_exchange_symbol("kraken", "ETH"),
+            TokenInfo::new("WBTC", 8, 1)
+                .with_address("0x2260FAC5E5542a773Aa44fBCfeDf7C193bc2C599")
+                .with_coingecko_id("wrapped-bitcoin")
+                .with_aliases(&["BTC"])
+                .with_exchange_symbol("binance", "BTC")
+                .with_exchange_symbol("kraken", "XBT"),
+            TokenInfo::new("USDT", 6, 1)
+                .with_address("0xdAC17F958D2ee523a2206206994597C13D831ec7")
+                .with_coingecko_id("tether"),
+            TokenInfo::new("BNB", 18, 1)
+                .with_address("0xB8c77482e45F1F44dE1745F52C74426C631bDD52")
+                .with_coingecko_id("binancecoin"),
+            TokenInfo::new("BNB", 18, 56)
+                .with_coingecko_id("binancecoin"),
+            TokenInfo::new("WBNB", 18, 56)
+                .with_address("0xbb4CdB9CBd36B01bD1cBaEBF2De08d9173bc095c")
+                .with_coingecko_id("wbnb")
+                .with_exchange_symbol("binance", "BNB"),
+            TokenInfo::new("ETH", 18, 56)
+                .with_address("0x2170Ed0880ac9A755fd29B2688956BD959F933F8")
+                .with_coingecko_id("ethereum"),
+            TokenInfo::new("USDT", 18, 56)
+                .with_address("0x55d398326f99059fF775485246999027B3197955")
+                .with_coingecko_id("tether"),
+        ])
+        .expect("default token registry is consistent")
+    }
+}
+
+impl TokenRegistry {
+    /// Сеть развёртывания — сеть первого токена; другую выбирает `with_chain`.
+    pub fn new(mut tokens: Vec<TokenInfo>) -> Result<Self, String> {
+        let mut index = HashMap::new();
+        for (i, token) in tokens.iter_mut().enumerate() {
+            token.symbol = token.symbol.trim().to_ascii_uppercase();
+            token.aliases.iter_mut().for_each(|a| *a = a.trim().to_ascii_uppercase());
+            token.exchange_symbols = token.exchange_symbols.iter()
+                .map(|(exchange, symbol)| (exchange.trim().to_ascii_lowercase(), symbol.trim().to_ascii_uppercase()))
+                .collect();
+            if token.decimals > 18 {
+                return Err(format!("Token {} has {} decimals, at most 18 supported", token.symbol, token.decimals));
+            }
+            for name in std::iter::once(&token.symbol).chain(&token.aliases) {
+                if index.insert((token.chain_id, name.clone()), i).is_some() {
+                    return Err(format!("Duplicate token symbol or alias {name} on chain {}", token.chain_id));
+                }
+            }
+        }
+        let chain_id = tokens.first().map_or(1, |t| t.chain_id);
+        Ok(Self { tokens, index, chain_id })
+    }
+
+    /// JSON-массив `TokenInfo`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let raw = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read token registry {}: {e}", path.display()))?;
+        let tokens = serde_json::from_str(&raw)
+            .map_err(|e| format!("Invalid token registry {}: {e}", path.display()))?;
+        Self::new(tokens)
+    }
+
+    /// Другая сеть развёртывания; в ней должен быть хотя бы один токен.
+    pub fn with_chain(mut self, chain_id: u64) -> Result<Self, String> {
+        if !self.tokens.iter().any(|t| t.chain_id == chain_id) {
+            return Err(format!("No tokens on chain {chain_id}"));
+        }
+        self.chain_id = chain_id;
+        Ok(self)
+    }
+
+    pub fn chain_id(&self) -> u64 {
+        self.chain_id
+    }
+
+    /// Токен сети развёртывания.
+    pub fn get(&self, symbol: &str) -> Option<&TokenInfo> {
+        self.get_on(self.chain_id, symbol)
+    }
+
+    pub fn get_on(&self, chain_id: u64, symbol: &str) -> Option<&TokenInfo> {
+        self.index.get(&(chain_id, symbol.trim().to_ascii_uppercase())).map(|i| &self.tokens[*i])
+    }
+
+    /// Как `get`, но с ошибкой для неизвестного символа.
+    pub fn resolve(&self, symbol: &str) -> Result<&TokenInfo, String> {
+        self.get(symbol).ok_or_else(|| format!("Unknown token: {symbol}"))
+    }
+
+    /// Канонический символ (алиасы и регистр сводятся к `TokenInfo::symbol`).
+    pub fn canonical(&self, symbol: &str) -> Result<&str, String> {
+        self.resolve(symbol).map(|t| t.symbol.as_str())
+    }
+
+    /// Токены сети развёртывания.
+    pub fn tokens(&self) -> Vec<&TokenInfo> {
+        self.tokens.iter().filter(|t| t.chain_id == self.chain_id).collect()
+    }
+
+    /// Таблица символ/алиас → CoinGecko id для токенов сети развёртывания, у которых он задан.
+    pub fn coingecko_ids(&self) -> HashMap<String, String> {
+        self.index
+            .iter()
+            .filter(|((chain_id, _), _)| *chain_id == self.chain_id)
+            .filter_map(|((_, name), i)| self.tokens[*i].coingecko_id.clone().map(|id| (name.clone(), id)))
+            .collect()
+    }
+
+    /// Таблица символ/алиас → тикер биржи `exchange` для токенов сети развёртывания,
+    /// которые торгуются там под другим именем.
+    pub fn exchange_symbols(&self, exchange: &str) -> HashMap<String, String> {
+        self.index
+            .iter()
+            .filter(|((chain_id, _), _)| *chain_id == self.chain_id)
+            .filter_map(|((_, name), i)| self.tokens[*i].exchange_symbols.get(exchange).map(|s| (name.clone(), s.clone())))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_symbols_and_aliases() {
+        let registry = TokenRegistry::default();
+        assert_eq!(registry.canonical("eth").unwrap(), "ETH");
+        assert_eq!(registry.canonical("BTC").unwrap(), "WBTC");
+        assert_eq!(registry.resolve("wbtc").unwrap().decimals, 8);
+        assert_eq!(registry.resolve("ETHH"), Err("Unknown token: ETHH".to_string()));
+        // Нативная монета и её wrapped-версия — разные токены
+        assert_eq!(registry.canonical("weth").unwrap(), "WETH");
+        assert_eq!(registry.coingecko_ids().get("WETH").map(String::as_str), Some("weth"));
+        assert_eq!(registry.coingecko_ids().get("ETH").map(String::as_str), Some("ethereum"));
+        // Тикеры бирж — тоже из реестра, включая алиасы
+        let kraken = registry.exchange_symbols("kraken");
+        assert_eq!((kraken["BTC"].as_str(), kraken["WBTC"].as_str(), kraken["WETH"].as_str()), ("XBT", "XBT", "ETH"));
+        assert!(!kraken.contains_key("ETH"));
+    }
+
+    #[test]
+    fn test_same_symbol_on_different_chains() {
+        let registry = TokenRegistry::default();
+        assert_eq!(registry.chain_id(), 1);
+        assert_eq!(registry.resolve("USDT").unwrap().decimals, 6);
+        assert!(registry.get("WBNB").is_none());
+        assert_eq!(registry.get_on(56, "usdt").unwrap().decimals, 18);
+
+        let bsc = TokenRegistry::default().with_chain(56).unwrap();
+        assert_eq!(bsc.resolve("USDT").unwrap().decimals, 18);
+        assert_eq!(bsc.canonical("wbnb").unwrap(), "WBNB");
+        assert!(bsc.get("WBTC").is_none());
+        assert!(bsc.tokens().iter().all(|t| t.chain_id == 56));
+        assert!(TokenRegistry::default().with_chain(10).is_err());
+    }
+
+    #[test]
+    fn test_duplicates_rejected() {
+        let tokens = vec![
+            TokenInfo::new("WBTC", 8, 1).with_aliases(&["BTC"]),
+            TokenInfo::new("BTC", 8, 1),
+        ];
+        assert!(TokenRegistry::new(tokens).is_err());
+        // В разных сетях один символ допустим
+        let tokens = vec![TokenInfo::new("USDT", 6, 1), TokenInfo::new("USDT", 18, 56)];
+        assert!(TokenRegistry::new(tokens).is_ok());
+        assert!(TokenRegistry::new(vec![TokenInfo::new("X", 24, 1)]).is_err());
+    }
+
+    #[test]
+    fn test_load_registry_file() {
+        let path = std::env::temp_dir().join(format!("tokens_{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(&path, r#"[
+            {"symbol": "ARB", "decimals": 18, "chain_id": 42161,
+             "address": "0x912CE59144191C1204E64559FE8253a0e49E6548", "coingecko_id": "arbitrum",
+             "exchange_symbols": {"Kraken": "arb"}}
+        ]"#).unwrap();
+        let registry = TokenRegistry::load(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        let arb = registry.resolve("arb").unwrap();
+        assert_eq!(arb.chain_id, 42161);
+        assert!(arb.aliases.is_empty());
+        assert_eq!(registry.exchange_symbols("kraken")["ARB"], "ARB");
+        assert!(registry.get("ETH").is_none());
+    }
+}
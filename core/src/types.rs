@@ -1,6 +1,8 @@
 use serde::{Serialize, Deserialize};
 use uuid::Uuid;
 
+use crate::swap_engine::SwapMode;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AddOrderRequest {
     pub base: String,
@@ -17,14 +19,38 @@ pub struct DeleteOrderRequest {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SwapMockRequest {
+    /// `from`/`to` — имена полей в ранних клиентах
+    #[serde(alias = "from")]
+    pub from_token: String,
+    #[serde(alias = "to")]
+    pub to_token: String,
     pub amount_in: String,
-    pub price: String,
+    /// Минимальный выход из котировки; меньше — своп отклоняется
+    #[serde(default)]
+    pub min_amount_out: Option<String>,
+    /// Источник цены исполнения; без него — основной
+    #[serde(default)]
+    pub source: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QuoteQuery {
     pub from_token: String,
     pub to_token: String,
-    pub amount_in: String,
-    pub price: String,
+    /// Вход для `mode=exact_in`
+    #[serde(default)]
+    pub amount_in: Option<String>,
+    /// Выход для `mode=exact_out`
+    #[serde(default)]
+    pub amount_out: Option<String>,
+    #[serde(default)]
+    pub mode: SwapMode,
+    /// Цена клиента; без неё цена берётся из источника `source` (по умолчанию — основного)
+    #[serde(default)]
+    pub price: Option<String>,
+    #[serde(default)]
+    pub source: Option<String>,
+    /// Допуск проскальзывания для `min_amount_out`/`max_amount_in`, по умолчанию `DEFAULT_SLIPPAGE_BPS`
+    #[serde(default)]
+    pub slippage_bps: Option<u32>,
 }
\ No newline at end of file
@@ -1,12 +1,13 @@
 use smartswap_core::orderbook::OrderBook;
 use smartswap_core::types::AddOrderRequest;
 use smartswap_core::swap_engine::SwapEngine;
-use smartswap_core::price_source::PriceSource;
+use smartswap_core::tokens::TokenRegistry;
 
 #[test]
 fn e2e_add_and_execute_order() {
-    // Тест базового сценария: добавить, исполнить и удалить ордер
-    let mut ob = OrderBook::new();
+    // Тест базового сценария: добавить, исполнить и удалить ордер; WBNB есть только в BSC
+    let tokens = TokenRegistry::default().with_chain(56).unwrap();
+    let mut ob = OrderBook::with_registry(std::sync::Arc::new(tokens));
     let req = AddOrderRequest {
         base: "WBNB".into(),
         quote: "USDT".into(),
@@ -28,18 +29,56 @@ fn e2e_add_and_execute_order() {
 #[cfg(feature = "uniswap")]
 #[tokio::test]
 async fn test_pricing_uniswap_pancake() {
-    use smartswap_core::price_source::uniswap_v2::UniswapV2PriceSource;
-    use ethers::types::Address;
+    use smartswap_core::price_source::PriceSource;
+    use smartswap_core::pool_state::RpcPoolStateProvider;
+    use smartswap_core::price_source::uniswap_v2::{connect, UniswapV2PriceSource, DEFAULT_FEE_BPS};
+    use std::sync::Arc;
 
-    let source = UniswapV2PriceSource {
-        rpc_url: "https://bsc-dataseed.binance.org/".to_string(),
-        pool_address: "0x16b9a82891338f9ba80e2d6970fdda79d1eb0dae".parse::<Address>().unwrap(),
-        token0_symbol: "WBNB",
-        token1_symbol: "USDT",
-        decimals0: 18, // WBNB
-        decimals1: 18, // USDT (BSC)
-    };
+    let provider = connect("https://bsc-dataseed.binance.org/").unwrap();
+    let source = UniswapV2PriceSource::new(
+        Arc::new(RpcPoolStateProvider::new(provider)),
+        "0x16b9a82891338f9ba80e2d6970fdda79d1eb0dae",
+        "WBNB",
+        "USDT",
+        18, // WBNB
+        18, // USDT (BSC)
+    )
+    .with_fee_bps(DEFAULT_FEE_BPS)
+    .unwrap();
     let price = source.get_price("WBNB", "USDT", None).await
         .expect("Uniswap price fetch failed");
     assert!(price > 0.0, "PancakeSwap price should be > 0, got {}", price);
-} 
\ No newline at end of file
+} 
+#[tokio::test]
+async fn test_pricing_amm_from_fixture() {
+    use smartswap_core::pool_state::FilePoolStateProvider;
+    use smartswap_core::price_source::PriceSource;
+    use smartswap_core::price_source::uniswap_v2::UniswapV2PriceSource;
+    use smartswap_core::price_source::uniswap_v3::UniswapV3PriceSource;
+    use std::sync::Arc;
+
+    // Записанные снимки пулов: тест не ходит в сеть и детерминирован
+    let state = Arc::new(FilePoolStateProvider::load(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/pools.json")).unwrap());
+    let v2 = UniswapV2PriceSource::new(state.clone(), "0x16b9a82891338f9ba80e2d6970fdda79d1eb0dae", "WBNB", "USDT", 18, 18);
+    assert_eq!(v2.get_price("WBNB", "USDT", None).await.unwrap(), 600.0);
+    let historical = v2.spot_price_at("WBNB", "USDT", Some(40_000_000)).await.unwrap();
+    assert_eq!(historical, 550.0);
+    let effective = v2.get_price("WBNB", "USDT", Some("10")).await.unwrap();
+    assert!(effective < 600.0 * 0.997 && effective > 590.0);
+    // Exact-out: за ровно 6000 USDT нужно больше 10 WBNB (комиссия и impact)
+    let exact_out = v2.quote_exact_out("WBNB", "USDT", "6000").await.unwrap();
+    assert_eq!(exact_out.amount_out, smartswap_core::amm::parse_units("6000", 18).unwrap());
+    assert!(exact_out.effective_price < 600.0 * 0.997);
+    // Комиссия 100% отвергается при сборке источника, а не переполняет расчёт
+    let pool = "0x16b9a82891338f9ba80e2d6970fdda79d1eb0dae";
+    assert!(UniswapV2PriceSource::new(state.clone(), pool, "WBNB", "USDT", 18, 18).with_fee_bps(10_000).is_err());
+
+    let v3 = UniswapV3PriceSource::new(state, "0x88e6a0c2ddd26feeb64f039a2c41296fcb3f5640", "USDC", "WETH", 6, 18);
+    let spot = v3.get_price("WETH", "USDC", None).await.unwrap();
+    assert!((spot - 2000.0).abs() < 1e-6, "got {spot}");
+    let effective = v3.get_price("WETH", "USDC", Some("1")).await.unwrap();
+    assert!(effective < spot && effective > spot * 0.99);
+    let exact_out = v3.quote_exact_out("WETH", "USDC", "1000").await.unwrap();
+    let exact_in = v3.quote("WETH", "USDC", &smartswap_core::amm::format_units(exact_out.amount_in, 18)).await.unwrap();
+    assert!(exact_in.amount_out >= exact_out.amount_out);
+}